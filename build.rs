@@ -1,10 +1,18 @@
 extern crate walkdir;
 
+#[path = "src/packer.rs"]
+mod packer;
+
 use std::env;
+use std::fmt::Write;
 use std::fs::DirBuilder;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// Largest side of an atlas packed at build time
+const ATLAS_MAX_SIZE: u32 = 4096;
+const ATLAS_PADDING: u32 = 2;
+
 fn main() {
     let out_dir: PathBuf = std::env::var("OUT_DIR").unwrap().into();
     let manifest_dir: PathBuf = std::env::var("CARGO_MANIFEST_DIR").unwrap().into();
@@ -16,7 +24,11 @@ fn main() {
     copy(
         &manifest_dir.join("shaders"),
         &exe_path.join("shaders")
-    )
+    );
+    pack_atlases(
+        &manifest_dir.join("shaders"),
+        &exe_path.join("shaders")
+    );
 }
 
 fn locate_target(mut target_dir: &Path) -> Option<&Path> {
@@ -53,4 +65,64 @@ fn copy(from: &Path, to: &Path) {
         }
 
     }
-}
\ No newline at end of file
+}
+
+/// Packs the images of every `<name>.atlas` directory under `from` into
+/// `<name>.png` and the region list `<name>.atlas.ron`, next to the directory in `to`
+fn pack_atlases(from: &Path, to: &Path) {
+    for entry in WalkDir::new(from) {
+        let entry = entry.unwrap();
+        let dir = entry.path();
+        if !entry.file_type().is_dir() || dir.extension().is_none_or(|ext| ext != "atlas") {
+            continue;
+        }
+
+        let mut images = Vec::new();
+        for file in WalkDir::new(dir).min_depth(1).max_depth(1).sort_by_file_name() {
+            let file = file.unwrap();
+            let is_image = file
+                .path()
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .is_some_and(|ext| ext == "png" || ext == "jpg" || ext == "jpeg");
+            if file.file_type().is_file() && is_image {
+                let image = image::open(file.path())
+                    .unwrap_or_else(|e| panic!("failed to decode {}: {}", file.path().display(), e))
+                    .to_rgba8();
+                let name = file.path().file_stem().unwrap().to_string_lossy().into_owned();
+                images.push((name, image));
+            }
+        }
+
+        let sizes: Vec<(u32, u32)> = images.iter().map(|(_, image)| image.dimensions()).collect();
+        let packing = packer::pack(&sizes, ATLAS_MAX_SIZE, ATLAS_PADDING)
+            .unwrap_or_else(|| panic!("{} does not fit in a {}x{} atlas", dir.display(), ATLAS_MAX_SIZE, ATLAS_MAX_SIZE));
+
+        let stem = dir.file_stem().unwrap().to_string_lossy().into_owned();
+        let mut atlas = image::RgbaImage::new(packing.width, packing.height);
+        let mut regions = String::new();
+        for ((name, image), (x, y)) in images.iter().zip(packing.positions) {
+            image::imageops::replace(&mut atlas, image, x as i64, y as i64);
+            writeln!(
+                regions,
+                "        {:?}: (x: {}, y: {}, width: {}, height: {}),",
+                name, x, y, image.width(), image.height()
+            ).unwrap();
+        }
+
+        let target_dir = to.join(dir.parent().unwrap().strip_prefix(from).unwrap());
+        DirBuilder::new()
+            .recursive(true)
+            .create(&target_dir)
+            .expect("failed to create target dir");
+        atlas
+            .save(target_dir.join(format!("{}.png", stem)))
+            .expect("failed to write atlas image");
+        let desc = format!(
+            "(\n    image: \"{}.png\",\n    regions: {{\n{}    }},\n)\n",
+            stem, regions
+        );
+        std::fs::write(target_dir.join(format!("{}.atlas.ron", stem)), desc)
+            .expect("failed to write atlas description");
+    }
+}
@@ -0,0 +1,529 @@
+// Colour type and parser shared by the renderer and the `ogl_main` macro,
+// which parses `bg_color` with it at compile time.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Encoding of colour components
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Proportional to light intensity, what shaders and blending work in
+    Linear,
+    /// Gamma encoded, what colour pickers, hex codes and CSS use
+    Srgb,
+}
+
+/// RGBA colour with linear components and straight alpha.
+///
+/// Laid out as four floats, so it can be used directly as a vertex attribute
+/// or a `vec4` uniform. Parses from `#RRGGBB[AA]`, `rgb(r, g, b)`,
+/// `rgba(r, g, b, a)`, CSS colour names and `R G B [A]` floats, all sRGB
+/// unless prefixed with `linear`, e.g. `"linear 0.2 0.2 0.2"`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+/// Why a string is not a colour
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseColorError {
+    Empty,
+    InvalidHex(String),
+    InvalidNumber(String),
+    ComponentCount { expected: &'static str, found: usize },
+    OutOfRange { value: f32, max: f32 },
+    UnknownName(String),
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseColorError::Empty => write!(f, "empty colour"),
+            ParseColorError::InvalidHex(hex) => {
+                write!(f, "invalid hex colour `{}`, expected `#RRGGBB` or `#RRGGBBAA`", hex)
+            }
+            ParseColorError::InvalidNumber(number) => {
+                write!(f, "invalid colour component `{}`", number)
+            }
+            ParseColorError::ComponentCount { expected, found } => {
+                write!(f, "expected {} colour components, found {}", expected, found)
+            }
+            ParseColorError::OutOfRange { value, max } => {
+                write!(f, "colour component {} is outside 0..={}", value, max)
+            }
+            ParseColorError::UnknownName(name) => write!(f, "unknown colour name `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// sRGB transfer function, from encoded to linear
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Inverse sRGB transfer function, from linear to encoded
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+
+    /// Colour from linear components
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    /// Opaque colour from linear components
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Color::rgba(r, g, b, 1.0)
+    }
+
+    /// Colour from sRGB-encoded components, e.g. picked in an image editor
+    pub fn srgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color::rgba(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// Opaque colour from sRGB-encoded components
+    pub fn srgb(r: f32, g: f32, b: f32) -> Self {
+        Color::srgba(r, g, b, 1.0)
+    }
+
+    /// Colour from 8-bit sRGB components
+    pub fn srgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let unit = |c: u8| c as f32 / 255.0;
+        Color::srgba(unit(r), unit(g), unit(b), unit(a))
+    }
+
+    /// Colour from `[r, g, b, a]` encoded in `space`
+    pub fn from_array(components: [f32; 4], space: ColorSpace) -> Self {
+        let [r, g, b, a] = components;
+        match space {
+            ColorSpace::Linear => Color::rgba(r, g, b, a),
+            ColorSpace::Srgb => Color::srgba(r, g, b, a),
+        }
+    }
+
+    /// Linear `[r, g, b, a]`
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// `[r, g, b, a]` encoded in `space`; alpha is never encoded
+    pub fn to_space(self, space: ColorSpace) -> [f32; 4] {
+        match space {
+            ColorSpace::Linear => self.to_array(),
+            ColorSpace::Srgb => [
+                linear_to_srgb(self.r),
+                linear_to_srgb(self.g),
+                linear_to_srgb(self.b),
+                self.a,
+            ],
+        }
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Color { a, ..self }
+    }
+
+    /// Colour with the components multiplied by alpha, for premultiplied blending
+    pub fn premultiplied(self) -> Self {
+        Color::rgba(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    /// Linear interpolation in linear space
+    pub fn lerp(self, rhs: Color, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Color::rgba(
+            mix(self.r, rhs.r),
+            mix(self.g, rhs.g),
+            mix(self.b, rhs.b),
+            mix(self.a, rhs.a),
+        )
+    }
+
+    /// CSS colour by name, ignoring case
+    pub fn named(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Color::TRANSPARENT);
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, rgb)| Color::srgba8((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 255))
+    }
+
+    /// Parses any of the forms described on `Color`
+    pub fn parse(s: &str) -> Result<Self, ParseColorError> {
+        let s = s.trim();
+        let (space, body) = match s.split_once(char::is_whitespace) {
+            Some((tag, rest)) if tag.eq_ignore_ascii_case("linear") => {
+                (ColorSpace::Linear, rest.trim())
+            }
+            Some((tag, rest)) if tag.eq_ignore_ascii_case("srgb") => {
+                (ColorSpace::Srgb, rest.trim())
+            }
+            _ => (ColorSpace::Srgb, s),
+        };
+        if body.is_empty() {
+            return Err(ParseColorError::Empty);
+        }
+
+        let lower = body.to_ascii_lowercase();
+        let components = if let Some(hex) = lower.strip_prefix('#') {
+            parse_hex(hex).ok_or_else(|| ParseColorError::InvalidHex(body.to_string()))?
+        } else if let Some(args) = function_args(&lower, "rgba") {
+            parse_rgb(args, true)?
+        } else if let Some(args) = function_args(&lower, "rgb") {
+            parse_rgb(args, false)?
+        } else if lower.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '.' | '-' | '+')) {
+            parse_floats(&lower)?
+        } else {
+            return Color::named(&lower)
+                .map(|color| match space {
+                    // Named colours are sRGB by definition; `linear` keeps the numbers
+                    ColorSpace::Linear => Color::from_array(color.to_space(ColorSpace::Srgb), space),
+                    ColorSpace::Srgb => color,
+                })
+                .ok_or_else(|| ParseColorError::UnknownName(body.to_string()));
+        };
+        Ok(Color::from_array(components, space))
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::parse(s)
+    }
+}
+
+/// `RRGGBB` or `RRGGBBAA` to unit components
+fn parse_hex(hex: &str) -> Option<[f32; 4]> {
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+    let alpha = if hex.len() == 8 { byte(3)? } else { 255 };
+    Some([byte(0)?, byte(1)?, byte(2)?, alpha].map(|c| c as f32 / 255.0))
+}
+
+/// Arguments of `name(...)`, if `s` is a call to `name`
+fn function_args<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    s.strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+}
+
+fn split_components(s: &str) -> Vec<&str> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// Number within `0..=max`, or a percentage of `max`
+fn parse_component(s: &str, max: f32) -> Result<f32, ParseColorError> {
+    let invalid = || ParseColorError::InvalidNumber(s.to_string());
+    let (value, max) = match s.strip_suffix('%') {
+        Some(percent) => (percent.parse::<f32>().map_err(|_| invalid())?, 100.0),
+        None => (s.parse::<f32>().map_err(|_| invalid())?, max),
+    };
+    if !(0.0..=max).contains(&value) {
+        return Err(ParseColorError::OutOfRange { value, max });
+    }
+    Ok(value / max)
+}
+
+/// CSS `rgb()` arguments: red, green and blue in 0..=255 and alpha in 0..=1,
+/// or percentages
+fn parse_rgb(args: &str, alpha: bool) -> Result<[f32; 4], ParseColorError> {
+    let components = split_components(args);
+    let expected = if alpha { 4 } else { 3 };
+    if components.len() != expected {
+        return Err(ParseColorError::ComponentCount {
+            expected: if alpha { "4" } else { "3" },
+            found: components.len(),
+        });
+    }
+    let mut rgba = [1.0; 4];
+    for (i, component) in components.iter().enumerate() {
+        rgba[i] = parse_component(component, if i == 3 { 1.0 } else { 255.0 })?;
+    }
+    Ok(rgba)
+}
+
+/// `R G B [A]` in 0..=1
+fn parse_floats(s: &str) -> Result<[f32; 4], ParseColorError> {
+    let components = split_components(s);
+    if !(3..=4).contains(&components.len()) {
+        return Err(ParseColorError::ComponentCount {
+            expected: "3 or 4",
+            found: components.len(),
+        });
+    }
+    let mut rgba = [1.0; 4];
+    for (i, component) in components.iter().enumerate() {
+        rgba[i] = parse_component(component, 1.0)?;
+    }
+    Ok(rgba)
+}
+
+/// CSS Color Module Level 4 named colours as `0xRRGGBB`
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn parses_hex() {
+        let color = Color::parse("#FF8000").unwrap();
+        assert_close(color.to_space(ColorSpace::Srgb), [1.0, 128.0 / 255.0, 0.0, 1.0]);
+        let color = Color::parse("#00000080").unwrap();
+        assert_close(color.to_array(), [0.0, 0.0, 0.0, 128.0 / 255.0]);
+        assert_eq!(Color::parse("#12345"), Err(ParseColorError::InvalidHex("#12345".into())));
+        assert_eq!(Color::parse("#gg0000"), Err(ParseColorError::InvalidHex("#gg0000".into())));
+    }
+
+    #[test]
+    fn parses_float_triples() {
+        assert_eq!(Color::parse("linear 0.2 0.4 0.6"), Ok(Color::rgb(0.2, 0.4, 0.6)));
+        assert_eq!(Color::parse("linear .5, 0, 1, 0.25"), Ok(Color::rgba(0.5, 0.0, 1.0, 0.25)));
+        let color = Color::parse("0.5 0 1").unwrap();
+        assert_close(color.to_space(ColorSpace::Srgb), [0.5, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            Color::parse("0.5 0"),
+            Err(ParseColorError::ComponentCount { expected: "3 or 4", found: 2 })
+        );
+    }
+
+    #[test]
+    fn parses_rgb_functions() {
+        assert_close(
+            Color::parse("rgba(255, 0, 50%, 0.5)").unwrap().to_space(ColorSpace::Srgb),
+            [1.0, 0.0, 0.5, 0.5],
+        );
+        assert!(Color::parse("rgb(0, 0, 0, 0)").is_err());
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(Color::parse("White"), Ok(Color::WHITE));
+        assert_eq!(Color::parse("transparent"), Ok(Color::TRANSPARENT));
+        assert_close(
+            Color::parse("rebeccapurple").unwrap().to_space(ColorSpace::Srgb),
+            [0x66 as f32 / 255.0, 0x33 as f32 / 255.0, 0x99 as f32 / 255.0, 1.0],
+        );
+        assert_eq!(Color::parse("nope"), Err(ParseColorError::UnknownName("nope".into())));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(Color::parse("  "), Err(ParseColorError::Empty));
+        assert_eq!(
+            Color::parse("-0.1 0 0"),
+            Err(ParseColorError::OutOfRange { value: -0.1, max: 1.0 })
+        );
+        assert_eq!(
+            Color::parse("+2 0 0"),
+            Err(ParseColorError::OutOfRange { value: 2.0, max: 1.0 })
+        );
+        assert_eq!(
+            Color::parse("rgb(256, 0, 0)"),
+            Err(ParseColorError::OutOfRange { value: 256.0, max: 255.0 })
+        );
+        assert_eq!(
+            Color::parse("0.5 x 0"),
+            Err(ParseColorError::InvalidNumber("x".into()))
+        );
+    }
+
+    #[test]
+    fn srgb_round_trips_through_linear() {
+        for i in 0..=255 {
+            let c = i as f32 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5, "{}", c);
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214_041).abs() < 1e-5);
+
+        let color = Color::srgba8(12, 128, 250, 64);
+        let back = Color::from_array(color.to_space(ColorSpace::Srgb), ColorSpace::Srgb);
+        assert_close(back.to_array(), color.to_array());
+    }
+}
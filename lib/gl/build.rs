@@ -5,7 +5,7 @@ use std::path::Path;
 
 fn main() {
     let out_dir = env::var("OUT_DIR").unwrap();
-    let mut file_gl = File::create(&Path::new(&out_dir).join("bindings.rs")).unwrap();
+    let mut file_gl = File::create(Path::new(&out_dir).join("bindings.rs")).unwrap();
 
     let registry = Registry::new(
         Api::Gl,
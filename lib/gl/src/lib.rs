@@ -1,3 +1,5 @@
+// Generated by gl_generator, not worth linting
+#[allow(clippy::all)]
 mod bindings {
     include!(concat!(env!("OUT_DIR"), "\\bindings.rs"));
 }
@@ -1,13 +1,14 @@
 use proc_macro::TokenStream;
 
 use quote::quote;
+
 mod options;
 
 macro_rules! quote_vec {
     ($($x:stmt;)*) => {
         vec![
             $(
-                syn::parse_quote!($x),
+                syn::parse_quote!($x;),
             )*
         ]
     };
@@ -17,7 +18,28 @@ macro_rules! quote_vec {
 /// for the OpenGL applications.
 /// The `main` function is responsible for initializing the OpenGL context
 /// and setting up the window.
+/// The using crate needs `sdl2`, `gl` and `ogl_runtime` as dependencies.
+///
+/// The function body can use `sdl`, `video_subsystem`, `window`,
+/// `gl_context` and `gl`, e.g. to hand them to `app::run`, and `config`,
+/// the `ogl_runtime::config::WindowConfig` the window was created with.
 ///
+/// The options are defaults: `WindowConfig` overrides them at startup
+/// from a `config.toml` file and command-line flags, exiting with a message
+/// on invalid or unknown settings. Positional arguments are left to `main`.
+///
+/// # Options
+/// * `title = "..."`, `window = "WIDTHxHEIGHT"`, `bg_color = "..."`
+/// * `bg_color` takes `"#RRGGBB[AA]"`, `"rgb(r, g, b)"`, `"rgba(r, g, b, a)"`,
+///   a CSS colour name or `"R G B [A]"` floats, all sRGB unless prefixed with `linear`
+/// * `gl_version = "4.5"`, `profile = "core" | "compatibility" | "es"`
+/// * `forward_compatible = bool`, `debug = bool` - context flags
+/// * `vsync = "on" | "off" | "adaptive"`, falling back to on without adaptive support
+/// * `msaa = 0 | 2 | 4 | 8 | 16`, `depth_bits = 0 | 16 | 24 | 32`, `stencil_bits = 0 | 8`
+/// * `srgb = bool` - sRGB-capable default framebuffer, with `GL_FRAMEBUFFER_SRGB` enabled
+/// * `fullscreen = "off" | "exclusive" | "desktop"`, `borderless = bool`, `resizable = bool`
+/// * `high_dpi = bool`, `min_size = "WIDTHxHEIGHT"`
+/// * `icon = "path.bmp"` - relative to the crate root, embedded at compile time
 #[proc_macro_attribute]
 pub fn ogl_main(attr: TokenStream, item: TokenStream) -> TokenStream {
     let attr = syn::parse_macro_input!(attr as syn::AttributeArgs);
@@ -26,7 +48,16 @@ pub fn ogl_main(attr: TokenStream, item: TokenStream) -> TokenStream {
     let og_stmts = function.block.stmts.clone();
     function.block.stmts = Vec::new();
 
-    options::parse_options(attr, &mut function);
+    if let Err(error) = options::parse_options(attr, &mut function) {
+        // Keep an empty `main` so only the option errors get reported
+        let errors = error.to_compile_error();
+        function.block.stmts = Vec::new();
+        return quote! {
+            #errors
+            #function
+        }
+        .into();
+    }
 
     function.block.stmts.extend(quote_vec![
         // Initialize SDL
@@ -35,23 +66,68 @@ pub fn ogl_main(attr: TokenStream, item: TokenStream) -> TokenStream {
         // Setup GL attributes
         let gl_attr = video_subsystem.gl_attr();
 
-        gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
-        gl_attr.set_context_version(4, 5);
+        gl_attr.set_context_profile(gl_profile);
+        gl_attr.set_context_version(gl_version.0, gl_version.1);
+        let mut gl_flags = gl_attr.set_context_flags();
+        if gl_forward_compatible {
+            gl_flags.forward_compatible();
+        };
+        if gl_debug {
+            gl_flags.debug();
+        };
+        gl_flags.set();
+        gl_attr.set_depth_size(depth_bits);
+        gl_attr.set_stencil_size(stencil_bits);
+        if msaa_samples > 0 {
+            gl_attr.set_multisample_buffers(1);
+            gl_attr.set_multisample_samples(msaa_samples);
+        };
+        gl_attr.set_framebuffer_srgb_compatible(srgb);
 
         // Create a window
-        let window = video_subsystem
-            .window(title, window_width as u32, window_height as u32)
-            .opengl() // Setup window to receive GL context
-            .resizable()
-            .build()
-            .unwrap();
+        let mut window_builder = video_subsystem.window(title, window_width, window_height);
+        window_builder.opengl(); // Setup window to receive GL context
+        if resizable {
+            window_builder.resizable();
+        };
+        if borderless {
+            window_builder.borderless();
+        };
+        if high_dpi {
+            window_builder.allow_highdpi();
+        };
+        match fullscreen {
+            ::ogl_runtime::config::Fullscreen::Exclusive => window_builder.fullscreen(),
+            ::ogl_runtime::config::Fullscreen::Desktop => window_builder.fullscreen_desktop(),
+            ::ogl_runtime::config::Fullscreen::Off => &mut window_builder,
+        };
+        let mut window = window_builder.build().unwrap();
+        if let Some((min_width, min_height)) = min_size {
+            window.set_minimum_size(min_width, min_height).unwrap();
+        };
+        if let Some(icon) = window_icon {
+            let mut icon = sdl2::rwops::RWops::from_bytes(icon).unwrap();
+            window.set_icon(sdl2::surface::Surface::load_bmp_rw(&mut icon).unwrap());
+        };
 
         // Create an GL context
         let gl_context = window.gl_create_context().unwrap();
         let gl = gl::Gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void);
+        // Adaptive vsync is not supported everywhere, fall back to regular vsync
+        if video_subsystem.gl_set_swap_interval(vsync).is_err() {
+            video_subsystem.gl_set_swap_interval(sdl2::video::SwapInterval::VSync).unwrap();
+        };
+        // The drawable can be larger than the window on high-DPI displays
+        let (drawable_width, drawable_height) = window.drawable_size();
         unsafe {
-            gl.Viewport(0, 0, window_width, window_height);
+            gl.Viewport(0, 0, drawable_width as i32, drawable_height as i32);
             gl.ClearColor(bg_color[0], bg_color[1], bg_color[2], bg_color[3]);
+            if msaa_samples > 0 {
+                gl.Enable(gl::MULTISAMPLE);
+            }
+            if srgb {
+                gl.Enable(gl::FRAMEBUFFER_SRGB);
+            }
         };
     ]);
     function.block.stmts.extend(og_stmts);
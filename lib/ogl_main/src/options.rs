@@ -1,75 +1,531 @@
+use color::Color;
+use proc_macro2::Span;
 use quote::quote;
 use regex::bytes::Regex;
+use std::path::PathBuf;
+use syn::spanned::Spanned;
 use syn::{AttributeArgs, ItemFn, Lit, Meta, MetaNameValue, NestedMeta};
 
-pub fn parse_options(attr: AttributeArgs, function: &mut ItemFn) {
-    let mut title: String = "OpenGL Application".into();
-    let mut width: i32 = 900;
-    let mut height: i32 = 700;
-    let mut bg_color: Vec<f32> = vec![0.0, 0.0, 0.0, 1.0];
-
-    attr.iter().for_each(|a: &NestedMeta| match a {
-        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
-            path,
-            lit,
-            eq_token: _,
-        })) => match path.segments.first().unwrap().ident.to_string().as_str() {
+/// OpenGL context versions that exist, per profile family
+const GL_VERSIONS: [(u8, u8); 12] = [
+    (2, 1),
+    (3, 0), (3, 1), (3, 2), (3, 3),
+    (4, 0), (4, 1), (4, 2), (4, 3), (4, 4), (4, 5), (4, 6),
+];
+const GLES_VERSIONS: [(u8, u8); 4] = [(2, 0), (3, 0), (3, 1), (3, 2)];
+
+/// Every option `#[ogl_main]` accepts
+const OPTION_NAMES: [&str; 18] = [
+    "title",
+    "window",
+    "bg_color",
+    "gl_version",
+    "profile",
+    "forward_compatible",
+    "debug",
+    "vsync",
+    "msaa",
+    "depth_bits",
+    "stencil_bits",
+    "srgb",
+    "fullscreen",
+    "borderless",
+    "resizable",
+    "high_dpi",
+    "min_size",
+    "icon",
+];
+
+/// Window and context settings read from the `#[ogl_main]` attribute
+struct Options {
+    title: String,
+    width: u32,
+    height: u32,
+    bg_color: Color,
+    gl_version: (u8, u8),
+    profile: String,
+    forward_compatible: bool,
+    debug: bool,
+    vsync: String,
+    msaa: u8,
+    depth_bits: u8,
+    stencil_bits: u8,
+    srgb: bool,
+    fullscreen: String,
+    borderless: bool,
+    resizable: bool,
+    high_dpi: bool,
+    min_size: Option<(u32, u32)>,
+    icon: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            title: "OpenGL Application".into(),
+            width: 900,
+            height: 700,
+            bg_color: Color::BLACK,
+            gl_version: (4, 5),
+            profile: "core".into(),
+            forward_compatible: false,
+            debug: false,
+            vsync: "on".into(),
+            msaa: 0,
+            depth_bits: 24,
+            stencil_bits: 8,
+            srgb: false,
+            fullscreen: "off".into(),
+            borderless: false,
+            resizable: true,
+            high_dpi: false,
+            min_size: None,
+            icon: None,
+        }
+    }
+}
+
+/// Errors of every option, reported together
+#[derive(Default)]
+struct Errors {
+    error: Option<syn::Error>,
+}
+
+impl Errors {
+    fn push(&mut self, error: syn::Error) {
+        match &mut self.error {
+            Some(errors) => errors.combine(error),
+            None => self.error = Some(error),
+        }
+    }
+
+    /// Keeps the value of `result`, or records its error
+    fn check<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+}
+
+fn expect_bool(name: &str, lit: &Lit) -> syn::Result<bool> {
+    match lit {
+        Lit::Bool(value) => Ok(value.value),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            format!("expected `true` or `false` for `{}`", name),
+        )),
+    }
+}
+
+fn expect_str(name: &str, lit: &Lit) -> syn::Result<String> {
+    match lit {
+        Lit::Str(value) => Ok(value.value()),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            format!("expected a string literal for `{}`", name),
+        )),
+    }
+}
+
+fn expect_u8(name: &str, lit: &Lit, allowed: &[u8]) -> syn::Result<u8> {
+    let value = match lit {
+        Lit::Int(value) => value.base10_parse::<u8>().ok(),
+        _ => {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!("expected an integer literal for `{}`", name),
+            ))
+        }
+    };
+    match value {
+        Some(value) if allowed.contains(&value) => Ok(value),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            format!("expected one of {:?} for `{}`", allowed, name),
+        )),
+    }
+}
+
+fn expect_one_of(name: &str, lit: &Lit, allowed: &[&str]) -> syn::Result<String> {
+    let value = expect_str(name, lit)?;
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(syn::Error::new(
+            lit.span(),
+            format!("expected one of {:?} for `{}`, found {:?}", allowed, name, value),
+        ))
+    }
+}
+
+fn parse_size(name: &str, lit: &Lit) -> syn::Result<(u32, u32)> {
+    let dimensions = expect_str(name, lit)?;
+    let invalid = || {
+        syn::Error::new(
+            lit.span(),
+            format!("expected `WIDTHxHEIGHT` for `{}`, e.g. \"800x600\"", name),
+        )
+    };
+
+    if !Regex::new(r"^\d+x\d+$")
+        .unwrap()
+        .is_match(dimensions.as_ref())
+    {
+        return Err(invalid());
+    }
+
+    let mut v = dimensions.split('x');
+    let width = v.next().unwrap().parse::<u32>().map_err(|_| invalid())?;
+    let height = v.next().unwrap().parse::<u32>().map_err(|_| invalid())?;
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!("`{}` must be at least 1x1", name),
+        ));
+    }
+    Ok((width, height))
+}
+
+fn parse_color(lit: &Lit) -> syn::Result<Color> {
+    let color = expect_str("bg_color", lit)?;
+    Color::parse(&color).map_err(|error| {
+        syn::Error::new(lit.span(), format!("invalid `bg_color`: {}", error))
+    })
+}
+
+fn parse_version(lit: &Lit) -> syn::Result<(u8, u8)> {
+    let version = expect_str("gl_version", lit)?;
+    if !Regex::new(r"^\d\.\d$").unwrap().is_match(version.as_ref()) {
+        return Err(syn::Error::new(
+            lit.span(),
+            "expected `MAJOR.MINOR` for `gl_version`, e.g. \"4.5\"",
+        ));
+    }
+
+    let mut v = version.split('.');
+    let major = v.next().unwrap().parse::<u8>().unwrap();
+    let minor = v.next().unwrap().parse::<u8>().unwrap();
+    Ok((major, minor))
+}
+
+/// Levenshtein distance, for suggesting option names
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn unknown_option(path: &syn::Path, name: &str) -> syn::Error {
+    let closest = OPTION_NAMES
+        .iter()
+        .map(|option| (edit_distance(name, option), option))
+        .min()
+        .filter(|(distance, _)| *distance <= 3);
+    let message = match closest {
+        Some((_, option)) => format!("unknown option `{}`, did you mean `{}`?", name, option),
+        None => format!(
+            "unknown option `{}`, expected one of: {}",
+            name,
+            OPTION_NAMES.join(", ")
+        ),
+    };
+    syn::Error::new(path.span(), message)
+}
+
+/// Reads the attribute options and declares them as local variables at the
+/// start of `function`. Every invalid option is reported, each spanned on
+/// the offending name or literal.
+pub fn parse_options(attr: AttributeArgs, function: &mut ItemFn) -> syn::Result<()> {
+    let mut options = Options::default();
+    let mut errors = Errors::default();
+    let mut version_span = Span::call_site();
+    let mut fullscreen_span = Span::call_site();
+
+    for a in &attr {
+        let (path, lit) = match a {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. })) => (path, lit),
+            _ => {
+                errors.push(syn::Error::new(
+                    a.span(),
+                    "expected an option of the form `name = value`",
+                ));
+                continue;
+            }
+        };
+        let name = path
+            .get_ident()
+            .map(|ident| ident.to_string())
+            .unwrap_or_default();
+
+        match name.as_str() {
             "title" => {
-                title = match lit {
-                    Lit::Str(value) => value.value(),
-                    _ => panic!("Expected string literal for title"),
-                };
+                if let Some(title) = errors.check(expect_str("title", lit)) {
+                    options.title = title;
+                }
             }
 
             "window" => {
-                match lit {
-                    Lit::Str(value) => {
-                        let dimensions = value.value();
-
-                        assert!(
-                            Regex::new(r"\d+x\d+")
-                                .unwrap()
-                                .is_match(dimensions.as_ref()),
-                            "Expected format: WIDTHxHEIGHT"
-                        );
-
-                        let mut v = dimensions.split('x');
-                        width = v.next().unwrap().parse::<i32>().unwrap();
-                        height = v.next().unwrap().parse::<i32>().unwrap();
-                    }
-                    _ => panic!("Expected string for window options"),
-                };
+                if let Some((width, height)) = errors.check(parse_size("window", lit)) {
+                    options.width = width;
+                    options.height = height;
+                }
             }
 
-            "bg_color" => match lit {
-                Lit::Str(value) => {
-                    let color = value.value();
+            "bg_color" => {
+                if let Some(color) = errors.check(parse_color(lit)) {
+                    options.bg_color = color;
+                }
+            }
 
-                    assert!(Regex::new(r"(\d+(\.\d+)? ){3}(\d+(\.\d+)?)")
-                        .unwrap()
-                        .is_match(color.as_ref()));
+            "gl_version" => {
+                version_span = lit.span();
+                if let Some(version) = errors.check(parse_version(lit)) {
+                    options.gl_version = version;
+                }
+            }
 
-                    let mut v = color.split(' ');
-                    let r = v.next().unwrap().parse::<f32>().unwrap();
-                    let g = v.next().unwrap().parse::<f32>().unwrap();
-                    let b = v.next().unwrap().parse::<f32>().unwrap();
-                    let a = v.next().unwrap().parse::<f32>().unwrap();
-                    bg_color = vec![r, g, b, a];
+            "profile" => {
+                if let Some(profile) =
+                    errors.check(expect_one_of("profile", lit, &["core", "compatibility", "es"]))
+                {
+                    options.profile = profile;
                 }
-                _ => panic!("Expected string for bg_color options"),
-            },
+            }
 
-            attr => panic!("Unknown attribute: {}", attr),
-        },
-        _ => panic!("Could not undertand attribute"),
-    });
+            "forward_compatible" => {
+                if let Some(value) = errors.check(expect_bool("forward_compatible", lit)) {
+                    options.forward_compatible = value;
+                }
+            }
 
-    let bg_color = quote! {
-        [#(#bg_color),*]
-    };
+            "debug" => {
+                if let Some(value) = errors.check(expect_bool("debug", lit)) {
+                    options.debug = value;
+                }
+            }
+
+            "vsync" => {
+                if let Some(vsync) =
+                    errors.check(expect_one_of("vsync", lit, &["on", "off", "adaptive"]))
+                {
+                    options.vsync = vsync;
+                }
+            }
+
+            "msaa" => {
+                if let Some(samples) = errors.check(expect_u8("msaa", lit, &[0, 2, 4, 8, 16])) {
+                    options.msaa = samples;
+                }
+            }
+
+            "depth_bits" => {
+                if let Some(bits) = errors.check(expect_u8("depth_bits", lit, &[0, 16, 24, 32])) {
+                    options.depth_bits = bits;
+                }
+            }
+
+            "stencil_bits" => {
+                if let Some(bits) = errors.check(expect_u8("stencil_bits", lit, &[0, 8])) {
+                    options.stencil_bits = bits;
+                }
+            }
+
+            "srgb" => {
+                if let Some(value) = errors.check(expect_bool("srgb", lit)) {
+                    options.srgb = value;
+                }
+            }
+
+            "fullscreen" => {
+                fullscreen_span = lit.span();
+                if let Some(fullscreen) = errors.check(expect_one_of(
+                    "fullscreen",
+                    lit,
+                    &["off", "exclusive", "desktop"],
+                )) {
+                    options.fullscreen = fullscreen;
+                }
+            }
+
+            "borderless" => {
+                if let Some(value) = errors.check(expect_bool("borderless", lit)) {
+                    options.borderless = value;
+                }
+            }
+
+            "resizable" => {
+                if let Some(value) = errors.check(expect_bool("resizable", lit)) {
+                    options.resizable = value;
+                }
+            }
+
+            "high_dpi" => {
+                if let Some(value) = errors.check(expect_bool("high_dpi", lit)) {
+                    options.high_dpi = value;
+                }
+            }
+
+            "min_size" => {
+                if let Some(size) = errors.check(parse_size("min_size", lit)) {
+                    options.min_size = Some(size);
+                }
+            }
 
-    function.block.stmts.push(syn::parse_quote!(let title = #title;));
-    function.block.stmts.push(syn::parse_quote!(let window_width = #width;));
-    function.block.stmts.push(syn::parse_quote!(let window_height = #height;));
-    function.block.stmts.push(syn::parse_quote!(let bg_color = #bg_color;));
+            "icon" => {
+                if let Some(icon) = errors.check(expect_str("icon", lit)) {
+                    if icon.to_lowercase().ends_with(".bmp") {
+                        options.icon = Some(icon);
+                    } else {
+                        errors.push(syn::Error::new(
+                            lit.span(),
+                            "expected a path to a `.bmp` file for `icon`",
+                        ));
+                    }
+                }
+            }
+
+            _ => errors.push(unknown_option(path, &name)),
+        }
+    }
+
+    let es = options.profile == "es";
+    let versions: &[(u8, u8)] = if es { &GLES_VERSIONS } else { &GL_VERSIONS };
+    if !versions.contains(&options.gl_version) {
+        errors.push(syn::Error::new(
+            version_span,
+            format!(
+                "OpenGL{} {}.{} does not exist",
+                if es { " ES" } else { "" },
+                options.gl_version.0,
+                options.gl_version.1
+            ),
+        ));
+    } else if options.gl_version < (3, 2) && options.profile == "core" {
+        errors.push(syn::Error::new(
+            version_span,
+            "core profiles need OpenGL 3.2 or newer",
+        ));
+    }
+    if options.borderless && options.fullscreen == "exclusive" {
+        errors.push(syn::Error::new(
+            fullscreen_span,
+            "`borderless` has no effect with exclusive fullscreen",
+        ));
+    }
+
+    match errors.error {
+        Some(error) => Err(error),
+        None => {
+            options.push_statements(function);
+            Ok(())
+        }
+    }
+}
+
+impl Options {
+    /// Declares the settings as local variables of the generated `main`
+    fn push_statements(&self, function: &mut ItemFn) {
+        let Options {
+            title,
+            width,
+            height,
+            bg_color,
+            forward_compatible,
+            debug,
+            msaa,
+            depth_bits,
+            stencil_bits,
+            srgb,
+            fullscreen,
+            borderless,
+            resizable,
+            high_dpi,
+            ..
+        } = self;
+
+        let [r, g, b, a] = bg_color.to_array();
+        let (major, minor) = self.gl_version;
+        let profile = match self.profile.as_str() {
+            "compatibility" => quote!(sdl2::video::GLProfile::Compatibility),
+            "es" => quote!(sdl2::video::GLProfile::GLES),
+            _ => quote!(sdl2::video::GLProfile::Core),
+        };
+        let vsync = match self.vsync.as_str() {
+            "off" => quote!(::ogl_runtime::config::Vsync::Off),
+            "adaptive" => quote!(::ogl_runtime::config::Vsync::Adaptive),
+            _ => quote!(::ogl_runtime::config::Vsync::On),
+        };
+        let fullscreen = match fullscreen.as_str() {
+            "exclusive" => quote!(::ogl_runtime::config::Fullscreen::Exclusive),
+            "desktop" => quote!(::ogl_runtime::config::Fullscreen::Desktop),
+            _ => quote!(::ogl_runtime::config::Fullscreen::Off),
+        };
+        let min_size = match self.min_size {
+            Some((width, height)) => quote!(Some((#width, #height))),
+            None => quote!(None),
+        };
+        // Relative to the crate using the macro; include_bytes! fails the
+        // build if the file is missing
+        let icon = match &self.icon {
+            Some(icon) => {
+                let path = std::env::var("CARGO_MANIFEST_DIR")
+                    .map(|dir| PathBuf::from(dir).join(icon))
+                    .unwrap_or_else(|_| PathBuf::from(icon));
+                let path = path.to_string_lossy().into_owned();
+                quote!(Some(&include_bytes!(#path)[..]))
+            }
+            None => quote!(None),
+        };
+
+        function.block.stmts.push(syn::parse_quote! {
+            let mut config = ::ogl_runtime::config::WindowConfig {
+                title: #title.to_string(),
+                width: #width,
+                height: #height,
+                bg_color: ::ogl_runtime::color::Color::rgba(#r, #g, #b, #a),
+                vsync: #vsync,
+                msaa: #msaa,
+                depth_bits: #depth_bits,
+                stencil_bits: #stencil_bits,
+                srgb: #srgb,
+                fullscreen: #fullscreen,
+                borderless: #borderless,
+                resizable: #resizable,
+                high_dpi: #high_dpi,
+                min_size: #min_size,
+                debug: #debug,
+            };
+        });
+        function.block.stmts.push(syn::parse_quote! {
+            if let Err(error) = config.load_overrides(std::env::args().skip(1)) {
+                eprintln!("{}", error);
+                std::process::exit(2);
+            }
+        });
+        function.block.stmts.push(syn::parse_quote!(let title = config.title.as_str();));
+        function.block.stmts.push(syn::parse_quote!(let window_width = config.width;));
+        function.block.stmts.push(syn::parse_quote!(let window_height = config.height;));
+        function.block.stmts.push(syn::parse_quote!(let bg_color = config.clear_color();));
+        function.block.stmts.push(syn::parse_quote!(let gl_version: (u8, u8) = (#major, #minor);));
+        function.block.stmts.push(syn::parse_quote!(let gl_profile = #profile;));
+        function.block.stmts.push(syn::parse_quote!(let gl_forward_compatible = #forward_compatible;));
+        function.block.stmts.push(syn::parse_quote!(let gl_debug = config.debug;));
+        function.block.stmts.push(syn::parse_quote!(let vsync = config.vsync.swap_interval();));
+        function.block.stmts.push(syn::parse_quote!(let msaa_samples = config.msaa;));
+        function.block.stmts.push(syn::parse_quote!(let depth_bits = config.depth_bits;));
+        function.block.stmts.push(syn::parse_quote!(let stencil_bits = config.stencil_bits;));
+        function.block.stmts.push(syn::parse_quote!(let srgb = config.srgb;));
+        function.block.stmts.push(syn::parse_quote!(let fullscreen = config.fullscreen;));
+        function.block.stmts.push(syn::parse_quote!(let borderless = config.borderless;));
+        function.block.stmts.push(syn::parse_quote!(let resizable = config.resizable;));
+        function.block.stmts.push(syn::parse_quote!(let high_dpi = config.high_dpi;));
+        function.block.stmts.push(syn::parse_quote!(let min_size = config.min_size;));
+        function.block.stmts.push(syn::parse_quote!(let window_icon: Option<&[u8]> = #icon;));
+    }
 }
@@ -0,0 +1,4 @@
+use ogl_main::ogl_main;
+
+#[ogl_main(bg_color = "0.3 0.3 0.5 1.0 1.0")]
+fn main() {}
@@ -0,0 +1,4 @@
+use ogl_main::ogl_main;
+
+#[ogl_main(gl_version = "2.1", profile = "core")]
+fn main() {}
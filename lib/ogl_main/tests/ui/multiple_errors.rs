@@ -0,0 +1,11 @@
+use ogl_main::ogl_main;
+
+#[ogl_main(
+    title = 42,
+    window = "800x",
+    vsync = "sometimes",
+    borderles = true,
+    fullscreen = "exclusive",
+    borderless = true
+)]
+fn main() {}
@@ -0,0 +1,9 @@
+use ogl_main::ogl_main;
+
+// Only compiled: `run` is never called, so no window is opened
+#[ogl_main(title = "Pass", window = "640x480", bg_color = "#336699")]
+fn run() {
+    let _ = (&config, &gl, &window, &gl_context);
+}
+
+fn main() {}
@@ -0,0 +1,4 @@
+use ogl_main::ogl_main;
+
+#[ogl_main(bg_color = "cornflowerblu")]
+fn main() {}
@@ -0,0 +1,4 @@
+use ogl_main::ogl_main;
+
+#[ogl_main(msaa = "4")]
+fn main() {}
@@ -0,0 +1,465 @@
+use crate::resources::{self, Resources};
+use color::{Color, ColorSpace};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Name of the config file looked up in `CONFIG_DIRS`
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Resource directories searched for `CONFIG_FILE`, in order, relative to
+/// the executable like the application's own `Resources`
+pub const CONFIG_DIRS: [&str; 2] = ["", "shaders"];
+
+/// Keys accepted in the config file and as flags
+pub const KEYS: [&str; 14] = [
+    "title",
+    "window",
+    "bg_color",
+    "vsync",
+    "msaa",
+    "depth_bits",
+    "stencil_bits",
+    "srgb",
+    "fullscreen",
+    "borderless",
+    "resizable",
+    "high_dpi",
+    "min_size",
+    "debug",
+];
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to read config file {}: {}", path, inner)]
+    Read { path: String, inner: std::io::Error },
+    #[fail(display = "Failed to parse config file {}: {}", path, message)]
+    Parse { path: String, message: String },
+    #[fail(display = "Unknown setting `{}` from {}, expected one of: {}", key, source, expected)]
+    UnknownKey { key: String, source: String, expected: String },
+    #[fail(display = "Invalid value {:?} for `{}` from {}: {}", value, key, source, message)]
+    InvalidValue { key: String, value: String, source: String, message: String },
+    #[fail(display = "Missing value for `--{}`", key)]
+    MissingValue { key: String },
+    #[fail(display = "`borderless` has no effect with exclusive fullscreen")]
+    BorderlessFullscreen,
+}
+
+/// Swap interval of the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vsync {
+    Off,
+    On,
+    /// Tears instead of stuttering when a frame is late, where supported
+    Adaptive,
+}
+
+impl Vsync {
+    pub fn swap_interval(self) -> sdl2::video::SwapInterval {
+        match self {
+            Vsync::Off => sdl2::video::SwapInterval::Immediate,
+            Vsync::On => sdl2::video::SwapInterval::VSync,
+            Vsync::Adaptive => sdl2::video::SwapInterval::LateSwapTearing,
+        }
+    }
+}
+
+impl FromStr for Vsync {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Vsync::Off),
+            "on" => Ok(Vsync::On),
+            "adaptive" => Ok(Vsync::Adaptive),
+            _ => Err("expected \"on\", \"off\" or \"adaptive\"".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fullscreen {
+    Off,
+    /// Changes the display mode to the window size
+    Exclusive,
+    /// Covers the desktop at its current resolution
+    Desktop,
+}
+
+impl FromStr for Fullscreen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Fullscreen::Off),
+            "exclusive" => Ok(Fullscreen::Exclusive),
+            "desktop" => Ok(Fullscreen::Desktop),
+            _ => Err("expected \"off\", \"exclusive\" or \"desktop\"".into()),
+        }
+    }
+}
+
+/// Window and framebuffer settings the `#[ogl_main]` `main` creates the
+/// window with, overridable at runtime.
+///
+/// Settings are layered, each layer replacing the keys it sets:
+/// 1. the `#[ogl_main]` attribute, or its defaults
+/// 2. one config file: `--config PATH` if given, else `config.toml` next to
+///    the executable, else `config.toml` in the `shaders` resource directory
+/// 3. command-line flags, `--key value` or `--key=value`; other arguments
+///    are left to the application
+///
+/// Keys are the attribute's option names, e.g. `--window 1920x1080 --vsync off`
+/// or `msaa = 4` in the file. `gl_version`, `profile`, `forward_compatible`
+/// and `icon` can only be set in the attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub bg_color: Color,
+    pub vsync: Vsync,
+    pub msaa: u8,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    pub srgb: bool,
+    pub fullscreen: Fullscreen,
+    pub borderless: bool,
+    pub resizable: bool,
+    pub high_dpi: bool,
+    pub min_size: Option<(u32, u32)>,
+    pub debug: bool,
+}
+
+impl WindowConfig {
+    /// Applies the config file, then the flags in `args` (without the program
+    /// name). Arguments not starting with `--`, and everything after `--`,
+    /// are left to the application.
+    pub fn load_overrides<I>(&mut self, args: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let flags = parse_flags(args)?;
+        let explicit = flags
+            .iter()
+            .find(|(key, _)| key == "config")
+            .map(|(_, path)| PathBuf::from(path));
+
+        match explicit {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path).map_err(|inner| Error::Read {
+                    path: path.display().to_string(),
+                    inner,
+                })?;
+                self.apply_toml(&text, &path.display().to_string())?;
+            }
+            None => {
+                if let Some((text, path)) = find_config_file(&CONFIG_DIRS)? {
+                    self.apply_toml(&text, &path)?;
+                }
+            }
+        }
+
+        for (key, value) in flags.iter().filter(|(key, _)| key != "config") {
+            self.set(key, value, "the command line")?;
+        }
+        self.validate()
+    }
+
+    /// Applies the settings of a TOML document; `source` names it in errors
+    pub fn apply_toml(&mut self, text: &str, source: &str) -> Result<(), Error> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| Error::Parse {
+            path: source.to_string(),
+            message: e.to_string().trim_end().to_string(),
+        })?;
+        for (key, value) in &table {
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                other => other.to_string(),
+            };
+            self.set(key, &value, source)?;
+        }
+        Ok(())
+    }
+
+    /// Sets one setting from its textual form; `source` names where it came from
+    pub fn set(&mut self, key: &str, value: &str, source: &str) -> Result<(), Error> {
+        let invalid = |message: String| Error::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            source: source.to_string(),
+            message,
+        };
+        let flag = |value: &str| match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(invalid("expected true or false".into())),
+        };
+        let samples = |value: &str, allowed: &[u8]| {
+            value
+                .parse::<u8>()
+                .ok()
+                .filter(|v| allowed.contains(v))
+                .ok_or_else(|| invalid(format!("expected one of {:?}", allowed)))
+        };
+
+        match key {
+            "title" => self.title = value.to_string(),
+            "window" => {
+                let (width, height) = parse_size(value).map_err(invalid)?;
+                self.width = width;
+                self.height = height;
+            }
+            "bg_color" => self.bg_color = value.parse().map_err(|e| invalid(format!("{}", e)))?,
+            "vsync" => self.vsync = value.parse().map_err(invalid)?,
+            "msaa" => self.msaa = samples(value, &[0, 2, 4, 8, 16])?,
+            "depth_bits" => self.depth_bits = samples(value, &[0, 16, 24, 32])?,
+            "stencil_bits" => self.stencil_bits = samples(value, &[0, 8])?,
+            "srgb" => self.srgb = flag(value)?,
+            "fullscreen" => self.fullscreen = value.parse().map_err(invalid)?,
+            "borderless" => self.borderless = flag(value)?,
+            "resizable" => self.resizable = flag(value)?,
+            "high_dpi" => self.high_dpi = flag(value)?,
+            "min_size" => self.min_size = Some(parse_size(value).map_err(invalid)?),
+            "debug" => self.debug = flag(value)?,
+            _ => {
+                return Err(Error::UnknownKey {
+                    key: key.to_string(),
+                    source: source.to_string(),
+                    expected: KEYS.join(", "),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// The background colour as `glClearColor` expects it for the default
+    /// framebuffer: linear only when it is sRGB
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.bg_color.to_space(if self.srgb {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        })
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.borderless && self.fullscreen == Fullscreen::Exclusive {
+            return Err(Error::BorderlessFullscreen);
+        }
+        Ok(())
+    }
+}
+
+/// Splits `--key value` and `--key=value` pairs, skipping positional arguments;
+/// boolean keys may omit `true`
+fn parse_flags<I>(args: I) -> Result<Vec<(String, String)>, Error>
+where
+    I: IntoIterator<Item = String>,
+{
+    let booleans = ["srgb", "borderless", "resizable", "high_dpi", "debug"];
+    let mut flags = Vec::new();
+    let mut args = args.into_iter().peekable();
+    while let Some(argument) = args.next() {
+        if argument == "--" {
+            break;
+        }
+        let flag = match argument.strip_prefix("--") {
+            Some(flag) => flag,
+            None => continue,
+        };
+        let (key, value) = match flag.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => {
+                // A boolean only takes `true` or `false` as its value, so
+                // `--debug scene.gltf` leaves the file to the application
+                let value = if booleans.contains(&flag) {
+                    match args.peek() {
+                        Some(next) if next == "true" || next == "false" => args.next(),
+                        _ => Some("true".into()),
+                    }
+                } else {
+                    match args.peek() {
+                        Some(next) if !next.starts_with("--") => args.next(),
+                        _ => None,
+                    }
+                };
+                let value = value.ok_or_else(|| Error::MissingValue { key: flag.to_string() })?;
+                (flag.to_string(), value)
+            }
+        };
+        flags.push((key, value));
+    }
+    Ok(flags)
+}
+
+/// The first `CONFIG_FILE` found in the resource directories `dirs`,
+/// with its resource name
+fn find_config_file(dirs: &[&str]) -> Result<Option<(String, String)>, Error> {
+    for dir in dirs {
+        let res = match Resources::from_rel_path(Path::new(dir)) {
+            Ok(res) => res,
+            Err(_) => return Ok(None),
+        };
+        let name = Path::new(dir).join(CONFIG_FILE).to_string_lossy().replace('\\', "/");
+        let bytes = match res.load_bytes(CONFIG_FILE) {
+            Ok(bytes) => bytes,
+            Err(resources::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(resources::Error::Io(inner)) => return Err(Error::Read { path: name, inner }),
+            Err(e) => {
+                return Err(Error::Parse {
+                    path: name,
+                    message: e.to_string(),
+                })
+            }
+        };
+        let text = String::from_utf8(bytes).map_err(|_| Error::Parse {
+            path: name.clone(),
+            message: "not valid UTF-8".into(),
+        })?;
+        return Ok(Some((text, name)));
+    }
+    Ok(None)
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || "expected WIDTHxHEIGHT, e.g. 1920x1080".to_string();
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse::<u32>().map_err(|_| invalid())?;
+    let height = height.parse::<u32>().map_err(|_| invalid())?;
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err("must be at least 1x1".into());
+    }
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WindowConfig {
+        WindowConfig {
+            title: "test".into(),
+            width: 800,
+            height: 600,
+            bg_color: Color::BLACK,
+            vsync: Vsync::On,
+            msaa: 0,
+            depth_bits: 24,
+            stencil_bits: 8,
+            srgb: false,
+            fullscreen: Fullscreen::Off,
+            borderless: false,
+            resizable: false,
+            high_dpi: false,
+            min_size: None,
+            debug: false,
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn flags(list: &[&str]) -> Vec<(String, String)> {
+        parse_flags(args(list)).unwrap()
+    }
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    /// Empty directory under the system temp dir, unique to the test
+    fn temp_dir(test: &str) -> PathBuf {
+        let name = format!("ogl-runtime-{}-{}", test, std::process::id());
+        let root = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn parses_flags() {
+        assert_eq!(
+            flags(&["--window", "1920x1080", "--vsync=off", "--msaa", "4"]),
+            pairs(&[("window", "1920x1080"), ("vsync", "off"), ("msaa", "4")])
+        );
+        assert_eq!(
+            flags(&["--debug", "--srgb", "false", "--resizable"]),
+            pairs(&[("debug", "true"), ("srgb", "false"), ("resizable", "true")])
+        );
+        assert_eq!(flags(&["--title", "a", "--", "--msaa", "4"]), pairs(&[("title", "a")]));
+        match parse_flags(args(&["--title"])) {
+            Err(Error::MissingValue { key }) => assert_eq!(key, "title"),
+            other => panic!("expected a missing value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn leaves_positional_arguments_to_the_application() {
+        assert_eq!(
+            flags(&["scene.gltf", "--msaa", "2", "extra", "--debug", "level.ron"]),
+            pairs(&[("msaa", "2"), ("debug", "true")])
+        );
+
+        let mut config = config();
+        config.load_overrides(args(&["scene.gltf", "--msaa", "2"])).unwrap();
+        assert_eq!(config.msaa, 2);
+    }
+
+    #[test]
+    fn command_line_overrides_the_config_file() {
+        let root = temp_dir("precedence");
+        let path = root.join(CONFIG_FILE);
+        std::fs::write(&path, "title = \"from file\"\nmsaa = 4\nvsync = \"off\"\n").unwrap();
+
+        let mut config = config();
+        let path = path.to_string_lossy().into_owned();
+        config.load_overrides(args(&["--config", &path, "--msaa", "8"])).unwrap();
+        assert_eq!(config.title, "from file");
+        assert_eq!(config.vsync, Vsync::Off);
+        assert_eq!(config.msaa, 8);
+        // Untouched by both layers
+        assert_eq!(config.depth_bits, 24);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        match config().apply_toml("colour = \"red\"", "test.toml") {
+            Err(Error::UnknownKey { key, source, .. }) => {
+                assert_eq!((key.as_str(), source.as_str()), ("colour", "test.toml"))
+            }
+            other => panic!("expected an unknown key, got {:?}", other),
+        }
+        match config().load_overrides(args(&["--config", "/nonexistent.toml"])) {
+            Err(Error::Read { .. }) => {}
+            other => panic!("expected a read error, got {:?}", other),
+        }
+        match config().set("vsnyc", "off", "the command line") {
+            Err(Error::UnknownKey { key, .. }) => assert_eq!(key, "vsnyc"),
+            other => panic!("expected an unknown key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn finds_the_config_file_in_the_first_directory_holding_it() {
+        let root = temp_dir("find");
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        std::fs::write(second.join(CONFIG_FILE), "msaa = 2").unwrap();
+
+        // Absolute directories replace the executable's directory
+        let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+        let (text, name) = find_config_file(&[&first, &second]).unwrap().unwrap();
+        assert_eq!(text, "msaa = 2");
+        assert!(name.ends_with("second/config.toml"), "{}", name);
+
+        std::fs::write(root.join("first").join(CONFIG_FILE), "msaa = 4").unwrap();
+        let (text, _) = find_config_file(&[&first, &second]).unwrap().unwrap();
+        assert_eq!(text, "msaa = 4");
+
+        assert!(find_config_file(&[&root.to_string_lossy()]).unwrap().is_none());
+    }
+}
@@ -0,0 +1,11 @@
+// Types the `#[ogl_main]` expansion refers to as `::ogl_runtime::...`,
+// so crates using the macro must depend on this one. Also holds the
+// resource loader, which the config file lookup shares with the application.
+
+#[macro_use]
+extern crate failure;
+
+pub use color;
+
+pub mod config;
+pub mod resources;
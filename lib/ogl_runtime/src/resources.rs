@@ -3,7 +3,6 @@ use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use crate::util;
 
 #[derive(Debug, Fail)]
 pub enum Error {
@@ -38,6 +37,25 @@ impl Resources {
         })
     }
 
+    /// Loads a resource as raw bytes, e.g. for binary formats
+    pub fn load_bytes(&self, resource_name: &str) -> Result<Vec<u8>, Error> {
+        Ok(std::fs::read(self.root_path.join(resource_name))?)
+    }
+
+    /// Names of the files directly inside the resource directory `dir`, sorted
+    pub fn list(&self, dir: &str) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(self.root_path.join(dir))? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                let file = entry.file_name().to_string_lossy().into_owned();
+                names.push(Path::new(dir).join(file).to_string_lossy().replace('\\', "/"));
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
     pub fn load(&self, resource_name: &str) -> Result<CString, Error> {
         let mut file = File::open(self.root_path.join(resource_name))?;
         
@@ -51,4 +69,15 @@ impl Resources {
         
         Ok(unsafe { CString::from_vec_unchecked(buffer) })
     }
-}
\ No newline at end of file
+}
+
+/// Resource name of `file` referenced from the resource `base`,
+/// i.e. `file` resolved against the directory of `base`
+pub fn sibling_name(base: &str, file: &str) -> String {
+    match Path::new(base).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            dir.join(file).to_string_lossy().replace('\\', "/")
+        }
+        _ => file.to_string(),
+    }
+}
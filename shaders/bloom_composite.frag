@@ -0,0 +1,11 @@
+#version 450 core
+
+#include "post.glsl"
+
+uniform sampler2D u_bloom;
+uniform float u_intensity = 0.05;
+
+void main() {
+    vec4 color = texture(u_input, IN.Uv);
+    Color = vec4(color.rgb + texture(u_bloom, IN.Uv).rgb * u_intensity, color.a);
+}
@@ -0,0 +1,27 @@
+#version 450 core
+
+#include "post.glsl"
+
+// 13 tap downsample of the previous bloom level
+void main() {
+    vec2 t = u_texel_size;
+    vec3 a = texture(u_input, IN.Uv + t * vec2(-2.0, 2.0)).rgb;
+    vec3 b = texture(u_input, IN.Uv + t * vec2(0.0, 2.0)).rgb;
+    vec3 c = texture(u_input, IN.Uv + t * vec2(2.0, 2.0)).rgb;
+    vec3 d = texture(u_input, IN.Uv + t * vec2(-2.0, 0.0)).rgb;
+    vec3 e = texture(u_input, IN.Uv).rgb;
+    vec3 f = texture(u_input, IN.Uv + t * vec2(2.0, 0.0)).rgb;
+    vec3 g = texture(u_input, IN.Uv + t * vec2(-2.0, -2.0)).rgb;
+    vec3 h = texture(u_input, IN.Uv + t * vec2(0.0, -2.0)).rgb;
+    vec3 i = texture(u_input, IN.Uv + t * vec2(2.0, -2.0)).rgb;
+    vec3 j = texture(u_input, IN.Uv + t * vec2(-1.0, 1.0)).rgb;
+    vec3 k = texture(u_input, IN.Uv + t * vec2(1.0, 1.0)).rgb;
+    vec3 l = texture(u_input, IN.Uv + t * vec2(-1.0, -1.0)).rgb;
+    vec3 m = texture(u_input, IN.Uv + t * vec2(1.0, -1.0)).rgb;
+
+    vec3 color = e * 0.125;
+    color += (a + c + g + i) * 0.03125;
+    color += (b + d + f + h) * 0.0625;
+    color += (j + k + l + m) * 0.125;
+    Color = vec4(color, 1.0);
+}
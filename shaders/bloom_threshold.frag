@@ -0,0 +1,25 @@
+#version 450 core
+
+#include "post.glsl"
+
+// First bloom step, into the first half resolution level: keeps the
+// light above `u_threshold`, fading in over `u_knee`
+
+uniform float u_threshold = 1.0;
+uniform float u_knee = 0.5;
+
+void main() {
+    vec3 color = vec3(0.0);
+    for (int x = -1; x <= 1; x += 2) {
+        for (int y = -1; y <= 1; y += 2) {
+            color += texture(u_input, IN.Uv + vec2(x, y) * u_texel_size).rgb;
+        }
+    }
+    color *= 0.25;
+
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - u_threshold + u_knee, 0.0, 2.0 * u_knee);
+    soft = soft * soft / (4.0 * u_knee + 1e-4);
+    float contribution = max(soft, brightness - u_threshold) / max(brightness, 1e-4);
+    Color = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450 core
+
+#include "post.glsl"
+
+// 3x3 tent upsample of the next smaller bloom level, added to this one
+uniform float u_radius = 1.0;
+
+void main() {
+    vec2 t = u_texel_size * u_radius;
+    vec3 color = texture(u_input, IN.Uv).rgb * 4.0;
+    color += (texture(u_input, IN.Uv + vec2(-t.x, 0.0)).rgb
+        + texture(u_input, IN.Uv + vec2(t.x, 0.0)).rgb
+        + texture(u_input, IN.Uv + vec2(0.0, -t.y)).rgb
+        + texture(u_input, IN.Uv + vec2(0.0, t.y)).rgb) * 2.0;
+    color += texture(u_input, IN.Uv + vec2(-t.x, -t.y)).rgb
+        + texture(u_input, IN.Uv + vec2(t.x, -t.y)).rgb
+        + texture(u_input, IN.Uv + vec2(-t.x, t.y)).rgb
+        + texture(u_input, IN.Uv + vec2(t.x, t.y)).rgb;
+    Color = vec4(color / 16.0, 1.0);
+}
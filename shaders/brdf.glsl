@@ -0,0 +1,71 @@
+// Cook-Torrance metallic-roughness BRDF
+
+const float PI = 3.14159265359;
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    // k for direct lighting
+    float r = roughness + 1.0;
+    float k = r * r / 8.0;
+    float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Light reflected towards `v` for unit radiance arriving from `l`
+vec3 brdf_direct(vec3 n, vec3 v, vec3 l, vec3 albedo, float metallic, float roughness) {
+    float n_dot_l = max(dot(n, l), 0.0);
+    if (n_dot_l <= 0.0) {
+        return vec3(0.0);
+    }
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+
+    vec3 h = normalize(l + v);
+    vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    float d = distribution_ggx(max(dot(n, h), 0.0), roughness);
+    float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+
+    vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo / PI;
+    return (diffuse + specular) * n_dot_l;
+}
+
+// Image based lighting from the maps of `ibl::Environment`
+vec3 brdf_environment(
+    samplerCube irradiance_map,
+    samplerCube prefiltered_map,
+    sampler2D brdf_lut,
+    float max_reflection_lod,
+    vec3 n,
+    vec3 v,
+    vec3 albedo,
+    float metallic,
+    float roughness
+) {
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    vec3 diffuse = texture(irradiance_map, n).rgb * albedo * (1.0 - f) * (1.0 - metallic);
+
+    vec3 r = reflect(-v, n);
+    vec3 prefiltered = textureLod(prefiltered_map, r, roughness * max_reflection_lod).rgb;
+    vec2 brdf = texture(brdf_lut, vec2(n_dot_v, roughness)).rg;
+    vec3 specular = prefiltered * (f0 * brdf.x + brdf.y);
+
+    return diffuse + specular;
+}
@@ -0,0 +1,67 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec2 Uv;
+} IN;
+
+out vec2 Color;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+float radical_inverse(uint bits) {
+    float result = 0.0;
+    float scale = 0.5;
+    for (int i = 0; i < 32; ++i) {
+        result += float(bits & 1u) * scale;
+        bits >>= 1u;
+        scale *= 0.5;
+    }
+    return result;
+}
+
+vec2 hammersley(uint i, uint n) {
+    return vec2(float(i) / float(n), radical_inverse(i));
+}
+
+vec3 importance_sample_ggx(vec2 xi, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+float geometry_schlick_ggx(float n_dot_v, float roughness) {
+    // k for image based lighting
+    float k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+// Scale (x) and bias (y) applied to F0 by the split-sum approximation,
+// indexed by n.v (u) and roughness (v)
+void main() {
+    float n_dot_v = max(IN.Uv.x, 1e-4);
+    float roughness = IN.Uv.y;
+    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+
+        float n_dot_l = max(l.z, 0.0);
+        float n_dot_h = max(h.z, 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            float g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            float g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            float fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    Color = vec2(scale, bias) / float(SAMPLE_COUNT);
+}
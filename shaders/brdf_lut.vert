@@ -0,0 +1,12 @@
+#version 330 core
+
+out VS_OUTPUT {
+    vec2 Uv;
+} OUT;
+
+// Fullscreen triangle from gl_VertexID, drawn without vertex attributes
+void main() {
+    vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    OUT.Uv = position;
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,30 @@
+#version 450 core
+
+#include "post.glsl"
+
+// 3D lookup table unwrapped into a strip of `u_lut_size` square slices
+// (e.g. 256x16): red along each slice, green top to bottom in the image
+// file, blue from slice to slice. Expects display referred input.
+uniform sampler2D u_lut;
+uniform float u_lut_size = 16.0;
+uniform float u_strength = 1.0;
+
+vec3 lookup(vec3 color) {
+    float size = u_lut_size;
+    float blue = color.b * (size - 1.0);
+    float slice = floor(blue);
+    float next = min(slice + 1.0, size - 1.0);
+
+    // Textures are loaded flipped, so green 0 is at the top of the texture
+    float u = (color.r * (size - 1.0) + 0.5) / (size * size);
+    float v = 1.0 - (color.g * (size - 1.0) + 0.5) / size;
+    vec3 a = texture(u_lut, vec2(u + slice / size, v)).rgb;
+    vec3 b = texture(u_lut, vec2(u + next / size, v)).rgb;
+    return mix(a, b, blue - slice);
+}
+
+void main() {
+    vec4 color = texture(u_input, IN.Uv);
+    vec3 graded = lookup(clamp(color.rgb, 0.0, 1.0));
+    Color = vec4(mix(color.rgb, graded, u_strength), color.a);
+}
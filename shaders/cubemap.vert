@@ -0,0 +1,14 @@
+#version 330 core
+
+layout (location = 0) in vec3 Position;
+
+uniform mat4 u_view_projection;
+
+out VS_OUTPUT {
+    vec3 Direction;
+} OUT;
+
+void main() {
+    OUT.Direction = Position;
+    gl_Position = u_view_projection * vec4(Position, 1.0);
+}
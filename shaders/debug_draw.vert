@@ -0,0 +1,15 @@
+#version 450 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec4 Color;
+
+uniform mat4 u_view_projection;
+
+out VS_OUTPUT {
+    vec4 Color;
+} OUT;
+
+void main() {
+    OUT.Color = Color;
+    gl_Position = u_view_projection * vec4(Position, 1.0);
+}
@@ -0,0 +1,73 @@
+#version 450 core
+
+// Values of `deferred::DeferredView`
+#define VIEW_LIT 0
+#define VIEW_ALBEDO 1
+#define VIEW_NORMAL 2
+#define VIEW_METALLIC_ROUGHNESS 3
+#define VIEW_OCCLUSION 4
+#define VIEW_EMISSIVE 5
+#define VIEW_DEPTH 6
+#define VIEW_LIGHT_COUNT 7
+
+in VS_OUTPUT {
+    vec2 Uv;
+} IN;
+
+uniform int u_view = VIEW_LIT;
+uniform sampler2D u_lit;
+uniform sampler2D u_albedo;
+uniform sampler2D u_normal;
+uniform sampler2D u_material;
+uniform sampler2D u_emissive;
+uniform sampler2D u_depth;
+
+uniform float u_exposure = 1.0;
+uniform bool u_perspective = true;
+uniform float u_near = 0.1;
+uniform float u_far = 100.0;
+
+out vec4 Color;
+
+vec3 tonemap(vec3 color) {
+    // Exponential tone mapping and gamma encoding, as in pbr.frag
+    color = vec3(1.0) - exp(-color * u_exposure);
+    return pow(color, vec3(1.0 / 2.2));
+}
+
+void main() {
+    vec3 color;
+    switch (u_view) {
+        case VIEW_ALBEDO:
+            color = texture(u_albedo, IN.Uv).rgb;
+            break;
+        case VIEW_NORMAL:
+            color = texture(u_normal, IN.Uv).xyz * 0.5 + 0.5;
+            break;
+        case VIEW_METALLIC_ROUGHNESS:
+            color = vec3(texture(u_material, IN.Uv).rg, 0.0);
+            break;
+        case VIEW_OCCLUSION:
+            color = vec3(texture(u_albedo, IN.Uv).a);
+            break;
+        case VIEW_EMISSIVE:
+            color = tonemap(texture(u_emissive, IN.Uv).rgb);
+            break;
+        case VIEW_DEPTH: {
+            float depth = texture(u_depth, IN.Uv).r;
+            if (u_perspective) {
+                float z = depth * 2.0 - 1.0;
+                depth = 2.0 * u_near * u_far / (u_far + u_near - z * (u_far - u_near)) / u_far;
+            }
+            color = vec3(depth);
+            break;
+        }
+        case VIEW_LIGHT_COUNT:
+            color = texture(u_lit, IN.Uv).rgb;
+            break;
+        default:
+            color = tonemap(texture(u_lit, IN.Uv).rgb);
+            break;
+    }
+    Color = vec4(color, 1.0);
+}
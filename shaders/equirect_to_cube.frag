@@ -0,0 +1,17 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec3 Direction;
+} IN;
+
+uniform sampler2D u_equirect;
+
+out vec4 Color;
+
+const vec2 INV_ATAN = vec2(0.1591549, 0.3183099);
+
+void main() {
+    vec3 v = normalize(IN.Direction);
+    vec2 uv = vec2(atan(v.z, v.x), asin(clamp(v.y, -1.0, 1.0))) * INV_ATAN + 0.5;
+    Color = vec4(texture(u_equirect, uv).rgb, 1.0);
+}
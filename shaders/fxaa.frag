@@ -0,0 +1,42 @@
+#version 450 core
+
+#include "post.glsl"
+
+// Run after tone mapping and gamma: edges are found on display luminance
+
+uniform float u_span_max = 8.0;
+uniform float u_reduce_min = 1.0 / 128.0;
+uniform float u_reduce_mul = 1.0 / 8.0;
+
+vec3 sample_at(vec2 offset) {
+    return texture(u_input, IN.Uv + offset * u_texel_size).rgb;
+}
+
+void main() {
+    vec4 center = texture(u_input, IN.Uv);
+    float luma_m = luminance(center.rgb);
+    float luma_nw = luminance(sample_at(vec2(-1.0, -1.0)));
+    float luma_ne = luminance(sample_at(vec2(1.0, -1.0)));
+    float luma_sw = luminance(sample_at(vec2(-1.0, 1.0)));
+    float luma_se = luminance(sample_at(vec2(1.0, 1.0)));
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur along the edge, perpendicular to the luminance gradient
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * u_reduce_mul, u_reduce_min);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-u_span_max), vec2(u_span_max));
+
+    vec3 near = 0.5 * (sample_at(dir * (1.0 / 3.0 - 0.5)) + sample_at(dir * (2.0 / 3.0 - 0.5)));
+    vec3 far = near * 0.5 + 0.25 * (sample_at(dir * -0.5) + sample_at(dir * 0.5));
+
+    // The wider blur is rejected when it pulls in colours from beyond the edge
+    float luma_far = luminance(far);
+    vec3 color = (luma_far < luma_min || luma_far > luma_max) ? near : far;
+    Color = vec4(color, center.a);
+}
@@ -0,0 +1,10 @@
+#version 450 core
+
+#include "post.glsl"
+
+uniform float u_gamma = 2.2;
+
+void main() {
+    vec4 linear = texture(u_input, IN.Uv);
+    Color = vec4(pow(max(linear.rgb, vec3(0.0)), vec3(1.0 / u_gamma)), linear.a);
+}
@@ -0,0 +1,27 @@
+#version 450 core
+
+#include "pbr_material.glsl"
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec2 Uv;
+    mat3 Tbn;
+} IN;
+
+// Layout of `deferred::GBuffer`
+layout (location = 0) out vec4 Albedo;   // rgb base color, a occlusion
+layout (location = 1) out vec4 Normal;   // xyz world space normal
+layout (location = 2) out vec4 Material; // r metallic, g roughness
+layout (location = 3) out vec4 Emissive; // rgb emitted radiance
+
+void main() {
+    Surface s = sample_surface(IN.Uv, IN.Tbn);
+    if (s.base_color.a < u_alpha_cutoff) {
+        discard;
+    }
+
+    Albedo = vec4(s.base_color.rgb, s.occlusion);
+    Normal = vec4(s.normal, 0.0);
+    Material = vec4(s.metallic, s.roughness, 0.0, 1.0);
+    Emissive = vec4(s.emissive, 1.0);
+}
@@ -0,0 +1,33 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec3 Direction;
+} IN;
+
+uniform samplerCube u_environment;
+
+out vec4 Color;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+// Cosine weighted convolution of the environment over the hemisphere around the normal
+void main() {
+    vec3 n = normalize(IN.Direction);
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            vec3 tangent_sample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * n;
+            irradiance += texture(u_environment, direction).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    Color = vec4(PI * irradiance / samples, 1.0);
+}
@@ -0,0 +1,36 @@
+// Light layout shared by the `Lights` uniform block and the deferred light
+// list; matches `lighting::GpuLight`
+
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+struct Light {
+    vec4 Position;    // xyz position, w type
+    vec4 Direction;   // xyz direction, w cos(inner angle)
+    vec4 Color;       // rgb radiance, w cos(outer angle)
+    vec4 Attenuation; // constant, linear, quadratic, w shadow slot (-1 for none)
+};
+
+// Distance and cone falloff of `light` at `position`;
+// `l` receives the direction towards the light
+float light_attenuation(Light light, vec3 position, out vec3 l) {
+    int light_type = int(light.Position.w);
+    if (light_type == LIGHT_DIRECTIONAL) {
+        l = -light.Direction.xyz;
+        return 1.0;
+    }
+
+    vec3 to_light = light.Position.xyz - position;
+    float d = length(to_light);
+    l = to_light / d;
+    float attenuation = 1.0 / (light.Attenuation.x + light.Attenuation.y * d + light.Attenuation.z * d * d);
+
+    if (light_type == LIGHT_SPOT) {
+        float theta = dot(-l, light.Direction.xyz);
+        float cos_inner = light.Direction.w;
+        float cos_outer = light.Color.w;
+        attenuation *= clamp((theta - cos_outer) / max(cos_inner - cos_outer, 1e-4), 0.0, 1.0);
+    }
+    return attenuation;
+}
@@ -0,0 +1,11 @@
+// Light list of the forward shaders; layout matches `lighting::LightBlock`
+
+#include "light_types.glsl"
+
+#define MAX_LIGHTS 8
+
+layout (std140) uniform Lights {
+    vec4 Ambient;
+    ivec4 Count;
+    Light Items[MAX_LIGHTS];
+};
@@ -0,0 +1,55 @@
+#version 450 core
+
+#include "lights.glsl"
+#include "shadows.glsl"
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec2 Uv;
+    mat3 Tbn;
+} IN;
+
+uniform vec3 u_camera_position;
+
+uniform vec4 u_diffuse_color = vec4(1.0);
+uniform vec3 u_specular_color = vec3(0.5);
+uniform float u_shininess = 32.0;
+uniform sampler2D u_diffuse_map;
+uniform bool u_has_diffuse_map = false;
+uniform sampler2D u_normal_map;
+uniform bool u_has_normal_map = false;
+
+out vec4 Color;
+
+void main() {
+    vec4 albedo = u_diffuse_color;
+    if (u_has_diffuse_map) {
+        albedo *= texture(u_diffuse_map, IN.Uv);
+    }
+
+    vec3 n = normalize(IN.Tbn[2]);
+    if (u_has_normal_map) {
+        vec3 tangent_normal = texture(u_normal_map, IN.Uv).xyz * 2.0 - 1.0;
+        n = normalize(IN.Tbn * tangent_normal);
+    }
+    vec3 v = normalize(u_camera_position - IN.Position);
+    vec3 geometric_normal = normalize(IN.Tbn[2]);
+    float view_depth = dot(IN.Position - u_camera_position, u_camera_forward);
+
+    vec3 color = Ambient.rgb * albedo.rgb;
+    for (int i = 0; i < Count.x; ++i) {
+        Light light = Items[i];
+
+        vec3 l;
+        float attenuation = light_attenuation(light, IN.Position, l);
+        attenuation *= shadow_factor(light, IN.Position, geometric_normal, view_depth);
+
+        float diffuse = max(dot(n, l), 0.0);
+        vec3 h = normalize(l + v);
+        float specular = diffuse > 0.0 ? pow(max(dot(n, h), 0.0), u_shininess) : 0.0;
+
+        color += (albedo.rgb * diffuse + u_specular_color * specular) * light.Color.rgb * attenuation;
+    }
+
+    Color = vec4(color, albedo.a);
+}
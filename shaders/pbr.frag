@@ -0,0 +1,77 @@
+#version 450 core
+
+#include "lights.glsl"
+#include "shadows.glsl"
+#include "brdf.glsl"
+#include "pbr_material.glsl"
+
+in VS_OUTPUT {
+    vec3 Position;
+    vec2 Uv;
+    mat3 Tbn;
+} IN;
+
+uniform vec3 u_camera_position;
+
+// Image based lighting
+uniform samplerCube u_irradiance_map;
+uniform samplerCube u_prefiltered_map;
+uniform sampler2D u_brdf_lut;
+uniform bool u_has_environment = false;
+uniform float u_max_reflection_lod = 4.0;
+
+uniform float u_exposure = 1.0;
+// Disabled when rendering into an HDR target that is tone mapped later
+uniform bool u_tonemap = true;
+
+out vec4 Color;
+
+void main() {
+    Surface s = sample_surface(IN.Uv, IN.Tbn);
+    if (s.base_color.a < u_alpha_cutoff) {
+        discard;
+    }
+
+    vec3 n = s.normal;
+    vec3 v = normalize(u_camera_position - IN.Position);
+    vec3 geometric_normal = normalize(IN.Tbn[2]);
+    float view_depth = dot(IN.Position - u_camera_position, u_camera_forward);
+
+    vec3 albedo = s.base_color.rgb;
+
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < Count.x; ++i) {
+        Light light = Items[i];
+
+        vec3 l;
+        float attenuation = light_attenuation(light, IN.Position, l);
+        attenuation *= shadow_factor(light, IN.Position, geometric_normal, view_depth);
+
+        color += brdf_direct(n, v, l, albedo, s.metallic, s.roughness) * light.Color.rgb * attenuation;
+    }
+
+    vec3 ambient;
+    if (u_has_environment) {
+        ambient = brdf_environment(
+            u_irradiance_map,
+            u_prefiltered_map,
+            u_brdf_lut,
+            u_max_reflection_lod,
+            n,
+            v,
+            albedo,
+            s.metallic,
+            s.roughness
+        );
+    } else {
+        ambient = Ambient.rgb * albedo;
+    }
+    color += ambient * s.occlusion + s.emissive;
+
+    if (u_tonemap) {
+        // Exponential tone mapping and gamma encoding
+        color = vec3(1.0) - exp(-color * u_exposure);
+        color = pow(color, vec3(1.0 / 2.2));
+    }
+    Color = vec4(color, s.base_color.a);
+}
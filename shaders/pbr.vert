@@ -0,0 +1,30 @@
+#version 450 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec3 Normal;
+layout (location = 2) in vec2 Uv;
+layout (location = 3) in vec4 Tangent;
+
+uniform mat4 u_model;
+uniform mat3 u_normal_matrix;
+uniform mat4 u_view_projection;
+
+out VS_OUTPUT {
+    vec3 Position;
+    vec2 Uv;
+    mat3 Tbn;
+} OUT;
+
+void main() {
+    vec4 world = u_model * vec4(Position, 1.0);
+
+    vec3 n = normalize(u_normal_matrix * Normal);
+    vec3 t = normalize(mat3(u_model) * Tangent.xyz);
+    t = normalize(t - n * dot(n, t));
+    vec3 b = cross(n, t) * (Tangent.w < 0.0 ? -1.0 : 1.0);
+
+    OUT.Position = world.xyz;
+    OUT.Uv = Uv;
+    OUT.Tbn = mat3(t, b, n);
+    gl_Position = u_view_projection * world;
+}
@@ -0,0 +1,66 @@
+// glTF metallic-roughness material inputs
+
+uniform vec4 u_base_color_factor = vec4(1.0);
+uniform float u_metallic_factor = 1.0;
+uniform float u_roughness_factor = 1.0;
+uniform vec3 u_emissive_factor = vec3(0.0);
+uniform float u_normal_scale = 1.0;
+uniform float u_occlusion_strength = 1.0;
+uniform float u_alpha_cutoff = 0.0;
+uniform sampler2D u_base_color_map;
+uniform bool u_has_base_color_map = false;
+uniform sampler2D u_metallic_roughness_map;
+uniform bool u_has_metallic_roughness_map = false;
+uniform sampler2D u_normal_map;
+uniform bool u_has_normal_map = false;
+uniform sampler2D u_occlusion_map;
+uniform bool u_has_occlusion_map = false;
+uniform sampler2D u_emissive_map;
+uniform bool u_has_emissive_map = false;
+
+struct Surface {
+    vec4 base_color;
+    float metallic;
+    float roughness;
+    vec3 normal;
+    // Ambient light reaching the surface, 1 when unoccluded
+    float occlusion;
+    vec3 emissive;
+};
+
+// Evaluates the material at `uv`, with `tbn` the interpolated tangent frame
+Surface sample_surface(vec2 uv, mat3 tbn) {
+    Surface s;
+
+    s.base_color = u_base_color_factor;
+    if (u_has_base_color_map) {
+        s.base_color *= texture(u_base_color_map, uv);
+    }
+
+    s.metallic = u_metallic_factor;
+    s.roughness = u_roughness_factor;
+    if (u_has_metallic_roughness_map) {
+        vec4 mr = texture(u_metallic_roughness_map, uv);
+        s.roughness *= mr.g;
+        s.metallic *= mr.b;
+    }
+    s.roughness = clamp(s.roughness, 0.04, 1.0);
+
+    s.normal = normalize(tbn[2]);
+    if (u_has_normal_map) {
+        vec3 tangent_normal = texture(u_normal_map, uv).xyz * 2.0 - 1.0;
+        tangent_normal.xy *= u_normal_scale;
+        s.normal = normalize(tbn * tangent_normal);
+    }
+
+    s.occlusion = 1.0;
+    if (u_has_occlusion_map) {
+        s.occlusion = mix(1.0, texture(u_occlusion_map, uv).r, u_occlusion_strength);
+    }
+
+    s.emissive = u_emissive_factor;
+    if (u_has_emissive_map) {
+        s.emissive *= texture(u_emissive_map, uv).rgb;
+    }
+    return s;
+}
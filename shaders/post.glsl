@@ -0,0 +1,17 @@
+// Inputs every post-processing pass receives from `postprocess::PostProcess`
+
+in VS_OUTPUT {
+    vec2 Uv;
+} IN;
+
+uniform sampler2D u_input;  // output of the previous pass
+uniform sampler2D u_scene;  // image the chain started from
+uniform vec2 u_resolution;  // output size in pixels
+uniform vec2 u_texel_size;  // 1 / input size
+uniform float u_time;       // seconds, from `PostProcess::time`
+
+out vec4 Color;
+
+float luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
@@ -0,0 +1,7 @@
+#version 450 core
+
+#include "post.glsl"
+
+void main() {
+    Color = texture(u_input, IN.Uv);
+}
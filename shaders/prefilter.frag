@@ -0,0 +1,77 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec3 Direction;
+} IN;
+
+uniform samplerCube u_environment;
+uniform float u_roughness;
+// Face size of the environment's level 0
+uniform float u_resolution;
+
+out vec4 Color;
+
+const float PI = 3.14159265359;
+const uint SAMPLE_COUNT = 1024u;
+
+float radical_inverse(uint bits) {
+    float result = 0.0;
+    float scale = 0.5;
+    for (int i = 0; i < 32; ++i) {
+        result += float(bits & 1u) * scale;
+        bits >>= 1u;
+        scale *= 0.5;
+    }
+    return result;
+}
+
+vec2 hammersley(uint i, uint n) {
+    return vec2(float(i) / float(n), radical_inverse(i));
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Split-sum prefiltering with n = v = r
+void main() {
+    vec3 n = normalize(IN.Direction);
+    vec3 v = n;
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; ++i) {
+        vec3 h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), n, u_roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // Sample a lower mip where the PDF is low to avoid fireflies
+            float n_dot_h = max(dot(n, h), 0.0);
+            float pdf = distribution_ggx(n_dot_h, u_roughness) / 4.0 + 0.0001;
+            float texel = 4.0 * PI / (6.0 * u_resolution * u_resolution);
+            float sample_solid_angle = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+            float lod = u_roughness == 0.0 ? 0.0 : 0.5 * log2(sample_solid_angle / texel);
+
+            color += textureLod(u_environment, l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    Color = vec4(color / weight, 1.0);
+}
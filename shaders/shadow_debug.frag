@@ -0,0 +1,34 @@
+#version 450 core
+
+in vec2 Uv;
+
+// 0 shows a layer of u_maps, 1 a face of a cube in u_cubes
+uniform int u_mode = 0;
+uniform sampler2DArray u_maps;
+uniform samplerCubeArray u_cubes;
+uniform int u_layer = 0;
+uniform int u_face = 0;
+
+out vec4 Color;
+
+vec3 face_direction(int face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    switch (face) {
+        case 0: return vec3(1.0, -st.y, -st.x);
+        case 1: return vec3(-1.0, -st.y, st.x);
+        case 2: return vec3(st.x, 1.0, st.y);
+        case 3: return vec3(st.x, -1.0, -st.y);
+        case 4: return vec3(st.x, -st.y, 1.0);
+        default: return vec3(-st.x, -st.y, -1.0);
+    }
+}
+
+void main() {
+    float depth;
+    if (u_mode == 0) {
+        depth = texture(u_maps, vec3(Uv, float(u_layer))).r;
+    } else {
+        depth = texture(u_cubes, vec4(face_direction(u_face, Uv), float(u_layer))).r;
+    }
+    Color = vec4(vec3(depth), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450 core
+
+// x, y, width, height of the tile in normalized device coordinates
+uniform vec4 u_rect;
+
+out vec2 Uv;
+
+void main() {
+    // Triangle strip over the tile
+    vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1);
+    Uv = corner;
+    gl_Position = vec4(u_rect.xy + corner * u_rect.zw, 0.0, 1.0);
+}
@@ -0,0 +1,6 @@
+#version 450 core
+
+// Directional and spot lights store the projected depth, which the fixed
+// function writes; leaving gl_FragDepth alone keeps early depth testing
+void main() {
+}
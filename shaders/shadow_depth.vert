@@ -0,0 +1,14 @@
+#version 450 core
+
+layout (location = 0) in vec3 Position;
+
+uniform mat4 u_model;
+uniform mat4 u_light_view_projection;
+
+out vec3 WorldPosition;
+
+void main() {
+    vec4 world = u_model * vec4(Position, 1.0);
+    WorldPosition = world.xyz;
+    gl_Position = u_light_view_projection * world;
+}
@@ -0,0 +1,20 @@
+#version 450 core
+
+in vec3 WorldPosition;
+
+// Point lights store the distance to the light instead of the projected depth
+uniform vec3 u_light_position;
+uniform float u_far = 1.0;
+
+// glPolygonOffset does not apply to gl_FragDepth, so the same
+// `factor * slope + units * r` bias is added here
+uniform float u_bias_slope;
+uniform float u_bias_constant;
+
+void main() {
+    float depth = length(WorldPosition - u_light_position) / u_far;
+    float slope = max(abs(dFdx(depth)), abs(dFdy(depth)));
+    // Smallest resolvable difference of a 32-bit float depth near `depth`
+    float r = exp2(floor(log2(max(depth, 1e-6))) - 23.0);
+    gl_FragDepth = depth + u_bias_slope * slope + u_bias_constant * r;
+}
@@ -0,0 +1,96 @@
+// Shadow map lookups, set up by `shadow::ShadowMaps::set_uniforms`.
+// Include after light_types.glsl.
+
+#define MAX_CASCADES 4
+#define MAX_SPOT_SHADOWS 4
+#define MAX_POINT_SHADOWS 4
+
+uniform bool u_shadows_enabled = false;
+uniform vec3 u_camera_forward;
+uniform int u_pcf_radius = 1;
+uniform float u_shadow_normal_offset = 0.02;
+
+// Cascades of the shadowed directional light
+uniform sampler2DArrayShadow u_cascade_maps;
+uniform mat4 u_cascade_matrices[MAX_CASCADES];
+uniform float u_cascade_splits[MAX_CASCADES]; // far view depth of each cascade
+uniform int u_cascade_count = 0;
+
+uniform sampler2DArrayShadow u_spot_shadow_maps;
+uniform mat4 u_spot_shadow_matrices[MAX_SPOT_SHADOWS];
+
+// Distance to the light divided by the far plane, one cube per light
+uniform samplerCubeArrayShadow u_point_shadow_maps;
+uniform float u_point_shadow_far[MAX_POINT_SHADOWS];
+
+const vec3 POINT_PCF_OFFSETS[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+// Percentage closer filtering over a (2 * u_pcf_radius + 1)^2 texel kernel
+float pcf_2d(sampler2DArrayShadow maps, int layer, vec4 clip) {
+    vec3 coords = clip.xyz / clip.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(maps, 0).xy);
+    float lit = 0.0;
+    for (int x = -u_pcf_radius; x <= u_pcf_radius; ++x) {
+        for (int y = -u_pcf_radius; y <= u_pcf_radius; ++y) {
+            vec2 uv = coords.xy + vec2(x, y) * texel;
+            lit += texture(maps, vec4(uv, float(layer), coords.z));
+        }
+    }
+    float size = float(2 * u_pcf_radius + 1);
+    return lit / (size * size);
+}
+
+float pcf_cube(int slot, vec3 to_fragment) {
+    float distance = length(to_fragment);
+    float reference = distance / u_point_shadow_far[slot];
+    if (u_pcf_radius == 0) {
+        return texture(u_point_shadow_maps, vec4(to_fragment, float(slot)), reference);
+    }
+
+    float radius = distance * float(u_pcf_radius) * 2.0 / float(textureSize(u_point_shadow_maps, 0).x);
+    float lit = 0.0;
+    for (int i = 0; i < 20; ++i) {
+        vec3 direction = to_fragment + POINT_PCF_OFFSETS[i] * radius;
+        lit += texture(u_point_shadow_maps, vec4(direction, float(slot)), reference);
+    }
+    return lit / 20.0;
+}
+
+// Fraction of `light` reaching `position` (1 when lit), with `normal` the
+// geometric normal and `view_depth` the distance along u_camera_forward
+float shadow_factor(Light light, vec3 position, vec3 normal, float view_depth) {
+    int slot = int(light.Attenuation.w);
+    if (!u_shadows_enabled || slot < 0) {
+        return 1.0;
+    }
+
+    int light_type = int(light.Position.w);
+    vec3 l = light_type == LIGHT_DIRECTIONAL
+        ? -light.Direction.xyz
+        : normalize(light.Position.xyz - position);
+    // Normal offset, strongest at grazing angles where acne appears
+    vec3 p = position + normal * u_shadow_normal_offset * (1.0 - max(dot(normal, l), 0.0));
+
+    if (light_type == LIGHT_DIRECTIONAL) {
+        for (int i = 0; i < u_cascade_count; ++i) {
+            if (view_depth < u_cascade_splits[i]) {
+                return pcf_2d(u_cascade_maps, i, u_cascade_matrices[i] * vec4(p, 1.0));
+            }
+        }
+        return 1.0;
+    } else if (light_type == LIGHT_SPOT) {
+        return pcf_2d(u_spot_shadow_maps, slot, u_spot_shadow_matrices[slot] * vec4(p, 1.0));
+    } else {
+        return pcf_cube(slot, p - light.Position.xyz);
+    }
+}
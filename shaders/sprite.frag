@@ -0,0 +1,14 @@
+#version 450 core
+
+in VS_OUTPUT {
+    vec2 Uv;
+    vec4 Color;
+} IN;
+
+uniform sampler2D u_texture;
+
+out vec4 Color;
+
+void main() {
+    Color = texture(u_texture, IN.Uv) * IN.Color;
+}
@@ -0,0 +1,18 @@
+#version 450 core
+
+layout (location = 0) in vec2 Position;
+layout (location = 1) in vec2 Uv;
+layout (location = 2) in vec4 Color;
+
+uniform mat4 u_projection;
+
+out VS_OUTPUT {
+    vec2 Uv;
+    vec4 Color;
+} OUT;
+
+void main() {
+    OUT.Uv = Uv;
+    OUT.Color = Color;
+    gl_Position = u_projection * vec4(Position, 0.0, 1.0);
+}
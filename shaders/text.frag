@@ -0,0 +1,26 @@
+#version 450 core
+
+in VS_OUTPUT {
+    vec2 Uv;
+    vec4 Color;
+} IN;
+
+// Glyph coverage, or a signed distance field centred on 0.5 when u_sdf is set
+uniform sampler2D u_atlas;
+uniform bool u_sdf = false;
+
+out vec4 Color;
+
+void main() {
+    float value = texture(u_atlas, IN.Uv).r;
+    float alpha = value;
+    if (u_sdf) {
+        // Antialias over one screen pixel whatever the scale
+        float width = max(fwidth(value), 1e-4);
+        alpha = smoothstep(0.5 - width, 0.5 + width, value);
+    }
+    if (alpha <= 0.0) {
+        discard;
+    }
+    Color = vec4(IN.Color.rgb, IN.Color.a * alpha);
+}
@@ -0,0 +1,19 @@
+#version 450 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec2 Uv;
+layout (location = 2) in vec4 Color;
+
+// Pixel space to clip space for screen text, model-view-projection for world text
+uniform mat4 u_transform;
+
+out VS_OUTPUT {
+    vec2 Uv;
+    vec4 Color;
+} OUT;
+
+void main() {
+    OUT.Uv = Uv;
+    OUT.Color = Color;
+    gl_Position = u_transform * vec4(Position, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 450 core
+
+#include "post.glsl"
+
+#define OPERATOR_REINHARD 0
+#define OPERATOR_ACES 1
+#define OPERATOR_EXPONENTIAL 2
+
+uniform int u_operator = OPERATOR_ACES;
+uniform float u_exposure = 1.0;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 hdr = texture(u_input, IN.Uv);
+    vec3 color = hdr.rgb * u_exposure;
+
+    if (u_operator == OPERATOR_REINHARD) {
+        color = color / (color + vec3(1.0));
+    } else if (u_operator == OPERATOR_ACES) {
+        color = aces(color);
+    } else {
+        color = vec3(1.0) - exp(-color);
+    }
+    Color = vec4(color, hdr.a);
+}
@@ -0,0 +1,15 @@
+#version 450 core
+
+#include "post.glsl"
+
+uniform float u_intensity = 0.5;
+// Distance from the center, in half screen heights, where darkening starts
+uniform float u_radius = 0.75;
+uniform float u_softness = 0.45;
+
+void main() {
+    vec4 color = texture(u_input, IN.Uv);
+    vec2 centered = (IN.Uv - 0.5) * vec2(u_resolution.x / u_resolution.y, 1.0) * 2.0;
+    float falloff = smoothstep(u_radius, u_radius - u_softness, length(centered));
+    Color = vec4(color.rgb * mix(1.0 - u_intensity, 1.0, falloff), color.a);
+}
@@ -0,0 +1,310 @@
+use crate::camera::Camera;
+use crate::color::{Color, ColorSpace};
+use crate::deferred::DeferredRenderer;
+use crate::framebuffer::Framebuffer;
+use crate::input::Input;
+use crate::postprocess::PostProcess;
+use crate::timing::{Clock, FixedTimestep, LoopMode, SystemClock};
+use sdl2::event::{Event, WindowEvent};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// Something sized after the window, kept in sync by `run` once registered
+/// with `Context::register_window_relative`
+pub trait WindowRelative {
+    /// Called with the new drawable size in pixels
+    fn window_resized(&mut self, width: u32, height: u32) -> Result<(), failure::Error>;
+}
+
+impl WindowRelative for Camera {
+    fn window_resized(&mut self, width: u32, height: u32) -> Result<(), failure::Error> {
+        self.set_viewport(width, height);
+        Ok(())
+    }
+}
+
+impl WindowRelative for Framebuffer {
+    fn window_resized(&mut self, width: u32, height: u32) -> Result<(), failure::Error> {
+        Ok(self.resize(width, height)?)
+    }
+}
+
+impl WindowRelative for DeferredRenderer {
+    fn window_resized(&mut self, width: u32, height: u32) -> Result<(), failure::Error> {
+        Ok(self.resize(width, height)?)
+    }
+}
+
+impl WindowRelative for PostProcess {
+    fn window_resized(&mut self, width: u32, height: u32) -> Result<(), failure::Error> {
+        Ok(self.resize(width, height)?)
+    }
+}
+
+/// Window, GL context and subsystems an `App` runs in.
+/// Built from the variables `#[ogl_main]` sets up.
+pub struct Context {
+    pub sdl: sdl2::Sdl,
+    pub video: sdl2::VideoSubsystem,
+    pub window: sdl2::video::Window,
+    pub gl: gl::Gl,
+    /// Input state of the current frame
+    pub input: Input,
+    _gl_context: sdl2::video::GLContext,
+    game_controller: Option<sdl2::GameControllerSubsystem>,
+    gamepads: Vec<sdl2::controller::GameController>,
+    quit_requested: bool,
+    drawable_size: (u32, u32),
+    window_relative: Vec<Weak<RefCell<dyn WindowRelative>>>,
+}
+
+impl Context {
+    pub fn new(
+        sdl: sdl2::Sdl,
+        video: sdl2::VideoSubsystem,
+        window: sdl2::video::Window,
+        gl_context: sdl2::video::GLContext,
+        gl: gl::Gl,
+    ) -> Self {
+        let drawable_size = window.drawable_size();
+        // Gamepads are optional; connected ones are reported as added events
+        let game_controller = sdl.game_controller().ok();
+        Context {
+            sdl,
+            video,
+            window,
+            gl,
+            input: Input::new(),
+            _gl_context: gl_context,
+            game_controller,
+            gamepads: Vec::new(),
+            quit_requested: false,
+            drawable_size,
+            window_relative: Vec::new(),
+        }
+    }
+
+    /// Ends the loop after the current frame
+    pub fn quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    /// Size of the window's framebuffer in pixels, larger than the window
+    /// size on high-DPI displays
+    pub fn drawable_size(&self) -> (u32, u32) {
+        self.drawable_size
+    }
+
+    /// Hides and confines the cursor, reporting only mouse movement, for
+    /// mouse look
+    pub fn set_relative_mouse_mode(&mut self, enabled: bool) {
+        self.sdl.mouse().set_relative_mouse_mode(enabled);
+        self.input.set_relative_mouse_mode(enabled);
+    }
+
+    /// Starts collecting typed text into `Input::text`
+    pub fn start_text_input(&self) {
+        self.video.text_input().start();
+    }
+
+    pub fn stop_text_input(&self) {
+        self.video.text_input().stop();
+    }
+
+    /// Sets the colour the window is cleared to before every frame, encoded
+    /// for the default framebuffer: linear when `GL_FRAMEBUFFER_SRGB` is
+    /// enabled, sRGB otherwise
+    pub fn set_clear_color(&self, color: Color) {
+        let srgb_framebuffer = unsafe { self.gl.IsEnabled(gl::FRAMEBUFFER_SRGB) } == gl::TRUE;
+        let space = if srgb_framebuffer {
+            ColorSpace::Linear
+        } else {
+            ColorSpace::Srgb
+        };
+        let [r, g, b, a] = color.to_space(space);
+        unsafe { self.gl.ClearColor(r, g, b, a) };
+    }
+
+    /// Resizes `target` to the drawable size now and whenever the window
+    /// changes size, until the last `Rc` to it is dropped
+    pub fn register_window_relative<T: WindowRelative + 'static>(
+        &mut self,
+        target: &Rc<RefCell<T>>,
+    ) -> Result<(), failure::Error> {
+        let (width, height) = self.drawable_size;
+        target.borrow_mut().window_resized(width, height)?;
+        let target: Rc<RefCell<dyn WindowRelative>> = target.clone();
+        self.window_relative.push(Rc::downgrade(&target));
+        Ok(())
+    }
+
+    /// Opens gamepads as they are connected and closes them when removed
+    fn update_gamepads(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Some(subsystem) = &self.game_controller {
+                    if let Ok(gamepad) = subsystem.open(which) {
+                        self.gamepads.push(gamepad);
+                    }
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.gamepads.retain(|gamepad| gamepad.instance_id() != which);
+            }
+            _ => {}
+        }
+    }
+
+    /// Updates the viewport and the registered targets if the drawable size
+    /// changed, returning the new size
+    fn update_drawable_size(&mut self) -> Result<Option<(u32, u32)>, failure::Error> {
+        let (width, height) = self.window.drawable_size();
+        // Minimized windows report an empty drawable
+        if (width, height) == self.drawable_size || width == 0 || height == 0 {
+            return Ok(None);
+        }
+        self.drawable_size = (width, height);
+
+        unsafe { self.gl.Viewport(0, 0, width as i32, height as i32) };
+        self.window_relative.retain(|target| target.strong_count() > 0);
+        for target in &self.window_relative {
+            if let Some(target) = target.upgrade() {
+                target.borrow_mut().window_resized(width, height)?;
+            }
+        }
+        Ok(Some((width, height)))
+    }
+}
+
+/// Per-frame data handed to `App::render`
+pub struct Frame<'a> {
+    pub gl: &'a gl::Gl,
+    /// Drawable size of the window in pixels
+    pub window_size: (u32, u32),
+    /// Seconds since the previous frame
+    pub delta_time: f32,
+    /// Seconds since the loop started
+    pub time: f32,
+    /// Number of frames rendered before this one
+    pub index: u64,
+    /// With `LoopMode::Fixed`, how far the time rendered lies between the
+    /// last two fixed updates, from 0 to 1; always 1 with `LoopMode::Variable`
+    pub alpha: f32,
+}
+
+/// Application driven by `run`, which owns the event loop, frame timing,
+/// quitting and buffer swaps. Every hook but `init` and `render` is optional.
+pub trait App: Sized {
+    /// Loads resources once the GL context exists
+    fn init(context: &mut Context) -> Result<Self, failure::Error>;
+
+    /// Called for every SDL event, including the ones `run` handles itself,
+    /// once `Context::input` has seen it
+    fn event(&mut self, context: &mut Context, event: &Event) {}
+
+    /// Advances the application by `delta_time` seconds, once per frame
+    fn update(&mut self, context: &mut Context, delta_time: f32) {}
+
+    /// Advances the simulation by one tick of `step` seconds (`LoopMode::Fixed` only)
+    fn fixed_update(&mut self, context: &mut Context, step: f32) {}
+
+    /// Draws a frame into the cleared default framebuffer
+    fn render(&mut self, frame: &mut Frame);
+
+    /// Called with the new drawable size after the window changed size,
+    /// once the viewport and the window-relative targets follow it
+    fn resize(&mut self, context: &mut Context, width: u32, height: u32) {}
+
+    /// Called once after the last frame
+    fn shutdown(&mut self, context: &mut Context) {}
+}
+
+/// Creates `A` and runs it in real time with a variable timestep until the
+/// window is closed or `Context::quit` is called
+pub fn run<A: App>(context: Context) -> Result<(), failure::Error> {
+    run_with::<A, _>(context, LoopMode::Variable, SystemClock::new())
+}
+
+/// Like `run`, with the timing taken from `clock`
+pub fn run_with<A: App, C: Clock>(
+    mut context: Context,
+    mode: LoopMode,
+    mut clock: C,
+) -> Result<(), failure::Error> {
+    let mut app = A::init(&mut context)?;
+    let mut event_pump = context.sdl.event_pump().map_err(failure::err_msg)?;
+
+    let mut timestep = match mode {
+        LoopMode::Variable => None,
+        LoopMode::Fixed {
+            ticks_per_second,
+            max_steps,
+        } => Some(FixedTimestep::new(ticks_per_second, max_steps)),
+    };
+    let start = clock.now();
+    let mut last_frame = start;
+    let mut index = 0;
+    while !context.quit_requested {
+        context.input.begin_frame();
+        for event in event_pump.poll_iter() {
+            context.input.handle_event(&event);
+            context.update_gamepads(&event);
+            app.event(&mut context, &event);
+            match event {
+                Event::Quit { .. } => context.quit(),
+                // The drawable size can differ from the event's window size
+                Event::Window {
+                    win_event: WindowEvent::Resized(..) | WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    if let Some((width, height)) = context.update_drawable_size()? {
+                        app.resize(&mut context, width, height);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if context.quit_requested {
+            break;
+        }
+
+        let now = clock.now();
+        let delta_time = now.saturating_sub(last_frame).as_secs_f32();
+        last_frame = now;
+        let alpha = match &mut timestep {
+            Some(timestep) => {
+                let step = timestep.step().as_secs_f32();
+                for _ in 0..timestep.advance(now) {
+                    app.fixed_update(&mut context, step);
+                }
+                timestep.alpha()
+            }
+            None => 1.0,
+        };
+        app.update(&mut context, delta_time);
+
+        unsafe {
+            context
+                .gl
+                .Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+        let mut frame = Frame {
+            gl: &context.gl,
+            window_size: context.drawable_size(),
+            delta_time,
+            time: now.saturating_sub(start).as_secs_f32(),
+            index,
+            alpha,
+        };
+        app.render(&mut frame);
+        context.window.gl_swap_window();
+        index += 1;
+    }
+
+    app.shutdown(&mut context);
+    Ok(())
+}
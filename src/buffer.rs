@@ -0,0 +1,276 @@
+use gl::types::*;
+use std::marker::PhantomData;
+
+/// Binding target of an OpenGL buffer object
+pub trait BufferType {
+    const BUFFER_TYPE: GLenum;
+}
+
+/// Vertex attribute data (`GL_ARRAY_BUFFER`)
+pub struct BufferTypeArray;
+impl BufferType for BufferTypeArray {
+    const BUFFER_TYPE: GLenum = gl::ARRAY_BUFFER;
+}
+
+/// Vertex indices (`GL_ELEMENT_ARRAY_BUFFER`)
+pub struct BufferTypeElementArray;
+impl BufferType for BufferTypeElementArray {
+    const BUFFER_TYPE: GLenum = gl::ELEMENT_ARRAY_BUFFER;
+}
+
+/// Shader storage blocks (`GL_SHADER_STORAGE_BUFFER`)
+pub struct BufferTypeShaderStorage;
+impl BufferType for BufferTypeShaderStorage {
+    const BUFFER_TYPE: GLenum = gl::SHADER_STORAGE_BUFFER;
+}
+
+/// Uniform blocks (`GL_UNIFORM_BUFFER`)
+pub struct BufferTypeUniform;
+impl BufferType for BufferTypeUniform {
+    const BUFFER_TYPE: GLenum = gl::UNIFORM_BUFFER;
+}
+
+/// Arguments for `glDispatchComputeIndirect` (`GL_DISPATCH_INDIRECT_BUFFER`)
+pub struct BufferTypeDispatchIndirect;
+impl BufferType for BufferTypeDispatchIndirect {
+    const BUFFER_TYPE: GLenum = gl::DISPATCH_INDIRECT_BUFFER;
+}
+
+/// Wrapper for OpenGL buffer objects
+pub struct Buffer<B: BufferType> {
+    gl: gl::Gl,
+    id: GLuint,
+    _marker: PhantomData<B>,
+}
+
+pub type ArrayBuffer = Buffer<BufferTypeArray>;
+pub type ElementArrayBuffer = Buffer<BufferTypeElementArray>;
+pub type UniformBuffer = Buffer<BufferTypeUniform>;
+pub type DispatchIndirectBuffer = Buffer<BufferTypeDispatchIndirect>;
+
+impl<B: BufferType> Buffer<B> {
+    pub fn new(gl: &gl::Gl) -> Self {
+        let mut id: GLuint = 0;
+        unsafe { gl.GenBuffers(1, &mut id) };
+
+        Buffer {
+            gl: gl.clone(),
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn bind(&self) {
+        unsafe { self.gl.BindBuffer(B::BUFFER_TYPE, self.id) };
+    }
+
+    pub fn unbind(&self) {
+        unsafe { self.gl.BindBuffer(B::BUFFER_TYPE, 0) };
+    }
+
+    /// Uploads `data` to the bound buffer with the given usage hint
+    /// (`gl::STATIC_DRAW`, `gl::DYNAMIC_DRAW`, ...), replacing its storage.
+    pub fn data<T>(&self, data: &[T], usage: GLenum) {
+        unsafe {
+            self.gl.BufferData(
+                B::BUFFER_TYPE,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const GLvoid,
+                usage,
+            );
+        }
+    }
+
+    /// Overwrites part of the bound buffer starting at `offset` bytes
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        unsafe {
+            self.gl.BufferSubData(
+                B::BUFFER_TYPE,
+                offset as GLintptr,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const GLvoid,
+            );
+        }
+    }
+
+    /// Binds the buffer to an indexed binding point of an indexed target
+    /// (uniform, shader storage, ...)
+    pub fn bind_base(&self, index: GLuint) {
+        unsafe { self.gl.BindBufferBase(B::BUFFER_TYPE, index, self.id) };
+    }
+
+    pub fn static_draw_data<T>(&self, data: &[T]) {
+        self.data(data, gl::STATIC_DRAW);
+    }
+
+    pub fn dynamic_draw_data<T>(&self, data: &[T]) {
+        self.data(data, gl::DYNAMIC_DRAW);
+    }
+}
+
+impl<B: BufferType> Drop for Buffer<B> {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteBuffers(1, &self.id) };
+    }
+}
+
+/// Marks types whose Rust memory layout matches the GLSL `std430` layout,
+/// so slices of them can be copied verbatim into storage buffers.
+///
+/// # Safety
+/// The type must be `#[repr(C)]` (or a primitive), contain no padding that
+/// differs from `std430`, and have a size that is a multiple of its `std430`
+/// alignment. Note that `vec3` is aligned to 16 bytes, so a `[f32; 3]` field
+/// must be followed by a scalar or explicit padding.
+pub unsafe trait Std430: Copy {}
+
+unsafe impl Std430 for f32 {}
+unsafe impl Std430 for i32 {}
+unsafe impl Std430 for u32 {}
+unsafe impl Std430 for [f32; 2] {}
+unsafe impl Std430 for [f32; 4] {}
+unsafe impl Std430 for [i32; 2] {}
+unsafe impl Std430 for [i32; 4] {}
+unsafe impl Std430 for [u32; 2] {}
+unsafe impl Std430 for [u32; 4] {}
+unsafe impl Std430 for [[f32; 4]; 4] {}
+unsafe impl Std430 for crate::color::Color {}
+
+/// Typed shader storage buffer holding `len` elements of `T` laid out as `std430`
+pub struct StorageBuffer<T: Std430> {
+    buffer: Buffer<BufferTypeShaderStorage>,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std430> StorageBuffer<T> {
+    /// Creates a storage buffer initialized with `data`
+    pub fn from_slice(gl: &gl::Gl, data: &[T], usage: GLenum) -> Self {
+        let buffer = Buffer::new(gl);
+        buffer.bind();
+        buffer.data(data, usage);
+        buffer.unbind();
+
+        StorageBuffer {
+            buffer,
+            len: data.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a storage buffer of `len` elements with undefined contents
+    pub fn with_len(gl: &gl::Gl, len: usize, usage: GLenum) -> Self {
+        let buffer = Buffer::new(gl);
+        buffer.bind();
+        unsafe {
+            gl.BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                (len * std::mem::size_of::<T>()) as GLsizeiptr,
+                std::ptr::null(),
+                usage,
+            );
+        }
+        buffer.unbind();
+
+        StorageBuffer {
+            buffer,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.buffer.id()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Binds the buffer to the indexed `binding` point used by
+    /// `layout(std430, binding = N) buffer` blocks.
+    pub fn bind_base(&self, binding: GLuint) {
+        unsafe {
+            self.buffer
+                .gl
+                .BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.buffer.id())
+        };
+    }
+
+    /// Overwrites elements starting at `offset` (in elements, not bytes)
+    pub fn write(&self, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.len, "write out of bounds");
+
+        self.buffer.bind();
+        unsafe {
+            self.buffer.gl.BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * std::mem::size_of::<T>()) as GLintptr,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const GLvoid,
+            );
+        }
+        self.buffer.unbind();
+    }
+
+    /// Copies the whole buffer back to the CPU.
+    /// Issue a `Barrier::BUFFER_UPDATE` after the writing dispatch first.
+    pub fn read(&self) -> Vec<T> {
+        let mut out: Vec<T> = Vec::with_capacity(self.len);
+
+        self.buffer.bind();
+        unsafe {
+            self.buffer.gl.GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                (self.len * std::mem::size_of::<T>()) as GLsizeiptr,
+                out.as_mut_ptr() as *mut GLvoid,
+            );
+            out.set_len(self.len);
+        }
+        self.buffer.unbind();
+
+        out
+    }
+}
+
+/// Wrapper for OpenGL vertex array objects
+pub struct VertexArray {
+    gl: gl::Gl,
+    id: GLuint,
+}
+
+impl VertexArray {
+    pub fn new(gl: &gl::Gl) -> Self {
+        let mut id: GLuint = 0;
+        unsafe { gl.GenVertexArrays(1, &mut id) };
+
+        VertexArray { gl: gl.clone(), id }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn bind(&self) {
+        unsafe { self.gl.BindVertexArray(self.id) };
+    }
+
+    pub fn unbind(&self) {
+        unsafe { self.gl.BindVertexArray(0) };
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteVertexArrays(1, &self.id) };
+    }
+}
@@ -0,0 +1,501 @@
+use crate::math::{Mat3, Mat4, Quat, Vec2, Vec3};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+
+/// Projection used by a `Camera`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// `height` is the visible height in world units; the width follows the aspect ratio
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/// Camera holding a projection and a view given by position and orientation.
+/// The camera looks down its local -Z axis with +Y up, as in OpenGL.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub projection: Projection,
+    viewport: (u32, u32),
+}
+
+impl Camera {
+    pub fn new(projection: Projection, viewport_width: u32, viewport_height: u32) -> Self {
+        Camera {
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            projection,
+            viewport: (viewport_width.max(1), viewport_height.max(1)),
+        }
+    }
+
+    pub fn perspective(fov_y: f32, near: f32, far: f32, width: u32, height: u32) -> Self {
+        Camera::new(Projection::Perspective { fov_y, near, far }, width, height)
+    }
+
+    pub fn orthographic(view_height: f32, near: f32, far: f32, width: u32, height: u32) -> Self {
+        Camera::new(
+            Projection::Orthographic {
+                height: view_height,
+                near,
+                far,
+            },
+            width,
+            height,
+        )
+    }
+
+    /// Viewport size in pixels
+    pub fn viewport(&self) -> (u32, u32) {
+        self.viewport
+    }
+
+    /// Updates the viewport size, and so the aspect ratio
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.viewport = (width.max(1), height.max(1));
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.viewport.0 as f32 / self.viewport.1 as f32
+    }
+
+    /// Keeps the aspect ratio in sync with window resize events
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::Window {
+            win_event: WindowEvent::SizeChanged(width, height),
+            ..
+        } = *event
+        {
+            self.set_viewport(width as u32, height as u32);
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * -Vec3::Z
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    /// Turns the camera towards `target`, keeping `up` as the vertical reference
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        let view = Mat4::look_at(self.position, target, up);
+        let rotation = Mat3::from_mat4(&view).transpose();
+        self.orientation = Quat::from_mat3(&rotation).normalize();
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::from_quat(self.orientation.conjugate()) * Mat4::from_translation(-self.position)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        let aspect = self.aspect();
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                Mat4::perspective(fov_y, aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_h = height / 2.0;
+                let half_w = half_h * aspect;
+                Mat4::orthographic(-half_w, half_w, -half_h, half_h, near, far)
+            }
+        }
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// World-space ray through the pixel (`x`, `y`), measured from the
+    /// top-left corner of the viewport as SDL reports mouse positions.
+    /// `None` when the view-projection matrix cannot be inverted.
+    pub fn screen_ray(&self, x: f32, y: f32) -> Option<Ray> {
+        let ndc_x = 2.0 * x / self.viewport.0 as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / self.viewport.1 as f32;
+
+        let inverse = self.view_projection().inverse()?;
+        let near = inverse.transform_point3(Vec3::new(ndc_x, ndc_y, -1.0));
+        let far = inverse.transform_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+
+        Some(Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    /// Projects a world-space point to pixel coordinates (top-left origin),
+    /// or `None` when it is behind the camera
+    pub fn world_to_screen(&self, point: Vec3) -> Option<Vec2> {
+        let clip = self.view_projection() * point.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = clip.truncate() / clip.w;
+        Some(Vec2::new(
+            (ndc.x + 1.0) / 2.0 * self.viewport.0 as f32,
+            (1.0 - ndc.y) / 2.0 * self.viewport.1 as f32,
+        ))
+    }
+}
+
+/// Half-line used for picking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Unit direction
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance along the ray to the plane through `point` with `normal`
+    pub fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<f32> {
+        let denom = normal.dot(self.direction);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+
+        let t = (point - self.origin).dot(normal) / denom;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// Distance along the ray to the nearest hit on the sphere
+    pub fn intersect_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let oc = self.origin - center;
+        let b = oc.dot(self.direction);
+        let c = oc.length_squared() - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrt_d = discriminant.sqrt();
+        [-b - sqrt_d, -b + sqrt_d].into_iter().find(|&t| t >= 0.0)
+    }
+
+    /// Distance along the ray to the axis-aligned box `[min, max]` (slab test)
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inv = 1.0 / self.direction[axis];
+            let mut t0 = (min[axis] - self.origin[axis]) * inv;
+            let mut t1 = (max[axis] - self.origin[axis]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+/// Moves a camera in response to the SDL events polled by the render loop
+pub trait CameraController {
+    /// Feeds one polled event to the controller
+    fn handle_event(&mut self, event: &Event);
+
+    /// Applies the accumulated input to `camera`, `dt` being the frame time in seconds
+    fn update(&mut self, camera: &mut Camera, dt: f32);
+}
+
+/// Orbits around a target point: left drag rotates, middle drag pans,
+/// the wheel zooms
+pub struct OrbitController {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel of mouse motion
+    pub rotate_speed: f32,
+    /// Fraction of the distance per wheel step
+    pub zoom_speed: f32,
+    rotate_delta: Vec2,
+    pan_delta: Vec2,
+    zoom_delta: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            rotate_speed: 0.005,
+            zoom_speed: 0.1,
+            rotate_delta: Vec2::ZERO,
+            pan_delta: Vec2::ZERO,
+            zoom_delta: 0.0,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::MouseMotion {
+                mousestate,
+                xrel,
+                yrel,
+                ..
+            } => {
+                let delta = Vec2::new(xrel as f32, yrel as f32);
+                if mousestate.left() {
+                    self.rotate_delta += delta;
+                } else if mousestate.middle() {
+                    self.pan_delta += delta;
+                }
+            }
+            Event::MouseWheel { y, .. } => self.zoom_delta += y as f32,
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, _dt: f32) {
+        const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+        self.yaw -= self.rotate_delta.x * self.rotate_speed;
+        self.pitch =
+            (self.pitch - self.rotate_delta.y * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+        self.distance = (self.distance * (1.0 - self.zoom_delta * self.zoom_speed))
+            .clamp(self.min_distance, self.max_distance);
+
+        camera.orientation = Quat::from_euler(self.yaw, self.pitch, 0.0);
+
+        // Pan so the target follows the cursor at the target's depth
+        let pixels_to_world = pixel_size_at(camera, self.distance);
+        self.target +=
+            (camera.right() * -self.pan_delta.x + camera.up() * self.pan_delta.y) * pixels_to_world;
+
+        camera.position = self.target - camera.forward() * self.distance;
+
+        self.rotate_delta = Vec2::ZERO;
+        self.pan_delta = Vec2::ZERO;
+        self.zoom_delta = 0.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct FlyKeys {
+    forward: bool,
+    back: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    fast: bool,
+}
+
+/// Free-flying camera: WASD moves, Space/C rises and sinks, Shift speeds up,
+/// dragging with the right mouse button looks around
+pub struct FlyController {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// World units per second
+    pub speed: f32,
+    /// Speed multiplier while Shift is held
+    pub boost: f32,
+    /// Radians per pixel of mouse motion
+    pub sensitivity: f32,
+    keys: FlyKeys,
+    look_delta: Vec2,
+}
+
+impl FlyController {
+    pub fn new(speed: f32) -> Self {
+        FlyController {
+            yaw: 0.0,
+            pitch: 0.0,
+            speed,
+            boost: 4.0,
+            sensitivity: 0.003,
+            keys: FlyKeys::default(),
+            look_delta: Vec2::ZERO,
+        }
+    }
+
+    fn set_key(&mut self, keycode: Keycode, pressed: bool) {
+        match keycode {
+            Keycode::W => self.keys.forward = pressed,
+            Keycode::S => self.keys.back = pressed,
+            Keycode::A => self.keys.left = pressed,
+            Keycode::D => self.keys.right = pressed,
+            Keycode::Space => self.keys.up = pressed,
+            Keycode::C => self.keys.down = pressed,
+            Keycode::LShift | Keycode::RShift => self.keys.fast = pressed,
+            _ => {}
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => self.set_key(keycode, true),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => self.set_key(keycode, false),
+            Event::MouseMotion {
+                mousestate,
+                xrel,
+                yrel,
+                ..
+            } if mousestate.right() => {
+                self.look_delta += Vec2::new(xrel as f32, yrel as f32);
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, dt: f32) {
+        const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+        self.yaw -= self.look_delta.x * self.sensitivity;
+        self.pitch =
+            (self.pitch - self.look_delta.y * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        self.look_delta = Vec2::ZERO;
+        camera.orientation = Quat::from_euler(self.yaw, self.pitch, 0.0);
+
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let keys = self.keys;
+        let direction = camera.forward() * axis(keys.forward, keys.back)
+            + camera.right() * axis(keys.right, keys.left)
+            + Vec3::Y * axis(keys.up, keys.down);
+
+        let speed = if keys.fast {
+            self.speed * self.boost
+        } else {
+            self.speed
+        };
+        camera.position += direction.normalize() * speed * dt;
+    }
+}
+
+/// 2D controller for orthographic cameras: dragging with the left or middle
+/// button pans, the wheel zooms towards the cursor
+pub struct PanZoomController {
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Fraction of the visible height per wheel step
+    pub zoom_speed: f32,
+    pan_delta: Vec2,
+    zoom_delta: f32,
+    cursor: Vec2,
+}
+
+impl PanZoomController {
+    pub fn new() -> Self {
+        PanZoomController {
+            min_height: 0.01,
+            max_height: 10000.0,
+            zoom_speed: 0.1,
+            pan_delta: Vec2::ZERO,
+            zoom_delta: 0.0,
+            cursor: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for PanZoomController {
+    fn default() -> Self {
+        PanZoomController::new()
+    }
+}
+
+impl CameraController for PanZoomController {
+    fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::MouseMotion {
+                mousestate,
+                x,
+                y,
+                xrel,
+                yrel,
+                ..
+            } => {
+                self.cursor = Vec2::new(x as f32, y as f32);
+                if mousestate.left() || mousestate.middle() {
+                    self.pan_delta += Vec2::new(xrel as f32, yrel as f32);
+                }
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => self.cursor = Vec2::new(x as f32, y as f32),
+            Event::MouseWheel { y, .. } => self.zoom_delta += y as f32,
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, _dt: f32) {
+        let height = match camera.projection {
+            Projection::Orthographic { height, .. } => height,
+            Projection::Perspective { .. } => return,
+        };
+
+        let (_, viewport_height) = camera.viewport;
+        let pixels_to_world = height / viewport_height as f32;
+        camera.position += Vec3::new(-self.pan_delta.x, self.pan_delta.y, 0.0) * pixels_to_world;
+
+        if self.zoom_delta != 0.0 {
+            // Keep the point under the cursor fixed while zooming
+            let before = camera.screen_ray(self.cursor.x, self.cursor.y);
+            if let Projection::Orthographic { height, .. } = &mut camera.projection {
+                *height = (*height * (1.0 - self.zoom_delta * self.zoom_speed))
+                    .clamp(self.min_height, self.max_height);
+            }
+            let after = camera.screen_ray(self.cursor.x, self.cursor.y);
+            if let (Some(before), Some(after)) = (before, after) {
+                let offset = before.origin - after.origin;
+                camera.position += Vec3::new(offset.x, offset.y, 0.0);
+            }
+        }
+
+        self.pan_delta = Vec2::ZERO;
+        self.zoom_delta = 0.0;
+    }
+}
+
+/// World units covered by one pixel at `depth` in front of the camera
+fn pixel_size_at(camera: &Camera, depth: f32) -> f32 {
+    let (_, viewport_height) = camera.viewport;
+    match camera.projection {
+        Projection::Perspective { fov_y, .. } => {
+            2.0 * depth * (fov_y / 2.0).tan() / viewport_height as f32
+        }
+        Projection::Orthographic { height, .. } => height / viewport_height as f32,
+    }
+}
@@ -0,0 +1,275 @@
+use crate::buffer::{DispatchIndirectBuffer, Std430};
+use crate::program::Error;
+use crate::resources::Resources;
+use crate::{Program, Shader};
+use gl::types::*;
+use std::ops::{BitOr, BitOrAssign};
+
+/// Compute shader program loaded from a single `.comp` resource
+pub struct ComputeProgram {
+    gl: gl::Gl,
+    program: Program,
+    work_group_size: [GLint; 3],
+}
+
+impl ComputeProgram {
+    /// Loads and links `<name>.comp` from the resources
+    pub fn from_resources(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Self, Error> {
+        let shader = Shader::from_resources(gl, res, &format!("{}.comp", name))?;
+
+        let program = Program::from_shaders(gl, &[shader]).map_err(|e| Error::LinkError {
+            name: name.to_string(),
+            message: e,
+        })?;
+
+        let mut work_group_size: [GLint; 3] = [0; 3];
+        unsafe {
+            gl.GetProgramiv(
+                program.id(),
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                work_group_size.as_mut_ptr(),
+            )
+        };
+
+        Ok(ComputeProgram {
+            gl: gl.clone(),
+            program,
+            work_group_size,
+        })
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// The `local_size_x/y/z` declared in the shader
+    pub fn work_group_size(&self) -> [GLint; 3] {
+        self.work_group_size
+    }
+
+    /// Runs `x * y * z` work groups
+    pub fn dispatch(&self, x: GLuint, y: GLuint, z: GLuint) {
+        self.program.set_used();
+        unsafe { self.gl.DispatchCompute(x, y, z) };
+    }
+
+    /// Runs enough work groups to cover `x * y * z` invocations,
+    /// rounding up by the shader's local size.
+    pub fn dispatch_invocations(&self, x: GLuint, y: GLuint, z: GLuint) {
+        let groups = |n: GLuint, size: GLint| n.div_ceil(size as GLuint);
+        let [sx, sy, sz] = self.work_group_size;
+        self.dispatch(groups(x, sx), groups(y, sy), groups(z, sz));
+    }
+
+    /// Runs the work group counts stored in `buffer` at byte `offset`,
+    /// laid out as a `DispatchIndirectCommand`.
+    pub fn dispatch_indirect(&self, buffer: &DispatchIndirectBuffer, offset: usize) {
+        self.program.set_used();
+        buffer.bind();
+        unsafe { self.gl.DispatchComputeIndirect(offset as GLintptr) };
+        buffer.unbind();
+    }
+
+    /// Binds a texture level to an image unit for `image2D`-style access
+    /// # Arguments
+    /// * `unit` - The `binding` of the image uniform in the shader.
+    /// * `texture` - The texture object name.
+    /// * `level` - The mipmap level to bind.
+    /// * `access` - How the shader accesses the image.
+    /// * `format` - The sized internal format, e.g. `gl::RGBA32F`.
+    pub fn bind_image(
+        &self,
+        unit: GLuint,
+        texture: GLuint,
+        level: GLint,
+        access: ImageAccess,
+        format: GLenum,
+    ) {
+        unsafe {
+            self.gl.BindImageTexture(
+                unit,
+                texture,
+                level,
+                gl::TRUE,
+                0,
+                access.as_gl(),
+                format,
+            )
+        };
+    }
+}
+
+/// Access qualifier of an image unit binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl ImageAccess {
+    fn as_gl(self) -> GLenum {
+        match self {
+            ImageAccess::ReadOnly => gl::READ_ONLY,
+            ImageAccess::WriteOnly => gl::WRITE_ONLY,
+            ImageAccess::ReadWrite => gl::READ_WRITE,
+        }
+    }
+}
+
+/// Layout of the arguments read by `dispatch_indirect`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: GLuint,
+    pub num_groups_y: GLuint,
+    pub num_groups_z: GLuint,
+}
+
+unsafe impl Std430 for DispatchIndirectCommand {}
+
+/// Set of `glMemoryBarrier` bits, combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Barrier(GLbitfield);
+
+impl Barrier {
+    /// Vertex data sourced from buffers written by shaders
+    pub const VERTEX_ATTRIB_ARRAY: Barrier = Barrier(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    /// Indices sourced from buffers written by shaders
+    pub const ELEMENT_ARRAY: Barrier = Barrier(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    /// Uniform blocks sourced from buffers written by shaders
+    pub const UNIFORM: Barrier = Barrier(gl::UNIFORM_BARRIER_BIT);
+    /// Texture sampling of images written by shaders
+    pub const TEXTURE_FETCH: Barrier = Barrier(gl::TEXTURE_FETCH_BARRIER_BIT);
+    /// Image load/store after image writes
+    pub const SHADER_IMAGE_ACCESS: Barrier = Barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    /// Indirect draw/dispatch arguments written by shaders
+    pub const COMMAND: Barrier = Barrier(gl::COMMAND_BARRIER_BIT);
+    /// Buffer reads and writes from the CPU side (`glGetBufferSubData`, mapping)
+    pub const BUFFER_UPDATE: Barrier = Barrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+    /// Texture reads and writes from the CPU side (`glGetTexImage`, ...)
+    pub const TEXTURE_UPDATE: Barrier = Barrier(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    /// Framebuffer reads and writes of images written by shaders
+    pub const FRAMEBUFFER: Barrier = Barrier(gl::FRAMEBUFFER_BARRIER_BIT);
+    /// Shader storage block access after writes
+    pub const SHADER_STORAGE: Barrier = Barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Barrier = Barrier(gl::ALL_BARRIER_BITS);
+
+    pub fn bits(self) -> GLbitfield {
+        self.0
+    }
+}
+
+impl BitOr for Barrier {
+    type Output = Barrier;
+
+    fn bitor(self, rhs: Barrier) -> Barrier {
+        Barrier(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Barrier {
+    fn bitor_assign(&mut self, rhs: Barrier) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Orders shader writes before the later accesses described by `barrier`
+pub fn memory_barrier(gl: &gl::Gl, barrier: Barrier) {
+    unsafe { gl.MemoryBarrier(barrier.bits()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::StorageBuffer;
+    use std::path::Path;
+
+    const COUNT: usize = 1000;
+
+    fn saxpy(a: f32, x: &[f32], y: &[f32]) -> Vec<f32> {
+        x.iter().zip(y).map(|(x, y)| a * x + y).collect()
+    }
+
+    /// The GPU may fuse the multiply-add, so allow for one rounding step
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (a - e).abs() <= 1e-5 * e.abs().max(1.0),
+                "element {}: {} != {}",
+                i,
+                a,
+                e
+            );
+        }
+    }
+
+    /// Runs `saxpy.comp` directly and through `dispatch_indirect` and checks
+    /// the results against the CPU.
+    /// Needs an OpenGL 4.5 driver; run it on Mesa's software rasterizer with
+    /// `LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn saxpy_matches_cpu() {
+        let sdl = sdl2::init().unwrap();
+        let video = sdl.video().unwrap();
+        let gl_attr = video.gl_attr();
+        gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+        gl_attr.set_context_version(4, 5);
+        let window = video
+            .window("compute", 16, 16)
+            .opengl()
+            .hidden()
+            .build()
+            .unwrap();
+        let _context = window.gl_create_context().unwrap();
+        let gl = gl::Gl::load_with(|s| video.gl_get_proc_address(s) as *const std::os::raw::c_void);
+
+        // Test binaries run from target/<profile>/deps
+        let res = Resources::from_rel_path(Path::new("../shaders")).unwrap();
+        let program = ComputeProgram::from_resources(&gl, &res, "saxpy").unwrap();
+        assert_eq!(program.work_group_size(), [64, 1, 1]);
+
+        let x: Vec<f32> = (0..COUNT).map(|i| i as f32 * 0.5).collect();
+        let y: Vec<f32> = (0..COUNT).map(|i| 3.0 - i as f32).collect();
+        let xs = StorageBuffer::from_slice(&gl, &x, gl::STATIC_DRAW);
+        let ys = StorageBuffer::from_slice(&gl, &y, gl::DYNAMIC_DRAW);
+        xs.bind_base(0);
+        ys.bind_base(1);
+
+        program.program().set_used();
+        program.program().set_uniform("u_a", &2.0f32);
+        program.program().set_uniform("u_count", &(COUNT as u32));
+        program.dispatch_invocations(COUNT as GLuint, 1, 1);
+        memory_barrier(&gl, Barrier::BUFFER_UPDATE);
+
+        let expected = saxpy(2.0, &x, &y);
+        assert_close(&ys.read(), &expected);
+
+        // Same again with the group count read from a buffer, after a
+        // padding command so the offset is exercised too
+        let commands = [
+            DispatchIndirectCommand {
+                num_groups_x: 0,
+                num_groups_y: 0,
+                num_groups_z: 0,
+            },
+            DispatchIndirectCommand {
+                num_groups_x: COUNT.div_ceil(64) as GLuint,
+                num_groups_y: 1,
+                num_groups_z: 1,
+            },
+        ];
+        let indirect = DispatchIndirectBuffer::new(&gl);
+        indirect.bind();
+        indirect.static_draw_data(&commands);
+        indirect.unbind();
+
+        program.program().set_uniform("u_a", &-1.0f32);
+        program.dispatch_indirect(&indirect, std::mem::size_of::<DispatchIndirectCommand>());
+        memory_barrier(&gl, Barrier::BUFFER_UPDATE);
+
+        assert_close(&ys.read(), &saxpy(-1.0, &x, &expected));
+    }
+}
@@ -0,0 +1,317 @@
+use crate::camera::Camera;
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::program;
+use crate::render_state::StateCache;
+use crate::resources::Resources;
+#[cfg(feature = "debug_draw")]
+use crate::{
+    buffer::{ArrayBuffer, VertexArray},
+    render_state::{Blend, CompareFunc, RenderState},
+    Program,
+};
+#[cfg(feature = "debug_draw")]
+use gl::types::*;
+
+/// Segments used for each circle of `DebugDraw::sphere`
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// How long a debug primitive stays on screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lifetime {
+    /// Drawn by the next `n` calls to `DebugDraw::draw`
+    Frames(u32),
+    /// Drawn until this much time has been passed to `DebugDraw::draw`,
+    /// and at least once
+    Seconds(f32),
+}
+
+#[cfg(feature = "debug_draw")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DebugLine {
+    a: Vec3,
+    b: Vec3,
+    color: Vec4,
+    depth_test: bool,
+    lifetime: Lifetime,
+}
+
+#[cfg(feature = "debug_draw")]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DebugVertex {
+    position: Vec3,
+    color: Vec4,
+}
+
+/// Immediate-mode line drawing for visualising bounds, directions and such.
+///
+/// Primitives can be queued from anywhere holding the `DebugDraw`, with the
+/// `depth_test` and `lifetime` current at the time, and are drawn from one
+/// dynamic line buffer by `draw`.
+/// Everything compiles to no-ops unless the `debug_draw` feature is enabled.
+pub struct DebugDraw {
+    /// Whether lines queued from now on are hidden behind scene geometry
+    pub depth_test: bool,
+    /// Lifetime of lines queued from now on
+    pub lifetime: Lifetime,
+    #[cfg(feature = "debug_draw")]
+    gl: gl::Gl,
+    #[cfg(feature = "debug_draw")]
+    program: Program,
+    #[cfg(feature = "debug_draw")]
+    vao: VertexArray,
+    #[cfg(feature = "debug_draw")]
+    vbo: ArrayBuffer,
+    #[cfg(feature = "debug_draw")]
+    lines: Vec<DebugLine>,
+}
+
+impl DebugDraw {
+    pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Self, program::Error> {
+        #[cfg(feature = "debug_draw")]
+        {
+            let program = Program::from_resources(gl, res, "debug_draw")?;
+            let vbo = ArrayBuffer::new(gl);
+            let vao = VertexArray::new(gl);
+            vao.bind();
+            vbo.bind();
+            let stride = std::mem::size_of::<DebugVertex>();
+            let attributes: [(GLuint, GLint, usize); 2] = [
+                (0, 3, std::mem::offset_of!(DebugVertex, position)),
+                (1, 4, std::mem::offset_of!(DebugVertex, color)),
+            ];
+            for (location, size, offset) in attributes {
+                unsafe {
+                    gl.EnableVertexAttribArray(location);
+                    gl.VertexAttribPointer(
+                        location,
+                        size,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        stride as GLsizei,
+                        offset as *const GLvoid,
+                    );
+                }
+            }
+            vao.unbind();
+            vbo.unbind();
+
+            Ok(DebugDraw {
+                depth_test: true,
+                lifetime: Lifetime::Frames(1),
+                gl: gl.clone(),
+                program,
+                vao,
+                vbo,
+                lines: Vec::new(),
+            })
+        }
+        #[cfg(not(feature = "debug_draw"))]
+        Ok(DebugDraw {
+            depth_test: true,
+            lifetime: Lifetime::Frames(1),
+        })
+    }
+
+    /// Whether primitives are actually drawn, i.e. the `debug_draw` feature is enabled
+    pub fn enabled(&self) -> bool {
+        cfg!(feature = "debug_draw")
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec4) {
+        #[cfg(feature = "debug_draw")]
+        self.lines.push(DebugLine {
+            a,
+            b,
+            color,
+            depth_test: self.depth_test,
+            lifetime: self.lifetime,
+        });
+    }
+
+    /// Axis-aligned box
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        if !self.enabled() {
+            return;
+        }
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        self.box_edges(corner, color);
+    }
+
+    /// Three great circles, one per axis plane
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        if !self.enabled() {
+            return;
+        }
+        let step = std::f32::consts::TAU / CIRCLE_SEGMENTS as f32;
+        for segment in 0..CIRCLE_SEGMENTS {
+            let (sin_a, cos_a) = (segment as f32 * step).sin_cos();
+            let (sin_b, cos_b) = ((segment + 1) as f32 * step).sin_cos();
+            let circles = [
+                (Vec3::new(cos_a, sin_a, 0.0), Vec3::new(cos_b, sin_b, 0.0)),
+                (Vec3::new(cos_a, 0.0, sin_a), Vec3::new(cos_b, 0.0, sin_b)),
+                (Vec3::new(0.0, cos_a, sin_a), Vec3::new(0.0, cos_b, sin_b)),
+            ];
+            for (a, b) in circles {
+                self.line(center + a * radius, center + b * radius, color);
+            }
+        }
+    }
+
+    /// Edges of the volume a view-projection matrix maps to clip space,
+    /// e.g. `camera.view_projection()` or a light's shadow matrix
+    pub fn frustum(&mut self, view_projection: &Mat4, color: Vec4) {
+        if !self.enabled() {
+            return;
+        }
+        let inverse = match view_projection.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let corner = |i: usize| {
+            inverse.transform_point3(Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            ))
+        };
+        self.box_edges(corner, color);
+    }
+
+    /// Square grid on the XZ plane through `center`, `size` wide with
+    /// `divisions` cells per side
+    pub fn grid(&mut self, center: Vec3, size: f32, divisions: u32, color: Vec4) {
+        if !self.enabled() || divisions == 0 {
+            return;
+        }
+        let half = size * 0.5;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(
+                center + Vec3::new(offset, 0.0, -half),
+                center + Vec3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                center + Vec3::new(-half, 0.0, offset),
+                center + Vec3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    /// The X, Y and Z axes of `transform` in red, green and blue, `size` long
+    pub fn axes(&mut self, transform: &Mat4, size: f32) {
+        if !self.enabled() {
+            return;
+        }
+        let origin = transform.translation();
+        let colors = [
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+            Vec4::new(0.0, 1.0, 0.0, 1.0),
+            Vec4::new(0.0, 0.0, 1.0, 1.0),
+        ];
+        for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].into_iter().zip(colors) {
+            let direction = transform.transform_vector3(axis).normalize();
+            self.line(origin, origin + direction * size, color);
+        }
+    }
+
+    /// Drops every queued primitive
+    pub fn clear(&mut self) {
+        #[cfg(feature = "debug_draw")]
+        self.lines.clear();
+    }
+
+    /// Draws the queued lines through `camera`, then ages them by one frame
+    /// and `delta_time` seconds, dropping the expired ones
+    pub fn draw(&mut self, cache: &mut StateCache, camera: &Camera, delta_time: f32) {
+        #[cfg(feature = "debug_draw")]
+        {
+            if self.lines.is_empty() {
+                return;
+            }
+
+            // Depth tested lines first, so one upload serves both draws
+            self.lines.sort_by_key(|line| !line.depth_test);
+            let tested = self.lines.iter().filter(|line| line.depth_test).count();
+            let vertices: Vec<DebugVertex> = self
+                .lines
+                .iter()
+                .flat_map(|line| {
+                    [
+                        DebugVertex {
+                            position: line.a,
+                            color: line.color,
+                        },
+                        DebugVertex {
+                            position: line.b,
+                            color: line.color,
+                        },
+                    ]
+                })
+                .collect();
+
+            self.program.set_used();
+            self.program
+                .set_uniform("u_view_projection", &camera.view_projection());
+            self.vao.bind();
+            self.vbo.bind();
+            self.vbo.dynamic_draw_data(&vertices);
+
+            let ranges = [
+                (Some(CompareFunc::LessEqual), 0, tested),
+                (None, tested, self.lines.len()),
+            ];
+            for (depth_test, start, end) in ranges {
+                if start == end {
+                    continue;
+                }
+                cache.apply(&RenderState {
+                    blend: Some(Blend::ALPHA),
+                    depth_test,
+                    depth_write: false,
+                    ..RenderState::default()
+                });
+                unsafe {
+                    self.gl.DrawArrays(
+                        gl::LINES,
+                        (start * 2) as GLint,
+                        ((end - start) * 2) as GLsizei,
+                    );
+                }
+            }
+            self.vbo.unbind();
+            self.vao.unbind();
+
+            self.lines.retain_mut(|line| match &mut line.lifetime {
+                Lifetime::Frames(frames) => {
+                    *frames = frames.saturating_sub(1);
+                    *frames > 0
+                }
+                Lifetime::Seconds(seconds) => {
+                    *seconds -= delta_time;
+                    *seconds > 0.0
+                }
+            });
+        }
+    }
+
+    /// Twelve edges of the box with corners `corner(0..8)`, bit 0, 1 and 2 of
+    /// the index selecting the x, y and z side
+    fn box_edges<F: Fn(usize) -> Vec3>(&mut self, corner: F, color: Vec4) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::buffer::{StorageBuffer, VertexArray};
+use crate::camera::{Camera, Projection};
+use crate::compute::{self, Barrier, ComputeProgram, ImageAccess};
+use crate::framebuffer::{self, DepthAttachment, Framebuffer, FramebufferDesc};
+use crate::ibl::{self, Environment};
+use crate::lighting::{GpuLight, Light};
+use crate::math::{Mat4, Vec3};
+use crate::program;
+use crate::render_state::{RenderState, StateCache};
+use crate::resources::Resources;
+use crate::shadow::{self, ShadowMaps};
+use crate::texture::TextureFormat;
+use crate::Program;
+use gl::types::*;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load deferred shading program: {}", inner)]
+    Program { inner: program::Error },
+    #[fail(display = "Deferred shading framebuffer is incomplete: {}", inner)]
+    Framebuffer { inner: framebuffer::Error },
+}
+
+impl From<program::Error> for Error {
+    fn from(inner: program::Error) -> Self {
+        Error::Program { inner }
+    }
+}
+
+impl From<framebuffer::Error> for Error {
+    fn from(inner: framebuffer::Error) -> Self {
+        Error::Framebuffer { inner }
+    }
+}
+
+/// Colour attachments of the G-buffer; see `shaders/gbuffer.frag`
+pub const GBUFFER_ALBEDO: usize = 0;
+pub const GBUFFER_NORMAL: usize = 1;
+pub const GBUFFER_MATERIAL: usize = 2;
+pub const GBUFFER_EMISSIVE: usize = 3;
+
+/// Texture units of the G-buffer during the lighting and resolve passes
+const ALBEDO_UNIT: GLuint = 0;
+const NORMAL_UNIT: GLuint = 1;
+const MATERIAL_UNIT: GLuint = 2;
+const EMISSIVE_UNIT: GLuint = 3;
+const DEPTH_UNIT: GLuint = 4;
+const LIT_UNIT: GLuint = 5;
+
+/// Storage buffer binding of the light list read by `deferred_lighting.comp`
+const LIGHTS_BINDING: GLuint = 0;
+
+/// What `DeferredRenderer::resolve` shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeferredView {
+    /// The shaded image, tone mapped
+    #[default]
+    Lit,
+    Albedo,
+    /// World space normals mapped to colours
+    Normal,
+    /// Metallic in red, roughness in green
+    MetallicRoughness,
+    Occlusion,
+    Emissive,
+    /// Linear depth, black at the camera and white at the far plane
+    Depth,
+    /// Number of lights each tile kept after culling, as a heat map
+    LightCount,
+}
+
+impl DeferredView {
+    fn as_gl(self) -> GLint {
+        match self {
+            DeferredView::Lit => 0,
+            DeferredView::Albedo => 1,
+            DeferredView::Normal => 2,
+            DeferredView::MetallicRoughness => 3,
+            DeferredView::Occlusion => 4,
+            DeferredView::Emissive => 5,
+            DeferredView::Depth => 6,
+            DeferredView::LightCount => 7,
+        }
+    }
+}
+
+/// Deferred shading path for scenes with many lights.
+///
+/// A frame runs `geometry_pass` (opaque surfaces into the G-buffer),
+/// `light_pass` (a compute shader culling the lights per 16x16 tile and
+/// shading into an HDR target), `forward_pass` (transparent surfaces drawn
+/// over the shaded image, depth tested against the G-buffer) and `resolve`
+/// (tone mapping, or one of the debug views, into the bound framebuffer).
+pub struct DeferredRenderer {
+    gl: gl::Gl,
+    gbuffer: Framebuffer,
+    lit: Framebuffer,
+    gbuffer_program: Program,
+    lighting: ComputeProgram,
+    resolve_program: Program,
+    lights: StorageBuffer<GpuLight>,
+    light_count: usize,
+    empty: VertexArray,
+    pub view: DeferredView,
+    pub exposure: f32,
+}
+
+impl DeferredRenderer {
+    pub fn new(gl: &gl::Gl, res: &Resources, width: u32, height: u32) -> Result<Self, Error> {
+        let gbuffer = Framebuffer::new(
+            gl,
+            FramebufferDesc {
+                width,
+                height,
+                colors: vec![
+                    TextureFormat::RGBA8,
+                    TextureFormat::RGBA16F,
+                    TextureFormat::RGBA8,
+                    TextureFormat::RGBA16F,
+                ],
+                depth: Some(DepthAttachment::Texture(TextureFormat::DEPTH32F)),
+                samples: 0,
+            },
+        )?;
+        let lit = Framebuffer::new(
+            gl,
+            FramebufferDesc {
+                width,
+                height,
+                colors: vec![TextureFormat::RGBA16F],
+                depth: Some(DepthAttachment::Renderbuffer(TextureFormat::DEPTH32F)),
+                samples: 0,
+            },
+        )?;
+
+        let load = |names: &[&str]| Program::from_resource_files(gl, res, names);
+        let gbuffer_program = load(&["pbr.vert", "gbuffer.frag"])?;
+        let lighting = ComputeProgram::from_resources(gl, res, "deferred_lighting")?;
+        let resolve_program = load(&["deferred_resolve.vert", "deferred_resolve.frag"])?;
+
+        let samplers = [
+            ("u_albedo", ALBEDO_UNIT),
+            ("u_normal", NORMAL_UNIT),
+            ("u_material", MATERIAL_UNIT),
+            ("u_emissive", EMISSIVE_UNIT),
+            ("u_depth", DEPTH_UNIT),
+            ("u_lit", LIT_UNIT),
+            ("u_irradiance_map", ibl::IRRADIANCE_UNIT),
+            ("u_prefiltered_map", ibl::PREFILTERED_UNIT),
+            ("u_brdf_lut", ibl::BRDF_LUT_UNIT),
+            ("u_cascade_maps", shadow::CASCADE_UNIT),
+            ("u_spot_shadow_maps", shadow::SPOT_UNIT),
+            ("u_point_shadow_maps", shadow::POINT_UNIT),
+        ];
+        for (name, unit) in samplers {
+            lighting.program().set_uniform(name, &(unit as i32));
+            resolve_program.set_uniform(name, &(unit as i32));
+        }
+
+        Ok(DeferredRenderer {
+            gl: gl.clone(),
+            gbuffer,
+            lit,
+            gbuffer_program,
+            lighting,
+            resolve_program,
+            lights: StorageBuffer::with_len(gl, 256, gl::DYNAMIC_DRAW),
+            light_count: 0,
+            empty: VertexArray::new(gl),
+            view: DeferredView::default(),
+            exposure: 1.0,
+        })
+    }
+
+    /// Recreates the G-buffer and the HDR target at the new size
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.gbuffer.resize(width, height)?;
+        self.lit.resize(width, height)?;
+        Ok(())
+    }
+
+    /// Albedo, normal, material and emissive colour attachments plus a depth texture
+    pub fn gbuffer(&self) -> &Framebuffer {
+        &self.gbuffer
+    }
+
+    /// HDR shaded image written by `light_pass` and `forward_pass`
+    pub fn lit(&self) -> &Framebuffer {
+        &self.lit
+    }
+
+    /// Program of the geometry pass: `pbr.vert` with the glTF material
+    /// uniforms of `pbr.frag`, so materials can be applied with `Material::bind_to`
+    pub fn gbuffer_program(&self) -> &Program {
+        &self.gbuffer_program
+    }
+
+    /// Clears the G-buffer and fills it with the opaque surfaces.
+    /// `draw` runs once with the G-buffer program in use; it should bind the
+    /// materials (`Material::bind_to`), set the transforms
+    /// (`lighting::set_transform_uniforms`) and draw the meshes.
+    pub fn geometry_pass<F>(&self, cache: &mut StateCache, draw: F)
+    where
+        F: FnOnce(&Program, &mut StateCache),
+    {
+        let _guard = self.gbuffer.bind();
+        cache.apply(&RenderState::opaque());
+        let zero = [0.0f32; 4];
+        unsafe {
+            for attachment in 0..self.gbuffer.desc().colors.len() {
+                self.gl
+                    .ClearBufferfv(gl::COLOR, attachment as GLint, zero.as_ptr());
+            }
+            self.gl.Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        self.gbuffer_program.set_used();
+        draw(&self.gbuffer_program, cache);
+    }
+
+    /// Shades the G-buffer with every light of `lights`, culled per tile.
+    /// `shadows` must have been rendered for the same lights.
+    pub fn light_pass(
+        &mut self,
+        lights: &[Light],
+        camera: &Camera,
+        shadows: Option<&ShadowMaps>,
+        environment: Option<&Environment>,
+    ) {
+        self.upload_lights(lights);
+
+        let program = self.lighting.program();
+        let view = camera.view_matrix();
+        let projection = camera.projection_matrix();
+        program.set_uniform("u_view", &view);
+        program.set_uniform(
+            "u_inverse_projection",
+            &projection.inverse().unwrap_or(Mat4::IDENTITY),
+        );
+        program.set_uniform(
+            "u_inverse_view_projection",
+            &(projection * view).inverse().unwrap_or(Mat4::IDENTITY),
+        );
+        program.set_uniform("u_camera_position", &camera.position);
+        program.set_uniform("u_camera_forward", &camera.forward());
+        program.set_uniform("u_ambient", &ambient(lights));
+        program.set_uniform("u_light_count", &(self.light_count as i32));
+        program.set_uniform("u_show_light_count", &(self.view == DeferredView::LightCount));
+
+        match shadows {
+            Some(shadows) => {
+                shadows.bind();
+                shadows.set_uniforms(program);
+            }
+            None => {
+                program.set_uniform("u_shadows_enabled", &false);
+            }
+        }
+        match environment {
+            Some(environment) => {
+                environment.bind();
+                environment.set_uniforms(program);
+            }
+            None => {
+                program.set_uniform("u_has_environment", &false);
+            }
+        }
+
+        self.bind_gbuffer_textures();
+        self.lights.bind_base(LIGHTS_BINDING);
+        let output = self.lit.color(0);
+        self.lighting
+            .bind_image(0, output.id(), 0, ImageAccess::WriteOnly, gl::RGBA16F);
+        self.lighting
+            .dispatch_invocations(output.width(), output.height(), 1);
+        compute::memory_barrier(
+            &self.gl,
+            Barrier::TEXTURE_FETCH | Barrier::SHADER_IMAGE_ACCESS | Barrier::FRAMEBUFFER,
+        );
+    }
+
+    /// Draws over the shaded image with the G-buffer depth, for the
+    /// transparent surfaces. Their programs should output linear HDR colour
+    /// (`u_tonemap = false` for `pbr.frag`); `resolve` tone maps everything.
+    pub fn forward_pass<F>(&self, cache: &mut StateCache, draw: F)
+    where
+        F: FnOnce(&mut StateCache),
+    {
+        let size = (self.lit.width(), self.lit.height());
+        self.gbuffer
+            .blit_to(Some(&self.lit), size, gl::DEPTH_BUFFER_BIT);
+        let _guard = self.lit.bind();
+        draw(cache);
+    }
+
+    /// Draws `view` over the framebuffer currently bound, which should be
+    /// `window_size` pixels
+    pub fn resolve(&self, cache: &mut StateCache, camera: &Camera, window_size: (u32, u32)) {
+        let (perspective, near, far) = match camera.projection {
+            Projection::Perspective { near, far, .. } => (true, near, far),
+            Projection::Orthographic { near, far, .. } => (false, near, far),
+        };
+
+        cache.apply(&RenderState {
+            depth_write: false,
+            ..RenderState::default()
+        });
+        unsafe {
+            self.gl
+                .Viewport(0, 0, window_size.0 as GLsizei, window_size.1 as GLsizei);
+        }
+
+        self.bind_gbuffer_textures();
+        self.lit.color(0).bind(LIT_UNIT);
+        let program = &self.resolve_program;
+        program.set_used();
+        program.set_uniform("u_view", &self.view.as_gl());
+        program.set_uniform("u_exposure", &self.exposure);
+        program.set_uniform("u_perspective", &perspective);
+        program.set_uniform("u_near", &near);
+        program.set_uniform("u_far", &far);
+
+        self.empty.bind();
+        unsafe { self.gl.DrawArrays(gl::TRIANGLES, 0, 3) };
+        self.empty.unbind();
+    }
+
+    fn bind_gbuffer_textures(&self) {
+        self.gbuffer.color(GBUFFER_ALBEDO).bind(ALBEDO_UNIT);
+        self.gbuffer.color(GBUFFER_NORMAL).bind(NORMAL_UNIT);
+        self.gbuffer.color(GBUFFER_MATERIAL).bind(MATERIAL_UNIT);
+        self.gbuffer.color(GBUFFER_EMISSIVE).bind(EMISSIVE_UNIT);
+        if let Some(depth) = self.gbuffer.depth_texture() {
+            depth.bind(DEPTH_UNIT);
+        }
+    }
+
+    /// Packs the non-ambient lights with their shadow slots, growing the
+    /// storage buffer when needed
+    fn upload_lights(&mut self, lights: &[Light]) {
+        let packed: Vec<GpuLight> = lights
+            .iter()
+            .zip(shadow::shadow_slots(lights))
+            .filter(|(light, _)| !matches!(light, Light::Ambient { .. }))
+            .map(|(light, slot)| light.to_gpu(slot))
+            .collect();
+
+        if packed.len() > self.lights.len() {
+            self.lights = StorageBuffer::with_len(&self.gl, packed.len().next_power_of_two(), gl::DYNAMIC_DRAW);
+        }
+        if !packed.is_empty() {
+            self.lights.write(0, &packed);
+        }
+        self.light_count = packed.len();
+    }
+}
+
+/// Sum of the ambient lights
+fn ambient(lights: &[Light]) -> Vec3 {
+    lights.iter().fold(Vec3::ZERO, |sum, light| match *light {
+        Light::Ambient { color, intensity } => sum + color * intensity,
+        _ => sum,
+    })
+}
+
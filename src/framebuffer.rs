@@ -0,0 +1,486 @@
+use crate::image::ImageBuffer;
+use crate::texture::{with_pixel_store, Texture2D, TextureFormat};
+use gl::types::*;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Framebuffer has an incomplete attachment")]
+    IncompleteAttachment,
+    #[fail(display = "Framebuffer has no attachments")]
+    MissingAttachment,
+    #[fail(display = "Framebuffer draw buffer has no attachment")]
+    IncompleteDrawBuffer,
+    #[fail(display = "Framebuffer read buffer has no attachment")]
+    IncompleteReadBuffer,
+    #[fail(display = "Framebuffer attachment formats are not supported")]
+    Unsupported,
+    #[fail(display = "Framebuffer attachments have mismatched sample counts")]
+    IncompleteMultisample,
+    #[fail(display = "Framebuffer attachments have mismatched layers")]
+    IncompleteLayerTargets,
+    #[fail(display = "Framebuffer is incomplete (status 0x{:X})", status)]
+    Unknown { status: GLenum },
+}
+
+impl Error {
+    pub(crate) fn from_status(status: GLenum) -> Option<Error> {
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => None,
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => Some(Error::IncompleteAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => Some(Error::MissingAttachment),
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => Some(Error::IncompleteDrawBuffer),
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => Some(Error::IncompleteReadBuffer),
+            gl::FRAMEBUFFER_UNSUPPORTED => Some(Error::Unsupported),
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => Some(Error::IncompleteMultisample),
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => Some(Error::IncompleteLayerTargets),
+            status => Some(Error::Unknown { status }),
+        }
+    }
+}
+
+/// Wrapper for OpenGL renderbuffers, used for attachments that are never sampled
+pub struct Renderbuffer {
+    gl: gl::Gl,
+    id: GLuint,
+    format: TextureFormat,
+}
+
+impl Renderbuffer {
+    pub fn new(gl: &gl::Gl, width: u32, height: u32, format: TextureFormat, samples: u32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl.GenRenderbuffers(1, &mut id);
+            gl.BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl.RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples as GLsizei,
+                format.internal,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+
+        Renderbuffer {
+            gl: gl.clone(),
+            id,
+            format,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteRenderbuffers(1, &self.id) };
+    }
+}
+
+/// How the depth (and stencil) attachment of a framebuffer is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthAttachment {
+    /// A texture that can be sampled later (e.g. shadow maps)
+    Texture(TextureFormat),
+    /// A renderbuffer, for depth testing only
+    Renderbuffer(TextureFormat),
+}
+
+/// Description of the attachments a `Framebuffer` owns
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramebufferDesc {
+    pub width: u32,
+    pub height: u32,
+    /// One colour texture per entry, attached to `GL_COLOR_ATTACHMENT0 + i`
+    pub colors: Vec<TextureFormat>,
+    pub depth: Option<DepthAttachment>,
+    /// MSAA sample count, 0 for single-sampled targets
+    pub samples: u32,
+}
+
+enum Depth {
+    Texture(Texture2D),
+    Renderbuffer(Renderbuffer),
+}
+
+/// Wrapper for OpenGL framebuffer objects owning their attachments
+pub struct Framebuffer {
+    gl: gl::Gl,
+    id: GLuint,
+    desc: FramebufferDesc,
+    colors: Vec<Texture2D>,
+    depth: Option<Depth>,
+}
+
+impl Framebuffer {
+    /// Creates the attachments in `desc` and checks the framebuffer for completeness
+    pub fn new(gl: &gl::Gl, desc: FramebufferDesc) -> Result<Self, Error> {
+        let mut id: GLuint = 0;
+        unsafe { gl.GenFramebuffers(1, &mut id) };
+
+        let mut framebuffer = Framebuffer {
+            gl: gl.clone(),
+            id,
+            desc,
+            colors: Vec::new(),
+            depth: None,
+        };
+        framebuffer.create_attachments()?;
+        Ok(framebuffer)
+    }
+
+    fn create_attachments(&mut self) -> Result<(), Error> {
+        let gl = &self.gl;
+        let FramebufferDesc {
+            width,
+            height,
+            samples,
+            ..
+        } = self.desc;
+
+        let new_texture = |format| {
+            if samples > 0 {
+                Texture2D::new_multisample(gl, width, height, format, samples)
+            } else {
+                Texture2D::new(gl, width, height, format)
+            }
+        };
+
+        self.colors = self.desc.colors.iter().map(|&f| new_texture(f)).collect();
+        self.depth = self.desc.depth.map(|depth| match depth {
+            DepthAttachment::Texture(format) => Depth::Texture(new_texture(format)),
+            DepthAttachment::Renderbuffer(format) => {
+                Depth::Renderbuffer(Renderbuffer::new(gl, width, height, format, samples))
+            }
+        });
+
+        let previous = FramebufferBindings::save(gl);
+        unsafe {
+            gl.BindFramebuffer(gl::FRAMEBUFFER, self.id);
+
+            for (i, texture) in self.colors.iter().enumerate() {
+                gl.FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0 + i as GLenum,
+                    texture.target(),
+                    texture.id(),
+                    0,
+                );
+            }
+
+            match &self.depth {
+                Some(Depth::Texture(texture)) => gl.FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    depth_attachment_point(texture.format()),
+                    texture.target(),
+                    texture.id(),
+                    0,
+                ),
+                Some(Depth::Renderbuffer(renderbuffer)) => gl.FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    depth_attachment_point(renderbuffer.format()),
+                    gl::RENDERBUFFER,
+                    renderbuffer.id(),
+                ),
+                None => {}
+            }
+
+            let draw_buffers = color_attachments(self.colors.len());
+            if draw_buffers.is_empty() {
+                gl.DrawBuffer(gl::NONE);
+                gl.ReadBuffer(gl::NONE);
+            } else {
+                gl.DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+            }
+
+            let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+            previous.restore(gl);
+
+            match Error::from_status(status) {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.desc.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.desc.height
+    }
+
+    pub fn desc(&self) -> &FramebufferDesc {
+        &self.desc
+    }
+
+    /// The colour texture attached at `GL_COLOR_ATTACHMENT0 + index`
+    pub fn color(&self, index: usize) -> &Texture2D {
+        &self.colors[index]
+    }
+
+    /// The depth texture, if the depth attachment is a texture
+    pub fn depth_texture(&self) -> Option<&Texture2D> {
+        match &self.depth {
+            Some(Depth::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    /// Recreates all attachments at the new size, discarding their contents
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if width == self.desc.width && height == self.desc.height {
+            return Ok(());
+        }
+
+        self.desc.width = width;
+        self.desc.height = height;
+        self.create_attachments()
+    }
+
+    /// Binds the framebuffer for drawing and sets the viewport to cover it.
+    /// The previous binding and viewport are restored when the guard drops,
+    /// so nested binds unwind like a stack.
+    pub fn bind(&self) -> FramebufferGuard {
+        let guard = FramebufferGuard::save(&self.gl, self.id, self.colors.len());
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            self.gl.Viewport(0, 0, self.desc.width as GLsizei, self.desc.height as GLsizei);
+        }
+        guard
+    }
+
+    /// Copies the given buffers (`gl::COLOR_BUFFER_BIT`, ...) into `target`,
+    /// resolving multisampled attachments.
+    /// Pass `None` to blit to the default framebuffer of size `window_size`.
+    pub fn blit_to(
+        &self,
+        target: Option<&Framebuffer>,
+        window_size: (u32, u32),
+        mask: GLbitfield,
+    ) {
+        let (target_id, width, height) = match target {
+            Some(fb) => (fb.id, fb.desc.width, fb.desc.height),
+            None => (0, window_size.0, window_size.1),
+        };
+        // Depth and stencil blits must not filter
+        let filter = if mask == gl::COLOR_BUFFER_BIT && self.desc.samples == 0 {
+            gl::LINEAR
+        } else {
+            gl::NEAREST
+        };
+
+        unsafe {
+            self.gl.BlitNamedFramebuffer(
+                self.id,
+                target_id,
+                0,
+                0,
+                self.desc.width as GLint,
+                self.desc.height as GLint,
+                0,
+                0,
+                width as GLint,
+                height as GLint,
+                mask,
+                filter,
+            );
+        }
+    }
+
+    /// Resolves this multisampled framebuffer into a single-sampled one of the same size.
+    /// Each colour attachment goes to the attachment with the same index in `target`;
+    /// depth is only copied when both framebuffers have a depth attachment of the same format.
+    pub fn resolve(&self, target: &Framebuffer) {
+        let count = self.colors.len().min(target.colors.len());
+        for i in 0..count {
+            let attachment = gl::COLOR_ATTACHMENT0 + i as GLenum;
+            unsafe {
+                self.gl.NamedFramebufferReadBuffer(self.id, attachment);
+                self.gl.NamedFramebufferDrawBuffer(target.id, attachment);
+            }
+            self.blit_to(Some(target), (0, 0), gl::COLOR_BUFFER_BIT);
+        }
+
+        // Put back the read buffer and draw buffers set up at creation
+        unsafe {
+            if !self.colors.is_empty() {
+                self.gl.NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0);
+            }
+            let draw_buffers = color_attachments(target.colors.len());
+            if !draw_buffers.is_empty() {
+                self.gl.NamedFramebufferDrawBuffers(
+                    target.id,
+                    draw_buffers.len() as GLsizei,
+                    draw_buffers.as_ptr(),
+                );
+            }
+        }
+
+        if let (Some(depth), Some(target_depth)) = (self.depth_format(), target.depth_format()) {
+            if depth == target_depth {
+                self.blit_to(Some(target), (0, 0), gl::DEPTH_BUFFER_BIT);
+            }
+        }
+    }
+
+    fn depth_format(&self) -> Option<TextureFormat> {
+        match &self.depth {
+            Some(Depth::Texture(texture)) => Some(texture.format()),
+            Some(Depth::Renderbuffer(renderbuffer)) => Some(renderbuffer.format()),
+            None => None,
+        }
+    }
+
+    /// Reads colour attachment `index` back as 8-bit RGBA, rows ordered bottom-up.
+    /// Multisampled framebuffers must be `resolve`d first.
+    pub fn read_pixels(&self, index: usize) -> ImageBuffer<u8> {
+        let mut image = ImageBuffer::new(self.desc.width, self.desc.height, 4);
+        unsafe {
+            self.gl.NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0 + index as GLenum);
+        }
+        read_framebuffer_pixels(&self.gl, self.id, &mut image);
+        image
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteFramebuffers(1, &self.id) };
+    }
+}
+
+/// Reads the default framebuffer back as 8-bit RGBA, rows ordered bottom-up
+pub fn read_default_pixels(gl: &gl::Gl, width: u32, height: u32) -> ImageBuffer<u8> {
+    let mut image = ImageBuffer::new(width, height, 4);
+    read_framebuffer_pixels(gl, 0, &mut image);
+    image
+}
+
+fn read_framebuffer_pixels(gl: &gl::Gl, framebuffer: GLuint, image: &mut ImageBuffer<u8>) {
+    let mut previous: GLint = 0;
+    unsafe {
+        gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+    }
+    with_pixel_store(gl, gl::PACK_ALIGNMENT, 1, || unsafe {
+        gl.ReadPixels(
+            0,
+            0,
+            image.width as GLsizei,
+            image.height as GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            image.data.as_mut_ptr() as *mut GLvoid,
+        );
+    });
+    unsafe { gl.BindFramebuffer(gl::READ_FRAMEBUFFER, previous as GLuint) };
+}
+
+fn depth_attachment_point(format: TextureFormat) -> GLenum {
+    if format.has_stencil() {
+        gl::DEPTH_STENCIL_ATTACHMENT
+    } else {
+        gl::DEPTH_ATTACHMENT
+    }
+}
+
+fn color_attachments(count: usize) -> Vec<GLenum> {
+    (0..count as GLenum).map(|i| gl::COLOR_ATTACHMENT0 + i).collect()
+}
+
+/// The draw and read framebuffer bindings, which `gl::FRAMEBUFFER` sets together
+#[derive(Clone, Copy, Debug)]
+struct FramebufferBindings {
+    draw: GLuint,
+    read: GLuint,
+}
+
+impl FramebufferBindings {
+    fn save(gl: &gl::Gl) -> Self {
+        let mut draw: GLint = 0;
+        let mut read: GLint = 0;
+        unsafe {
+            gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw);
+            gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read);
+        }
+        FramebufferBindings {
+            draw: draw as GLuint,
+            read: read as GLuint,
+        }
+    }
+
+    fn restore(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw);
+            gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.read);
+        }
+    }
+}
+
+/// Restores the framebuffer bindings, viewport and draw buffers
+/// that were current when it was created
+pub struct FramebufferGuard {
+    gl: gl::Gl,
+    framebuffer: GLuint,
+    color_count: usize,
+    previous_bindings: FramebufferBindings,
+    previous_viewport: [GLint; 4],
+    draw_buffers_changed: bool,
+}
+
+impl FramebufferGuard {
+    fn save(gl: &gl::Gl, framebuffer: GLuint, color_count: usize) -> Self {
+        let mut previous_viewport: [GLint; 4] = [0; 4];
+        unsafe { gl.GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr()) };
+
+        FramebufferGuard {
+            gl: gl.clone(),
+            framebuffer,
+            color_count,
+            previous_bindings: FramebufferBindings::save(gl),
+            previous_viewport,
+            draw_buffers_changed: false,
+        }
+    }
+
+    /// Draws only into the listed colour attachments until the guard drops
+    pub fn set_draw_buffers(&mut self, indices: &[usize]) {
+        let buffers: Vec<GLenum> = indices
+            .iter()
+            .map(|&i| gl::COLOR_ATTACHMENT0 + i as GLenum)
+            .collect();
+        unsafe { self.gl.DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr()) };
+        self.draw_buffers_changed = true;
+    }
+}
+
+impl Drop for FramebufferGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if self.draw_buffers_changed {
+                let buffers = color_attachments(self.color_count);
+                self.gl.NamedFramebufferDrawBuffers(
+                    self.framebuffer,
+                    buffers.len() as GLsizei,
+                    buffers.as_ptr(),
+                );
+            }
+
+            self.previous_bindings.restore(&self.gl);
+            let [x, y, width, height] = self.previous_viewport;
+            self.gl.Viewport(x, y, width, height);
+        }
+    }
+}
@@ -1,13 +1,18 @@
-use crate::resources::Resources;
+use crate::resources::{self, Resources};
 use crate::{Program, Shader};
 use gl::types::GLenum;
 use crate::program::Error;
+use std::ffi::CString;
+
+/// Nesting limit of `#include` directives, to catch include cycles
+const MAX_INCLUDE_DEPTH: u32 = 16;
 
 impl Shader {
     pub fn from_resources(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Shader, Error> {
-        const POSSIBLE_EXT: [(&str, GLenum); 2] = [
+        const POSSIBLE_EXT: [(&str, GLenum); 3] = [
             (".vert", gl::VERTEX_SHADER),
             (".frag", gl::FRAGMENT_SHADER),
+            (".comp", gl::COMPUTE_SHADER),
         ];
 
         let shader_kind = POSSIBLE_EXT.iter()
@@ -17,11 +22,11 @@ impl Shader {
             .map(|&(_, kind)| kind)
             .ok_or_else(|| Error::UndefinedShaderType { name: name.to_string() })?;
 
-        let source = res.load(name)
-            .map_err(|e| Error::ResourceLoad {
-                name: name.to_string(),
-                inner: e,
-            })?;
+        let source = load_source(res, name, 0)?;
+        let source = CString::new(source).map_err(|_| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: resources::Error::FileContainsNil,
+        })?;
 
         Shader::from_source(gl, &source, shader_kind)
     }
@@ -42,4 +47,131 @@ impl Program {
             message: e,
         })
     }
-}
\ No newline at end of file
+}
+
+/// Loads the shader source `name`, replacing each `#include "file"` line by
+/// the contents of `file` (resolved next to the including file).
+/// `#line` directives keep the compiler messages pointing at the right lines.
+fn load_source(res: &Resources, name: &str, depth: u32) -> Result<String, Error> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::CompileError {
+            name: name.to_string(),
+            message: "#include nested too deeply".to_string(),
+        });
+    }
+
+    let source = res.load(name)
+        .map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?
+        .into_string()
+        .map_err(|e| Error::CompileError {
+            name: name.to_string(),
+            message: e.to_string(),
+        })?;
+    if !source.contains("#include") {
+        return Ok(source);
+    }
+
+    let mut expanded = String::with_capacity(source.len());
+    for (index, line) in source.lines().enumerate() {
+        let included = line.trim().strip_prefix("#include").map(|rest| rest.trim().trim_matches('"'));
+        match included {
+            Some(file) => {
+                let file = resources::sibling_name(name, file);
+                expanded.push_str("#line 1\n");
+                expanded.push_str(&load_source(res, &file, depth + 1)?);
+                expanded.push_str(&format!("\n#line {}\n", index + 2));
+            }
+            None => {
+                expanded.push_str(line);
+                expanded.push('\n');
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Writes `files` into a fresh directory under the system temp dir
+    fn resources_with(test: &str, files: &[(&str, &str)]) -> (Resources, PathBuf) {
+        let root = std::env::temp_dir().join(format!("opengl-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (name, contents) in files {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        (Resources::from_rel_path(&root).unwrap(), root)
+    }
+
+    #[test]
+    fn nested_includes_resolve_next_to_the_including_file() {
+        let (res, root) = resources_with(
+            "nested-includes",
+            &[
+                ("main.frag", "#version 330 core\n#include \"lib/a.glsl\"\nvoid main() {}\n"),
+                ("lib/a.glsl", "float a;\n  #include \"b.glsl\"\nfloat c;\n"),
+                ("lib/b.glsl", "float b;\n"),
+            ],
+        );
+
+        let source = load_source(&res, "main.frag", 0).unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+
+        assert_eq!(
+            source,
+            "#version 330 core\n\
+             #line 1\n\
+             float a;\n\
+             #line 1\n\
+             float b;\n\n\
+             #line 3\n\
+             float c;\n\n\
+             #line 3\n\
+             void main() {}\n"
+        );
+    }
+
+    #[test]
+    fn include_cycles_hit_the_depth_limit() {
+        let (res, root) = resources_with(
+            "include-cycle",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+
+        let result = load_source(&res, "a.glsl", 0);
+        std::fs::remove_dir_all(root).unwrap();
+
+        match result {
+            Err(Error::CompileError { message, .. }) => {
+                assert_eq!(message, "#include nested too deeply")
+            }
+            other => panic!("expected a depth error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_include_reports_the_resolved_name() {
+        let (res, root) = resources_with(
+            "missing-include",
+            &[("shaders/main.frag", "#include \"missing.glsl\"\n")],
+        );
+
+        let result = load_source(&res, "shaders/main.frag", 0);
+        std::fs::remove_dir_all(root).unwrap();
+
+        match result {
+            Err(Error::ResourceLoad { name, .. }) => assert_eq!(name, "shaders/missing.glsl"),
+            other => panic!("expected a load error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,808 @@
+use crate::camera::Projection;
+use crate::image::ImageBuffer;
+use crate::math::{Mat4, Quat, Vec2, Vec3, Vec4};
+use crate::mesh::{Mesh, MeshData, SubMesh, Vertex};
+use crate::resources::{self, Resources};
+use crate::texture::{SamplerDesc, Texture2D};
+use gl::types::*;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Invalid glTF {}: {}", name, message)]
+    InvalidDocument { name: String, message: String },
+    #[fail(display = "Buffer {} of {} has no data", index, name)]
+    MissingBuffer { name: String, index: usize },
+    #[fail(display = "Failed to decode image {} of {}: {}", index, name, message)]
+    ImageDecode {
+        name: String,
+        index: usize,
+        message: String,
+    },
+    #[fail(display = "Mesh {} of {} has a primitive without positions", mesh, name)]
+    MissingPositions { name: String, mesh: usize },
+    #[fail(display = "Mesh {} of {} uses unsupported primitive mode {:?}", mesh, name, mode)]
+    UnsupportedMode {
+        name: String,
+        mesh: usize,
+        mode: gltf::mesh::Mode,
+    },
+}
+
+/// How a material's alpha channel is interpreted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Alpha tested against the cutoff
+    Mask(f32),
+    Blend,
+}
+
+/// Reference to a texture of the scene along with the UV set it samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureRef {
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+/// glTF metallic-roughness material parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+    pub name: String,
+    pub base_color_factor: Vec4,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Roughness in the green channel, metalness in the blue channel
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: Vec3,
+    pub emissive_texture: Option<TextureRef>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for PbrMaterial {
+    /// The glTF default material
+    fn default() -> Self {
+        PbrMaterial {
+            name: String::from("default"),
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: Vec3::ZERO,
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Texture {
+    /// Index into `GltfScene::images`
+    pub image: usize,
+    pub sampler: SamplerDesc,
+}
+
+/// One draw call worth of geometry.
+/// `mesh` holds the attributes of the shared `Vertex` layout; the remaining
+/// accessors are kept alongside, one entry per vertex, when present.
+#[derive(Debug, Clone, Default)]
+pub struct Primitive {
+    pub mesh: MeshData,
+    pub material: Option<usize>,
+    pub tex_coords_1: Option<Vec<Vec2>>,
+    pub colors: Option<Vec<Vec4>>,
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<Vec4>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GltfMesh {
+    pub name: String,
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: String,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub skin: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfCamera {
+    pub name: String,
+    pub projection: Projection,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub name: String,
+    /// Node indices of the joints, in the order `JOINTS_0` refers to them
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Mat4>,
+    pub skeleton: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationProperty {
+    Translation,
+    Rotation,
+    Scale,
+    MorphTargetWeights,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    /// Each keyframe stores in-tangent, value and out-tangent
+    CubicSpline,
+}
+
+/// Keyframes driving one property of one node
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize,
+    pub property: AnimationProperty,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds
+    pub times: Vec<f32>,
+    /// Keyframe values, flattened (3 floats per translation, 4 per rotation, ...)
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+/// Everything imported from a glTF asset, still on the CPU
+#[derive(Debug, Clone, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<Texture>,
+    /// Decoded as 8-bit RGBA, rows top-down as glTF UVs expect
+    pub images: Vec<ImageBuffer<u8>>,
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of the default scene
+    pub roots: Vec<usize>,
+    pub cameras: Vec<GltfCamera>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+impl GltfScene {
+    /// Uploads every primitive, indexed as `[mesh][primitive]`
+    pub fn upload_meshes(&self, gl: &gl::Gl) -> Vec<Vec<Mesh>> {
+        self.meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| Mesh::new(gl, &primitive.mesh))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Uploads every texture with its sampler settings
+    pub fn upload_textures(&self, gl: &gl::Gl) -> Vec<Texture2D> {
+        self.textures
+            .iter()
+            .map(|texture| {
+                let gpu = Texture2D::from_image(gl, &self.images[texture.image]);
+                gpu.set_sampler(&texture.sampler);
+                gpu
+            })
+            .collect()
+    }
+}
+
+/// Imports a `.gltf` (with external or embedded buffers) or `.glb` resource.
+/// External buffers and images are loaded relative to `name`.
+pub fn load(res: &Resources, name: &str) -> Result<GltfScene, Error> {
+    let bytes = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+        name: name.to_string(),
+        inner: e,
+    })?;
+    let gltf = gltf::Gltf::from_slice(&bytes).map_err(|e| Error::InvalidDocument {
+        name: name.to_string(),
+        message: e.to_string(),
+    })?;
+    let document = &gltf.document;
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => gltf.blob.clone().ok_or(Error::MissingBuffer {
+                name: name.to_string(),
+                index: buffer.index(),
+            }),
+            gltf::buffer::Source::Uri(uri) => load_uri(res, name, uri),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let images = document
+        .images()
+        .map(|image| {
+            let encoded = match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let range = view.offset()..view.offset() + view.length();
+                    buffers
+                        .get(view.buffer().index())
+                        .and_then(|buffer| buffer.get(range))
+                        .ok_or_else(|| Error::InvalidDocument {
+                            name: name.to_string(),
+                            message: format!(
+                                "image {} reads past the end of buffer {}",
+                                image.index(),
+                                view.buffer().index()
+                            ),
+                        })?
+                        .to_vec()
+                }
+                gltf::image::Source::Uri { uri, .. } => load_uri(res, name, uri)?,
+            };
+            ImageBuffer::decode(&encoded).map_err(|message| Error::ImageDecode {
+                name: name.to_string(),
+                index: image.index(),
+                message,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let textures = document
+        .textures()
+        .map(|texture| Texture {
+            image: texture.source().index(),
+            sampler: convert_sampler(&texture.sampler()),
+        })
+        .collect();
+
+    let materials = document.materials().map(|m| convert_material(&m)).collect();
+
+    let meshes = document
+        .meshes()
+        .map(|mesh| convert_mesh(name, &mesh, &buffers))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let nodes = document
+        .nodes()
+        .map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            GltfNode {
+                name: node.name().unwrap_or_default().to_string(),
+                translation: Vec3::from(translation),
+                rotation: Quat::from(rotation),
+                scale: Vec3::from(scale),
+                children: node.children().map(|child| child.index()).collect(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                camera: node.camera().map(|camera| camera.index()),
+                skin: node.skin().map(|skin| skin.index()),
+            }
+        })
+        .collect();
+
+    let roots = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|node| node.index()).collect())
+        .unwrap_or_default();
+
+    let cameras = document
+        .cameras()
+        .map(|camera| GltfCamera {
+            name: camera.name().unwrap_or_default().to_string(),
+            projection: match camera.projection() {
+                gltf::camera::Projection::Perspective(p) => Projection::Perspective {
+                    fov_y: p.yfov(),
+                    near: p.znear(),
+                    far: p.zfar().unwrap_or(1000.0),
+                },
+                gltf::camera::Projection::Orthographic(o) => Projection::Orthographic {
+                    height: o.ymag() * 2.0,
+                    near: o.znear(),
+                    far: o.zfar(),
+                },
+            },
+        })
+        .collect();
+
+    let skins = document
+        .skins()
+        .map(|skin| {
+            let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(|m| mat4_from_cols(&m)).collect(),
+                None => vec![Mat4::IDENTITY; joints.len()],
+            };
+            Skin {
+                name: skin.name().unwrap_or_default().to_string(),
+                joints,
+                inverse_bind_matrices,
+                skeleton: skin.skeleton().map(|node| node.index()),
+            }
+        })
+        .collect();
+
+    let animations = document
+        .animations()
+        .map(|animation| Animation {
+            name: animation.name().unwrap_or_default().to_string(),
+            channels: animation
+                .channels()
+                .filter_map(|channel| convert_channel(&channel, &buffers))
+                .collect(),
+        })
+        .collect();
+
+    Ok(GltfScene {
+        meshes,
+        materials,
+        textures,
+        images,
+        nodes,
+        roots,
+        cameras,
+        skins,
+        animations,
+    })
+}
+
+/// Resolves a buffer or image URI: base64 `data:` URIs or files next to `name`
+fn load_uri(res: &Resources, name: &str, uri: &str) -> Result<Vec<u8>, Error> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let invalid = || Error::InvalidDocument {
+            name: name.to_string(),
+            message: format!("unsupported data URI '{:.32}...'", uri),
+        };
+        let (_, payload) = data.split_once(";base64,").ok_or_else(invalid)?;
+        return decode_base64(payload).ok_or_else(invalid);
+    }
+
+    let file = resources::sibling_name(name, &percent_decode(uri));
+    res.load_bytes(&file).map_err(|e| Error::ResourceLoad { name: file, inner: e })
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in input.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        acc = (acc << 6) | value(c)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+fn convert_sampler(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    use gltf::texture::{MagFilter, MinFilter};
+
+    SamplerDesc {
+        min_filter: sampler
+            .min_filter()
+            .unwrap_or(MinFilter::LinearMipmapLinear)
+            .as_gl_enum(),
+        mag_filter: sampler.mag_filter().unwrap_or(MagFilter::Linear).as_gl_enum(),
+        wrap_s: sampler.wrap_s().as_gl_enum(),
+        wrap_t: sampler.wrap_t().as_gl_enum(),
+    }
+}
+
+fn texture_ref(info: Option<gltf::texture::Info>) -> Option<TextureRef> {
+    info.map(|info| TextureRef {
+        texture: info.texture().index(),
+        tex_coord: info.tex_coord(),
+    })
+}
+
+fn convert_material(material: &gltf::Material) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+
+    PbrMaterial {
+        name: material.name().unwrap_or_default().to_string(),
+        base_color_factor: Vec4::from(pbr.base_color_factor()),
+        base_color_texture: texture_ref(pbr.base_color_texture()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: texture_ref(pbr.metallic_roughness_texture()),
+        normal_texture: normal.as_ref().map(|n| TextureRef {
+            texture: n.texture().index(),
+            tex_coord: n.tex_coord(),
+        }),
+        normal_scale: normal.as_ref().map_or(1.0, |n| n.scale()),
+        occlusion_texture: occlusion.as_ref().map(|o| TextureRef {
+            texture: o.texture().index(),
+            tex_coord: o.tex_coord(),
+        }),
+        occlusion_strength: occlusion.as_ref().map_or(1.0, |o| o.strength()),
+        emissive_factor: Vec3::from(material.emissive_factor()),
+        emissive_texture: texture_ref(material.emissive_texture()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        double_sided: material.double_sided(),
+    }
+}
+
+fn convert_mesh(name: &str, mesh: &gltf::Mesh, buffers: &[Vec<u8>]) -> Result<GltfMesh, Error> {
+    use gltf::mesh::Mode;
+
+    let mut primitives = Vec::new();
+    for primitive in mesh.primitives() {
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .ok_or_else(|| Error::MissingPositions {
+                name: name.to_string(),
+                mesh: mesh.index(),
+            })?
+            .map(Vec3::from)
+            .collect();
+        let count = positions.len();
+        let invalid = |message: String| Error::InvalidDocument {
+            name: name.to_string(),
+            message: format!(
+                "mesh {} primitive {}: {}",
+                mesh.index(),
+                primitive.index(),
+                message
+            ),
+        };
+
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count as u32).collect(),
+        };
+        if let Some(index) = indices.iter().find(|&&index| index as usize >= count) {
+            return Err(invalid(format!(
+                "index {} out of range for {} vertices",
+                index, count
+            )));
+        }
+        let indices = match primitive.mode() {
+            Mode::Triangles => indices,
+            Mode::TriangleStrip => (2..indices.len())
+                .flat_map(|i| {
+                    // Keep the winding consistent on odd triangles
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            Mode::TriangleFan => (2..indices.len())
+                .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            mode => {
+                return Err(Error::UnsupportedMode {
+                    name: name.to_string(),
+                    mesh: mesh.index(),
+                    mode,
+                })
+            }
+        };
+
+        let normals: Option<Vec<Vec3>> = reader.read_normals().map(|n| n.map(Vec3::from).collect());
+        let uvs: Option<Vec<Vec2>> = reader
+            .read_tex_coords(0)
+            .map(|t| t.into_f32().map(Vec2::from).collect());
+        let tangents: Option<Vec<Vec4>> =
+            reader.read_tangents().map(|t| t.map(Vec4::from).collect());
+        let lengths = [
+            ("NORMAL", normals.as_ref().map(Vec::len)),
+            ("TEXCOORD_0", uvs.as_ref().map(Vec::len)),
+            ("TANGENT", tangents.as_ref().map(Vec::len)),
+        ];
+        for (attribute, len) in lengths {
+            match len {
+                Some(len) if len != count => {
+                    return Err(invalid(format!(
+                        "{} has {} elements but POSITION has {}",
+                        attribute, len, count
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        let vertices = (0..count)
+            .map(|i| Vertex {
+                position: positions[i],
+                normal: normals.as_ref().map_or(Vec3::ZERO, |n| n[i]),
+                uv: uvs.as_ref().map_or(Vec2::ZERO, |t| t[i]),
+                tangent: tangents.as_ref().map_or(Vec4::ZERO, |t| t[i]),
+            })
+            .collect();
+
+        let mut data = MeshData {
+            vertices,
+            submeshes: vec![SubMesh {
+                name: mesh.name().unwrap_or_default().to_string(),
+                material: primitive.material().index(),
+                start: 0,
+                count: indices.len(),
+            }],
+            indices,
+        };
+        if normals.is_none() {
+            data.compute_normals();
+        }
+        if tangents.is_none() {
+            data.compute_tangents();
+        }
+
+        primitives.push(Primitive {
+            mesh: data,
+            material: primitive.material().index(),
+            tex_coords_1: reader
+                .read_tex_coords(1)
+                .map(|t| t.into_f32().map(Vec2::from).collect()),
+            colors: reader
+                .read_colors(0)
+                .map(|c| c.into_rgba_f32().map(Vec4::from).collect()),
+            joints: reader.read_joints(0).map(|j| j.into_u16().collect()),
+            weights: reader
+                .read_weights(0)
+                .map(|w| w.into_f32().map(Vec4::from).collect()),
+        });
+    }
+
+    Ok(GltfMesh {
+        name: mesh.name().unwrap_or_default().to_string(),
+        primitives,
+    })
+}
+
+fn convert_channel(channel: &gltf::animation::Channel, buffers: &[Vec<u8>]) -> Option<Channel> {
+    use gltf::animation::util::ReadOutputs;
+
+    let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+
+    let (property, values): (AnimationProperty, Vec<f32>) = match reader.read_outputs()? {
+        ReadOutputs::Translations(t) => (AnimationProperty::Translation, t.flatten().collect()),
+        ReadOutputs::Rotations(r) => (AnimationProperty::Rotation, r.into_f32().flatten().collect()),
+        ReadOutputs::Scales(s) => (AnimationProperty::Scale, s.flatten().collect()),
+        ReadOutputs::MorphTargetWeights(w) => {
+            (AnimationProperty::MorphTargetWeights, w.into_f32().collect())
+        }
+    };
+
+    Some(Channel {
+        node: channel.target().node().index(),
+        property,
+        interpolation: match channel.sampler().interpolation() {
+            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+            gltf::animation::Interpolation::Step => Interpolation::Step,
+            gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+        },
+        times,
+        values,
+    })
+}
+
+fn mat4_from_cols(m: &[[f32; 4]; 4]) -> Mat4 {
+    Mat4::from_cols(
+        Vec4::from(m[0]),
+        Vec4::from(m[1]),
+        Vec4::from(m[2]),
+        Vec4::from(m[3]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    /// One triangle with an extra float attribute of `components` floats per
+    /// element and `attribute_count` elements, and `indices` as u16
+    fn triangle_gltf(
+        attribute: &str,
+        components: usize,
+        attribute_count: usize,
+        indices: [u16; 3],
+    ) -> String {
+        let mut bytes = Vec::new();
+        for position in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            bytes.extend(position.iter().flat_map(|v| v.to_le_bytes()));
+        }
+        let attribute_offset = bytes.len();
+        for i in 0..attribute_count * components {
+            bytes.extend((i as f32 / 10.0).to_le_bytes());
+        }
+        let index_offset = bytes.len();
+        bytes.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+        bytes.extend([0, 0]);
+
+        let kind = ["SCALAR", "VEC2", "VEC3", "VEC4"][components - 1];
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{
+                    "byteLength": {len},
+                    "uri": "data:application/octet-stream;base64,{data}"
+                }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": {attribute_offset},
+                       "byteLength": {attribute_len} }},
+                    {{ "buffer": 0, "byteOffset": {index_offset}, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                       "min": [0, 0, 0], "max": [1, 1, 0] }},
+                    {{ "bufferView": 1, "componentType": 5126, "count": {attribute_count},
+                       "type": "{kind}" }},
+                    {{ "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "{attribute}": 1 }},
+                    "indices": 2
+                }}] }}]
+            }}"#,
+            len = bytes.len(),
+            data = encode_base64(&bytes),
+            attribute_offset = attribute_offset,
+            attribute_len = attribute_count * components * 4,
+            index_offset = index_offset,
+            attribute_count = attribute_count,
+            kind = kind,
+            attribute = attribute,
+        )
+    }
+
+    /// Writes `source` to a fresh directory under the system temp dir and loads it
+    fn load_source(test: &str, source: &str) -> Result<GltfScene, Error> {
+        let root = std::env::temp_dir().join(format!("opengl-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("scene.gltf"), source).unwrap();
+        let result = load(&Resources::from_rel_path(&root).unwrap(), "scene.gltf");
+        std::fs::remove_dir_all(root).unwrap();
+        result
+    }
+
+    fn assert_invalid(result: Result<GltfScene, Error>, expected: &str) {
+        match result {
+            Err(Error::InvalidDocument { message, .. }) => {
+                assert!(message.contains(expected), "{:?} does not mention {:?}", message, expected)
+            }
+            Err(other) => panic!("expected an invalid document error, got {:?}", other),
+            Ok(_) => panic!("expected an invalid document error, got a scene"),
+        }
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64("SGVsbG8=").unwrap(), b"Hello");
+        assert_eq!(decode_base64("SGVs\nbG8h").unwrap(), b"Hello!");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        // Standard and URL-safe alphabets
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("-_8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("SGV*bG8="), None);
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn percent_decodes_uris() {
+        assert_eq!(percent_decode("my%20scene.bin"), "my scene.bin");
+        assert_eq!(percent_decode("caf%C3%A9.png"), "café.png");
+        assert_eq!(percent_decode("plain.bin"), "plain.bin");
+        // Malformed escapes are kept as written
+        assert_eq!(percent_decode("100%.bin"), "100%.bin");
+        assert_eq!(percent_decode("a%zzb"), "a%zzb");
+        assert_eq!(percent_decode("end%2"), "end%2");
+    }
+
+    #[test]
+    fn loads_a_well_formed_triangle() {
+        let scene = load_source("gltf-valid", &triangle_gltf("NORMAL", 3, 3, [0, 1, 2])).unwrap();
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert_eq!(mesh.vertices[1].normal, Vec3::new(0.3, 0.4, 0.5));
+    }
+
+    #[test]
+    fn rejects_attributes_shorter_than_positions() {
+        for (attribute, components) in [("NORMAL", 3), ("TEXCOORD_0", 2), ("TANGENT", 4)] {
+            let source = triangle_gltf(attribute, components, 2, [0, 1, 2]);
+            assert_invalid(load_source("gltf-short-attribute", &source), attribute);
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let source = triangle_gltf("NORMAL", 3, 3, [0, 1, 3]);
+        assert_invalid(load_source("gltf-index-range", &source), "index 3 out of range");
+    }
+}
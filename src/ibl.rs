@@ -0,0 +1,693 @@
+use crate::buffer::{ArrayBuffer, VertexArray};
+use crate::framebuffer;
+use crate::image::ImageBuffer;
+use crate::math::{radians, Mat4, Vec3};
+use crate::program;
+use crate::resources::{self, Resources};
+use crate::texture::{SamplerDesc, Texture2D, TextureCube, TextureFormat};
+use crate::Program;
+use gl::types::*;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to decode image {}: {}", name, message)]
+    Image { name: String, message: String },
+    #[fail(display = "Failed to load IBL program: {}", inner)]
+    Program { inner: program::Error },
+    #[fail(display = "IBL capture framebuffer is incomplete: {}", inner)]
+    Framebuffer { inner: framebuffer::Error },
+    #[fail(display = "Failed to write IBL cache {}: {}", path, inner)]
+    Cache { path: String, inner: io::Error },
+}
+
+impl From<program::Error> for Error {
+    fn from(inner: program::Error) -> Self {
+        Error::Program { inner }
+    }
+}
+
+/// Texture units `Environment::bind` uses, above the units taken by materials
+pub const IRRADIANCE_UNIT: GLuint = 13;
+pub const PREFILTERED_UNIT: GLuint = 14;
+pub const BRDF_LUT_UNIT: GLuint = 15;
+
+/// Resolution of the maps generated from an environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IblSettings {
+    /// Face size of the environment cube map
+    pub environment_size: u32,
+    /// Face size of the diffuse irradiance cube map
+    pub irradiance_size: u32,
+    /// Face size of the prefiltered specular cube map's level 0
+    pub prefiltered_size: u32,
+    /// Mip levels of the prefiltered map, from roughness 0 to 1
+    pub prefiltered_levels: u32,
+    pub brdf_lut_size: u32,
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+        IblSettings {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            brdf_lut_size: 512,
+        }
+    }
+}
+
+/// Image based lighting maps derived from an HDR environment
+pub struct Environment {
+    environment: TextureCube,
+    irradiance: TextureCube,
+    prefiltered: TextureCube,
+    brdf_lut: Texture2D,
+}
+
+impl Environment {
+    /// Loads an equirectangular Radiance `.hdr` resource and generates the maps
+    pub fn from_resources(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        settings: IblSettings,
+    ) -> Result<Environment, Error> {
+        let bytes = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        Environment::from_equirect(gl, res, &decode_hdr(name, &bytes)?, settings)
+    }
+
+    /// Like `from_resources`, but reuses the maps stored in `cache_dir` by a
+    /// previous run with the same image and settings, and stores them otherwise
+    pub fn from_resources_cached(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        settings: IblSettings,
+        cache_dir: &Path,
+    ) -> Result<Environment, Error> {
+        let bytes = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        let key = cache_key(&bytes, &settings);
+        let path = cache_dir.join(format!("{}.ibl", name.replace(['/', '\\'], "_")));
+
+        // A missing, stale or unreadable cache is regenerated
+        if let Ok(Some(environment)) = Environment::load(gl, &path, key) {
+            return Ok(environment);
+        }
+
+        let environment = Environment::from_equirect(gl, res, &decode_hdr(name, &bytes)?, settings)?;
+        std::fs::create_dir_all(cache_dir)
+            .and_then(|_| environment.save(&path, key))
+            .map_err(|inner| Error::Cache {
+                path: path.to_string_lossy().into_owned(),
+                inner,
+            })?;
+        Ok(environment)
+    }
+
+    /// Generates every map on the GPU from an equirectangular image with
+    /// rows ordered bottom-up.
+    /// Uses the shaders `cubemap.vert`, `equirect_to_cube.frag`,
+    /// `irradiance.frag`, `prefilter.frag` and `brdf_lut.vert/.frag`.
+    pub fn from_equirect(
+        gl: &gl::Gl,
+        res: &Resources,
+        image: &ImageBuffer<f32>,
+        settings: IblSettings,
+    ) -> Result<Environment, Error> {
+        let load = |names: &[&str]| Program::from_resource_files(gl, res, names);
+        let to_cube = load(&["cubemap.vert", "equirect_to_cube.frag"])?;
+        let convolve = load(&["cubemap.vert", "irradiance.frag"])?;
+        let prefilter = load(&["cubemap.vert", "prefilter.frag"])?;
+        let integrate = load(&["brdf_lut.vert", "brdf_lut.frag"])?;
+
+        let capture = Capture::new(gl);
+        unsafe { gl.Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
+
+        let equirect = Texture2D::from_image_f32(gl, image);
+        equirect.set_sampler(&SamplerDesc {
+            wrap_s: gl::REPEAT,
+            ..SamplerDesc::LINEAR_CLAMP
+        });
+        let environment = TextureCube::new(
+            gl,
+            settings.environment_size,
+            TextureFormat::RGB16F,
+            mip_levels(settings.environment_size),
+        );
+        equirect.bind(0);
+        to_cube.set_uniform("u_equirect", &0i32);
+        capture.render_cube(&to_cube, &environment, 0)?;
+        // The mip chain lets the convolutions sample bright texels without aliasing
+        environment.generate_mipmaps();
+
+        let irradiance = TextureCube::new(gl, settings.irradiance_size, TextureFormat::RGB16F, 1);
+        let levels = settings.prefiltered_levels.max(1);
+        let prefiltered = TextureCube::new(gl, settings.prefiltered_size, TextureFormat::RGB16F, levels);
+
+        // Creating the targets rebinds unit 0, so only bind the source once they exist
+        environment.bind(0);
+        convolve.set_uniform("u_environment", &0i32);
+        capture.render_cube(&convolve, &irradiance, 0)?;
+
+        prefilter.set_uniform("u_environment", &0i32);
+        prefilter.set_uniform("u_resolution", &(settings.environment_size as f32));
+        for level in 0..levels {
+            let roughness = level as f32 / (levels - 1).max(1) as f32;
+            prefilter.set_uniform("u_roughness", &roughness);
+            capture.render_cube(&prefilter, &prefiltered, level)?;
+        }
+
+        let size = settings.brdf_lut_size;
+        let brdf_lut = Texture2D::new(gl, size, size, TextureFormat::RG16F);
+        capture.render_quad(&integrate, &brdf_lut)?;
+
+        Ok(Environment {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        })
+    }
+
+    /// The environment itself, e.g. for drawing a skybox
+    pub fn environment(&self) -> &TextureCube {
+        &self.environment
+    }
+
+    pub fn irradiance(&self) -> &TextureCube {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &TextureCube {
+        &self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &Texture2D {
+        &self.brdf_lut
+    }
+
+    /// Binds the lighting maps to `IRRADIANCE_UNIT`, `PREFILTERED_UNIT` and `BRDF_LUT_UNIT`
+    pub fn bind(&self) {
+        self.irradiance.bind(IRRADIANCE_UNIT);
+        self.prefiltered.bind(PREFILTERED_UNIT);
+        self.brdf_lut.bind(BRDF_LUT_UNIT);
+    }
+
+    /// Points the IBL samplers of `pbr.frag` at the units used by `bind`
+    pub fn set_uniforms(&self, program: &Program) {
+        program.set_uniform("u_irradiance_map", &(IRRADIANCE_UNIT as i32));
+        program.set_uniform("u_prefiltered_map", &(PREFILTERED_UNIT as i32));
+        program.set_uniform("u_brdf_lut", &(BRDF_LUT_UNIT as i32));
+        program.set_uniform("u_has_environment", &true);
+        program.set_uniform(
+            "u_max_reflection_lod",
+            &((self.prefiltered.levels() - 1) as f32),
+        );
+    }
+
+    /// Writes every map to `path` as raw floats, tagged with `key`
+    pub fn save(&self, path: &Path, key: u64) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(CACHE_MAGIC)?;
+        out.write_all(&key.to_le_bytes())?;
+
+        for cube in [&self.environment, &self.irradiance, &self.prefiltered] {
+            write_u32s(&mut out, &[cube.size(), cube.levels()])?;
+            for level in 0..cube.levels() {
+                for face in 0..6 {
+                    write_f32s(&mut out, &cube.read_face(face, level))?;
+                }
+            }
+        }
+
+        let lut = self.brdf_lut.read_f32();
+        write_u32s(&mut out, &[lut.width])?;
+        write_f32s(&mut out, &lut.data)?;
+        out.flush()
+    }
+
+    /// Reads maps written by `save`
+    /// # Returns
+    /// `None` if the file does not exist or was saved with another `key`
+    pub fn load(gl: &gl::Gl, path: &Path, key: u64) -> io::Result<Option<Environment>> {
+        let mut input = match File::open(path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut magic = [0u8; 4];
+        let mut stored_key = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        input.read_exact(&mut stored_key)?;
+        if &magic != CACHE_MAGIC || u64::from_le_bytes(stored_key) != key {
+            return Ok(None);
+        }
+
+        let read_cube = |input: &mut BufReader<File>| -> io::Result<TextureCube> {
+            let [size, levels] = read_u32s::<2>(input)?;
+            let cube = TextureCube::new(gl, size, TextureFormat::RGB16F, levels);
+            for level in 0..levels {
+                let level_size = cube.level_size(level) as usize;
+                for face in 0..6 {
+                    cube.upload_face(face, level, &read_f32s(input, level_size * level_size * 3)?);
+                }
+            }
+            Ok(cube)
+        };
+        let environment = read_cube(&mut input)?;
+        let irradiance = read_cube(&mut input)?;
+        let prefiltered = read_cube(&mut input)?;
+
+        let [size] = read_u32s::<1>(&mut input)?;
+        let lut = ImageBuffer {
+            width: size,
+            height: size,
+            channels: 2,
+            data: read_f32s(&mut input, (size * size * 2) as usize)?,
+        };
+        let brdf_lut = Texture2D::from_image_f32(gl, &lut);
+        brdf_lut.set_sampler(&SamplerDesc::LINEAR_CLAMP);
+
+        unsafe { gl.Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
+        Ok(Some(Environment {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        }))
+    }
+}
+
+const CACHE_MAGIC: &[u8; 4] = b"IBL1";
+
+/// FNV-1a hash of the source image and settings, identifying a cache file
+fn cache_key(bytes: &[u8], settings: &IblSettings) -> u64 {
+    let settings = [
+        settings.environment_size,
+        settings.irradiance_size,
+        settings.prefiltered_size,
+        settings.prefiltered_levels,
+        settings.brdf_lut_size,
+    ];
+    bytes
+        .iter()
+        .copied()
+        .chain(settings.iter().flat_map(|v| v.to_le_bytes()))
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn write_u32s(out: &mut impl Write, values: &[u32]) -> io::Result<()> {
+    values.iter().try_for_each(|v| out.write_all(&v.to_le_bytes()))
+}
+
+fn write_f32s(out: &mut impl Write, values: &[f32]) -> io::Result<()> {
+    values.iter().try_for_each(|v| out.write_all(&v.to_le_bytes()))
+}
+
+fn read_u32s<const N: usize>(input: &mut impl Read) -> io::Result<[u32; N]> {
+    let mut values = [0u32; N];
+    for value in &mut values {
+        let mut bytes = [0u8; 4];
+        input.read_exact(&mut bytes)?;
+        *value = u32::from_le_bytes(bytes);
+    }
+    Ok(values)
+}
+
+fn read_f32s(input: &mut impl Read, count: usize) -> io::Result<Vec<f32>> {
+    let mut bytes = vec![0u8; count * 4];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn mip_levels(size: u32) -> u32 {
+    32 - size.max(1).leading_zeros()
+}
+
+fn decode_hdr(name: &str, bytes: &[u8]) -> Result<ImageBuffer<f32>, Error> {
+    let mut image = ImageBuffer::decode_hdr(bytes).map_err(|message| Error::Image {
+        name: name.to_string(),
+        message,
+    })?;
+    image.flip_vertical();
+    Ok(image)
+}
+
+/// Framebuffer and geometry used to render into cube map faces and LUTs.
+/// GL state touched while capturing is restored afterwards.
+struct Capture {
+    gl: gl::Gl,
+    framebuffer: GLuint,
+    cube: VertexArray,
+    _cube_vertices: ArrayBuffer,
+    empty: VertexArray,
+}
+
+impl Capture {
+    fn new(gl: &gl::Gl) -> Self {
+        // Unit cube, 12 triangles; drawn with culling disabled so winding does not matter
+        const CORNERS: [[f32; 3]; 8] = [
+            [-1.0, -1.0, -1.0],
+            [1.0, -1.0, -1.0],
+            [1.0, 1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+            [1.0, -1.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [-1.0, 1.0, 1.0],
+        ];
+        const FACES: [[usize; 4]; 6] = [
+            [1, 5, 6, 2],
+            [4, 0, 3, 7],
+            [3, 2, 6, 7],
+            [4, 5, 1, 0],
+            [5, 4, 7, 6],
+            [0, 1, 2, 3],
+        ];
+        let vertices: Vec<[f32; 3]> = FACES
+            .iter()
+            .flat_map(|f| [f[0], f[1], f[2], f[0], f[2], f[3]])
+            .map(|i| CORNERS[i])
+            .collect();
+
+        let vbo = ArrayBuffer::new(gl);
+        vbo.bind();
+        vbo.static_draw_data(&vertices);
+        let cube = VertexArray::new(gl);
+        cube.bind();
+        unsafe {
+            gl.EnableVertexAttribArray(0);
+            gl.VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+        }
+        cube.unbind();
+        vbo.unbind();
+
+        let mut framebuffer: GLuint = 0;
+        unsafe { gl.GenFramebuffers(1, &mut framebuffer) };
+
+        Capture {
+            gl: gl.clone(),
+            framebuffer,
+            cube,
+            _cube_vertices: vbo,
+            empty: VertexArray::new(gl),
+        }
+    }
+
+    /// Renders `program` over the six faces of `target` at mip `level`,
+    /// looking from the cube center
+    fn render_cube(&self, program: &Program, target: &TextureCube, level: u32) -> Result<(), Error> {
+        const VIEWS: [([f32; 3], [f32; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+        ];
+        let projection = Mat4::perspective(radians(90.0), 1.0, 0.1, 10.0);
+        let size = target.level_size(level);
+
+        self.with_state(size, size, || {
+            program.set_used();
+            self.cube.bind();
+            for (face, (forward, up)) in VIEWS.iter().enumerate() {
+                let view = Mat4::look_at(Vec3::ZERO, Vec3::from(*forward), Vec3::from(*up));
+                program.set_uniform("u_view_projection", &(projection * view));
+                self.attach(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum,
+                    target.id(),
+                    level,
+                )?;
+                unsafe {
+                    self.gl.Clear(gl::COLOR_BUFFER_BIT);
+                    self.gl.DrawArrays(gl::TRIANGLES, 0, 36);
+                }
+            }
+            self.cube.unbind();
+            Ok(())
+        })
+    }
+
+    /// Renders `program` with a fullscreen triangle into `target`
+    fn render_quad(&self, program: &Program, target: &Texture2D) -> Result<(), Error> {
+        self.with_state(target.width(), target.height(), || {
+            program.set_used();
+            self.attach(gl::TEXTURE_2D, target.id(), 0)?;
+            self.empty.bind();
+            unsafe {
+                self.gl.Clear(gl::COLOR_BUFFER_BIT);
+                self.gl.DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+            self.empty.unbind();
+            Ok(())
+        })
+    }
+
+    fn attach(&self, target: GLenum, texture: GLuint, level: u32) -> Result<(), Error> {
+        unsafe {
+            self.gl.FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                target,
+                texture,
+                level as GLint,
+            );
+            match framebuffer::Error::from_status(self.gl.CheckFramebufferStatus(gl::FRAMEBUFFER)) {
+                Some(inner) => Err(Error::Framebuffer { inner }),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Binds the capture framebuffer with a `width` x `height` viewport and
+    /// no depth test, culling, blending or scissor while `f` runs
+    fn with_state<F>(&self, width: u32, height: u32, f: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<(), Error>,
+    {
+        const CAPABILITIES: [GLenum; 4] = [gl::DEPTH_TEST, gl::CULL_FACE, gl::BLEND, gl::SCISSOR_TEST];
+
+        let mut previous_framebuffer: GLint = 0;
+        let mut previous_viewport: [GLint; 4] = [0; 4];
+        let mut enabled = [false; 4];
+        unsafe {
+            self.gl
+                .GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+            self.gl.GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+            for (capability, enabled) in CAPABILITIES.iter().zip(&mut enabled) {
+                *enabled = self.gl.IsEnabled(*capability) == gl::TRUE;
+                self.gl.Disable(*capability);
+            }
+
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            self.gl.Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+
+        let result = f();
+
+        unsafe {
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+            let [x, y, w, h] = previous_viewport;
+            self.gl.Viewport(x, y, w, h);
+            for (capability, enabled) in CAPABILITIES.iter().zip(enabled) {
+                if enabled {
+                    self.gl.Enable(*capability);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteFramebuffers(1, &self.framebuffer) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::framebuffer::{DepthAttachment, Framebuffer, FramebufferDesc};
+    use crate::lighting::{self, Light, LightBuffer};
+    use crate::math::Vec4;
+    use crate::mesh::{Mesh, MeshData};
+    use crate::render_state::{RenderState, StateCache};
+    use std::path::PathBuf;
+
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 96;
+
+    /// Sky gradient over a dark ground with a small bright sun,
+    /// so both the diffuse and the specular terms show
+    fn test_sky() -> ImageBuffer<f32> {
+        let mut image = ImageBuffer::new(64, 32, 3);
+        for y in 0..32 {
+            for x in 0..64 {
+                // Rows are bottom-up: y = 31 is the zenith
+                let elevation = (y as f32 + 0.5) / 32.0 * 2.0 - 1.0;
+                let color = if elevation > 0.0 {
+                    [0.4 + 0.4 * elevation, 0.6 + 0.3 * elevation, 1.0]
+                } else {
+                    [0.15, 0.12, 0.1]
+                };
+                let sun = if (40..42).contains(&x) && (24..26).contains(&y) {
+                    50.0
+                } else {
+                    0.0
+                };
+                image
+                    .pixel_mut(x, y)
+                    .copy_from_slice(&[color[0] + sun, color[1] + sun, color[2] + sun]);
+            }
+        }
+        image
+    }
+
+    /// Compares `image` (rows bottom-up) with `tests/golden/<name>.png`.
+    /// Set `UPDATE_GOLDEN=1` to write the reference instead.
+    fn assert_matches_golden(name: &str, image: &ImageBuffer<u8>) {
+        let mut actual = image.clone();
+        actual.flip_vertical();
+
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let path = root.join("tests/golden").join(format!("{}.png", name));
+        let save = |path: &Path| {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            ::image::save_buffer(
+                path,
+                &actual.data,
+                actual.width,
+                actual.height,
+                ::image::ExtendedColorType::Rgba8,
+            )
+            .unwrap();
+        };
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            save(&path);
+            return;
+        }
+        let golden = std::fs::read(&path)
+            .unwrap_or_else(|_| panic!("missing {:?}, run with UPDATE_GOLDEN=1 to create it", path));
+        let golden = ImageBuffer::decode(&golden).unwrap();
+        assert_eq!((golden.width, golden.height), (actual.width, actual.height));
+
+        // Tolerate rasterizer rounding differences on a few pixels
+        let mismatched = golden
+            .data
+            .chunks_exact(4)
+            .zip(actual.data.chunks_exact(4))
+            .filter(|(g, a)| g.iter().zip(a.iter()).any(|(g, a)| g.abs_diff(*a) > 2))
+            .count();
+        if mismatched > (actual.width * actual.height / 200) as usize {
+            let actual_path = root.join("target").join(format!("{}.actual.png", name));
+            save(&actual_path);
+            panic!(
+                "{} pixels differ from {:?}, output written to {:?}",
+                mismatched, path, actual_path
+            );
+        }
+    }
+
+    /// Spheres going from rough plastic to polished metal, lit by the test sky
+    /// and a directional light.
+    /// Needs an OpenGL 4.5 driver; run it on Mesa's software rasterizer with
+    /// `LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn pbr_spheres_match_golden() {
+        let sdl = sdl2::init().unwrap();
+        let video = sdl.video().unwrap();
+        let gl_attr = video.gl_attr();
+        gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+        gl_attr.set_context_version(4, 5);
+        let window = video
+            .window("golden", WIDTH, HEIGHT)
+            .opengl()
+            .hidden()
+            .build()
+            .unwrap();
+        let _context = window.gl_create_context().unwrap();
+        let gl = gl::Gl::load_with(|s| video.gl_get_proc_address(s) as *const std::os::raw::c_void);
+
+        // Test binaries run from target/<profile>/deps
+        let res = Resources::from_rel_path(Path::new("../shaders")).unwrap();
+        let settings = IblSettings {
+            environment_size: 64,
+            irradiance_size: 16,
+            prefiltered_size: 32,
+            prefiltered_levels: 5,
+            brdf_lut_size: 64,
+        };
+        let environment = Environment::from_equirect(&gl, &res, &test_sky(), settings).unwrap();
+
+        let program = lighting::pbr_program(&gl, &res).unwrap();
+        let lights = LightBuffer::new(&gl);
+        lights.upload(
+            &[Light::directional(Vec3::new(-1.0, -1.0, -1.0), Vec3::splat(2.0))],
+            Vec3::ZERO,
+        );
+        lights.bind();
+        environment.bind();
+        environment.set_uniforms(&program);
+
+        let target = Framebuffer::new(
+            &gl,
+            FramebufferDesc {
+                width: WIDTH,
+                height: HEIGHT,
+                colors: vec![TextureFormat::RGBA8],
+                depth: Some(DepthAttachment::Renderbuffer(TextureFormat::DEPTH24)),
+                samples: 0,
+            },
+        )
+        .unwrap();
+        let sphere = Mesh::new(&gl, &MeshData::uv_sphere(1.0, 32, 16));
+        let mut camera = Camera::perspective(radians(20.0), 0.1, 100.0, WIDTH, HEIGHT);
+        camera.position = Vec3::new(0.0, 0.0, 20.0);
+
+        let mut cache = StateCache::new(&gl);
+        cache.reset(&RenderState::opaque());
+        {
+            let _bound = target.bind();
+            unsafe {
+                gl.ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+            program.set_used();
+            for i in 0..5 {
+                let t = i as f32 / 4.0;
+                let model = Mat4::from_translation(Vec3::new((i as f32 - 2.0) * 2.4, 0.0, 0.0));
+                lighting::set_transform_uniforms(&program, &camera, &model);
+                program.set_uniform("u_base_color_factor", &Vec4::new(0.9, 0.5, 0.3, 1.0));
+                program.set_uniform("u_metallic_factor", &t);
+                program.set_uniform("u_roughness_factor", &(1.0 - 0.9 * t));
+                sphere.draw();
+            }
+        }
+
+        assert_matches_golden("pbr_spheres", &target.read_pixels(0));
+    }
+}
@@ -0,0 +1,74 @@
+/// CPU-side image with tightly packed rows of `channels` components each
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer<T = u8> {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub data: Vec<T>,
+}
+
+impl<T: Copy + Default> ImageBuffer<T> {
+    /// Creates an image filled with `T::default()`
+    pub fn new(width: u32, height: u32, channels: u32) -> Self {
+        ImageBuffer {
+            width,
+            height,
+            channels,
+            data: vec![T::default(); (width * height * channels) as usize],
+        }
+    }
+
+    /// The components of pixel (`x`, `y`), counted from the first row
+    pub fn pixel(&self, x: u32, y: u32) -> &[T] {
+        let start = ((y * self.width + x) * self.channels) as usize;
+        &self.data[start..start + self.channels as usize]
+    }
+
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> &mut [T] {
+        let start = ((y * self.width + x) * self.channels) as usize;
+        &mut self.data[start..start + self.channels as usize]
+    }
+
+    /// Reverses the row order.
+    /// OpenGL stores images bottom-up, most file formats top-down.
+    pub fn flip_vertical(&mut self) {
+        let row = (self.width * self.channels) as usize;
+        let height = self.height as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.data.split_at_mut((height - 1 - y) * row);
+            top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+        }
+    }
+}
+
+impl ImageBuffer<u8> {
+    /// Decodes a PNG or JPEG file to 8-bit RGBA, rows in file order (top-down)
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let image = ::image::load_from_memory(bytes)
+            .map_err(|e| e.to_string())?
+            .to_rgba8();
+
+        Ok(ImageBuffer {
+            width: image.width(),
+            height: image.height(),
+            channels: 4,
+            data: image.into_raw(),
+        })
+    }
+}
+
+impl ImageBuffer<f32> {
+    /// Decodes a Radiance `.hdr` file to linear RGB floats, rows top-down
+    pub fn decode_hdr(bytes: &[u8]) -> Result<Self, String> {
+        let image = ::image::load_from_memory_with_format(bytes, ::image::ImageFormat::Hdr)
+            .map_err(|e| e.to_string())?
+            .to_rgb32f();
+
+        Ok(ImageBuffer {
+            width: image.width(),
+            height: image.height(),
+            channels: 3,
+            data: image.into_raw(),
+        })
+    }
+}
@@ -0,0 +1,620 @@
+use crate::math::Vec2;
+use crate::resources::{self, Resources};
+use sdl2::controller::{Axis, Button as GamepadButton};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::{MouseButton, MouseWheelDirection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to parse input bindings {}: {}", name, message)]
+    Parse { name: String, message: String },
+    #[fail(display = "Unknown button {:?} bound to `{}` in {}", button, action, name)]
+    UnknownButton { name: String, action: String, button: String },
+}
+
+const MOUSE_BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::X1,
+    MouseButton::X2,
+];
+
+const GAMEPAD_BUTTONS: [GamepadButton; 21] = [
+    GamepadButton::A,
+    GamepadButton::B,
+    GamepadButton::X,
+    GamepadButton::Y,
+    GamepadButton::Back,
+    GamepadButton::Guide,
+    GamepadButton::Start,
+    GamepadButton::LeftStick,
+    GamepadButton::RightStick,
+    GamepadButton::LeftShoulder,
+    GamepadButton::RightShoulder,
+    GamepadButton::DPadUp,
+    GamepadButton::DPadDown,
+    GamepadButton::DPadLeft,
+    GamepadButton::DPadRight,
+    GamepadButton::Misc1,
+    GamepadButton::Paddle1,
+    GamepadButton::Paddle2,
+    GamepadButton::Paddle3,
+    GamepadButton::Paddle4,
+    GamepadButton::Touchpad,
+];
+
+/// Anything that can be pressed and bound to an action.
+///
+/// Written as SDL key names (`"Space"`, `"Left Shift"`), `"Mouse"` followed by
+/// a mouse button (`"MouseLeft"`) or `"Gamepad"` followed by a controller
+/// button (`"GamepadA"`, `"GamepadDPadUp"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(Keycode),
+    Mouse(MouseButton),
+    /// The button on any connected gamepad
+    Gamepad(GamepadButton),
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Button::Key(key) => write!(f, "{}", key.name()),
+            Button::Mouse(button) => write!(f, "Mouse{:?}", button),
+            Button::Gamepad(button) => write!(f, "Gamepad{:?}", button),
+        }
+    }
+}
+
+impl FromStr for Button {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.strip_prefix("Mouse") {
+            if let Some(button) = MOUSE_BUTTONS.iter().find(|b| format!("{:?}", b) == name) {
+                return Ok(Button::Mouse(*button));
+            }
+        }
+        if let Some(name) = s.strip_prefix("Gamepad") {
+            if let Some(button) = GAMEPAD_BUTTONS.iter().find(|b| format!("{:?}", b) == name) {
+                return Ok(Button::Gamepad(*button));
+            }
+        }
+        Keycode::from_name(s).map(Button::Key).ok_or(())
+    }
+}
+
+/// Named actions and the buttons triggering them, e.g. `"jump"` bound to
+/// `Space` and `GamepadA`, so game code does not depend on the device.
+///
+/// Loaded from RON maps of action names to button names:
+/// `{ "jump": ["Space", "GamepadA"], "fire": ["MouseLeft"] }`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionMap {
+    bindings: BTreeMap<String, Vec<Button>>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap::default()
+    }
+
+    pub fn from_resources(res: &Resources, name: &str) -> Result<Self, Error> {
+        let source = res.load(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        ActionMap::from_ron(&source.to_string_lossy(), name)
+    }
+
+    /// Parses bindings from RON; `name` identifies the source in errors
+    pub fn from_ron(source: &str, name: &str) -> Result<Self, Error> {
+        let desc: BTreeMap<String, Vec<String>> =
+            ron::from_str(source).map_err(|e| Error::Parse {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+
+        let mut map = ActionMap::new();
+        for (action, buttons) in desc {
+            let buttons = buttons
+                .iter()
+                .map(|button| {
+                    button.parse().map_err(|_| Error::UnknownButton {
+                        name: name.to_string(),
+                        action: action.clone(),
+                        button: button.clone(),
+                    })
+                })
+                .collect::<Result<Vec<Button>, Error>>()?;
+            map.rebind(&action, buttons);
+        }
+        Ok(map)
+    }
+
+    /// Adds `button` to the buttons triggering `action`
+    pub fn bind(&mut self, action: &str, button: Button) {
+        let buttons = self.bindings.entry(action.to_string()).or_default();
+        if !buttons.contains(&button) {
+            buttons.push(button);
+        }
+    }
+
+    /// Replaces every button of `action`
+    pub fn rebind(&mut self, action: &str, buttons: Vec<Button>) {
+        self.bindings.insert(action.to_string(), buttons);
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    /// Buttons bound to `action`, none for unknown actions
+    pub fn bindings(&self, action: &str) -> &[Button] {
+        self.bindings.get(action).map_or(&[], |buttons| buttons)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(|action| action.as_str())
+    }
+}
+
+/// Keyboard, mouse and gamepad state, fed every SDL event by `app::run`.
+///
+/// Pressed and released states last for the frame the event arrived in,
+/// held states until the release. Events can also be built by hand and
+/// passed to `handle_event`, e.g. to drive the input from tests.
+#[derive(Debug, Default)]
+pub struct Input {
+    pub actions: ActionMap,
+    held: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    /// Gamepad buttons down, per controller instance id
+    gamepad_held: HashSet<(u32, GamepadButton)>,
+    axes: HashMap<(u32, Axis), f32>,
+    mouse_position: Vec2,
+    mouse_delta: Vec2,
+    wheel: Vec2,
+    relative_mouse_mode: bool,
+    text: String,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Input::default()
+    }
+
+    /// Forgets the per-frame state of the previous frame; called by
+    /// `app::run` before polling events
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = Vec2::ZERO;
+        self.wheel = Vec2::ZERO;
+        self.text.clear();
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat: false,
+                ..
+            } => self.press(Button::Key(key)),
+            Event::KeyUp {
+                keycode: Some(key), ..
+            } => self.release(Button::Key(key)),
+            Event::MouseButtonDown { mouse_btn, .. } => self.press(Button::Mouse(mouse_btn)),
+            Event::MouseButtonUp { mouse_btn, .. } => self.release(Button::Mouse(mouse_btn)),
+            Event::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => {
+                self.mouse_position = Vec2::new(x as f32, y as f32);
+                self.mouse_delta += Vec2::new(xrel as f32, yrel as f32);
+            }
+            Event::MouseWheel {
+                x, y, direction, ..
+            } => {
+                let sign = match direction {
+                    MouseWheelDirection::Flipped => -1.0,
+                    _ => 1.0,
+                };
+                self.wheel += Vec2::new(x as f32, y as f32) * sign;
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.gamepad_held.insert((which, button));
+                self.press(Button::Gamepad(button));
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.gamepad_held.remove(&(which, button));
+                // Still held while another gamepad holds it
+                if !self.gamepad_held.iter().any(|(_, b)| *b == button) {
+                    self.release(Button::Gamepad(button));
+                }
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let value = (value as f32 / i16::MAX as f32).max(-1.0);
+                self.axes.insert((which, axis), value);
+            }
+            Event::ControllerDeviceRemoved { which, .. } => self.disconnect_gamepad(which),
+            Event::TextInput { ref text, .. } => self.text.push_str(text),
+            // Releases are not delivered to unfocused windows
+            Event::Window {
+                win_event: WindowEvent::FocusLost,
+                ..
+            } => self.release_all(),
+            _ => {}
+        }
+    }
+
+    /// Whether `button` went down this frame
+    pub fn pressed(&self, button: Button) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// Whether `button` is down
+    pub fn held(&self, button: Button) -> bool {
+        self.held.contains(&button)
+    }
+
+    /// Whether `button` went up this frame
+    pub fn released(&self, button: Button) -> bool {
+        self.released.contains(&button)
+    }
+
+    /// Whether a button bound to `action` went down this frame
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.pressed(*b))
+    }
+
+    /// Whether a button bound to `action` is down
+    pub fn action_held(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.held(*b))
+    }
+
+    /// Whether a button bound to `action` went up this frame
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.released(*b))
+    }
+
+    /// Cursor position in window coordinates
+    pub fn mouse_position(&self) -> Vec2 {
+        self.mouse_position
+    }
+
+    /// Mouse movement this frame, also reported in relative mouse mode
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    /// Wheel scrolling this frame, positive y away from the user
+    pub fn wheel(&self) -> Vec2 {
+        self.wheel
+    }
+
+    /// Position of `axis` from -1 to 1 (triggers from 0 to 1), the one
+    /// furthest from rest across the connected gamepads
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes
+            .iter()
+            .filter(|((_, a), _)| *a == axis)
+            .map(|(_, value)| *value)
+            .fold(0.0, |furthest, value| {
+                if value.abs() > furthest.abs() {
+                    value
+                } else {
+                    furthest
+                }
+            })
+    }
+
+    /// Text typed this frame while text input is active
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the cursor is hidden and confined, reporting only
+    /// movement; changed with `Context::set_relative_mouse_mode`
+    pub fn relative_mouse_mode(&self) -> bool {
+        self.relative_mouse_mode
+    }
+
+    pub(crate) fn set_relative_mouse_mode(&mut self, enabled: bool) {
+        self.relative_mouse_mode = enabled;
+    }
+
+    /// Releases every held button, e.g. when the window loses focus
+    pub fn release_all(&mut self) {
+        for button in std::mem::take(&mut self.held) {
+            self.released.insert(button);
+        }
+        self.gamepad_held.clear();
+        self.axes.clear();
+    }
+
+    /// Releases the buttons and centres the axes of a removed gamepad
+    pub fn disconnect_gamepad(&mut self, which: u32) {
+        let buttons: Vec<GamepadButton> = self
+            .gamepad_held
+            .iter()
+            .filter(|(id, _)| *id == which)
+            .map(|(_, button)| *button)
+            .collect();
+        for button in buttons {
+            self.handle_event(&Event::ControllerButtonUp {
+                timestamp: 0,
+                which,
+                button,
+            });
+        }
+        self.axes.retain(|(id, _), _| *id != which);
+    }
+
+    fn press(&mut self, button: Button) {
+        if self.held.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: Button) {
+        if self.held.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::keyboard::Mod;
+
+    fn key_down(key: Keycode, repeat: bool) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat,
+        }
+    }
+
+    fn key_up(key: Keycode) -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    fn gamepad_down(which: u32, button: GamepadButton) -> Event {
+        Event::ControllerButtonDown {
+            timestamp: 0,
+            which,
+            button,
+        }
+    }
+
+    fn gamepad_up(which: u32, button: GamepadButton) -> Event {
+        Event::ControllerButtonUp {
+            timestamp: 0,
+            which,
+            button,
+        }
+    }
+
+    fn state(input: &Input, button: Button) -> (bool, bool, bool) {
+        (input.pressed(button), input.held(button), input.released(button))
+    }
+
+    #[test]
+    fn pressed_held_released_across_frames() {
+        let space = Button::Key(Keycode::Space);
+        let mut input = Input::new();
+
+        input.begin_frame();
+        input.handle_event(&key_down(Keycode::Space, false));
+        assert_eq!(state(&input, space), (true, true, false));
+
+        input.begin_frame();
+        assert_eq!(state(&input, space), (false, true, false));
+
+        input.begin_frame();
+        input.handle_event(&key_up(Keycode::Space));
+        assert_eq!(state(&input, space), (false, false, true));
+
+        input.begin_frame();
+        assert_eq!(state(&input, space), (false, false, false));
+    }
+
+    #[test]
+    fn press_and_release_in_one_frame() {
+        let left = Button::Mouse(MouseButton::Left);
+        let mut input = Input::new();
+
+        input.handle_event(&Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        });
+        input.handle_event(&Event::MouseButtonUp {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        });
+        assert_eq!(state(&input, left), (true, false, true));
+    }
+
+    #[test]
+    fn key_repeat_is_ignored() {
+        let w = Button::Key(Keycode::W);
+        let mut input = Input::new();
+
+        input.handle_event(&key_down(Keycode::W, false));
+        input.begin_frame();
+        input.handle_event(&key_down(Keycode::W, true));
+        input.handle_event(&key_down(Keycode::W, true));
+        assert_eq!(state(&input, w), (false, true, false));
+
+        // A repeat alone, e.g. for a key held before the window got focus
+        let a = Button::Key(Keycode::A);
+        input.handle_event(&key_down(Keycode::A, true));
+        assert_eq!(state(&input, a), (false, false, false));
+    }
+
+    #[test]
+    fn gamepad_button_held_on_two_controllers() {
+        let a = Button::Gamepad(GamepadButton::A);
+        let mut input = Input::new();
+
+        input.handle_event(&gamepad_down(0, GamepadButton::A));
+        input.begin_frame();
+        input.handle_event(&gamepad_down(1, GamepadButton::A));
+        // Already held through the first controller
+        assert_eq!(state(&input, a), (false, true, false));
+
+        input.begin_frame();
+        input.handle_event(&gamepad_up(0, GamepadButton::A));
+        assert_eq!(state(&input, a), (false, true, false));
+
+        input.begin_frame();
+        input.handle_event(&gamepad_up(1, GamepadButton::A));
+        assert_eq!(state(&input, a), (false, false, true));
+    }
+
+    #[test]
+    fn disconnecting_a_gamepad_releases_its_buttons() {
+        let b = Button::Gamepad(GamepadButton::B);
+        let mut input = Input::new();
+
+        input.handle_event(&gamepad_down(3, GamepadButton::B));
+        input.handle_event(&Event::ControllerAxisMotion {
+            timestamp: 0,
+            which: 3,
+            axis: Axis::LeftX,
+            value: i16::MIN,
+        });
+        assert_eq!(input.axis(Axis::LeftX), -1.0);
+
+        input.begin_frame();
+        input.handle_event(&Event::ControllerDeviceRemoved {
+            timestamp: 0,
+            which: 3,
+        });
+        assert_eq!(state(&input, b), (false, false, true));
+        assert_eq!(input.axis(Axis::LeftX), 0.0);
+    }
+
+    #[test]
+    fn focus_lost_releases_everything() {
+        let mut input = Input::new();
+        input.handle_event(&key_down(Keycode::LShift, false));
+        input.handle_event(&gamepad_down(0, GamepadButton::X));
+        input.handle_event(&Event::ControllerAxisMotion {
+            timestamp: 0,
+            which: 0,
+            axis: Axis::TriggerRight,
+            value: i16::MAX,
+        });
+
+        input.begin_frame();
+        input.handle_event(&Event::Window {
+            timestamp: 0,
+            window_id: 0,
+            win_event: WindowEvent::FocusLost,
+        });
+
+        let shift = Button::Key(Keycode::LShift);
+        let x = Button::Gamepad(GamepadButton::X);
+        assert_eq!(state(&input, shift), (false, false, true));
+        assert_eq!(state(&input, x), (false, false, true));
+        assert_eq!(input.axis(Axis::TriggerRight), 0.0);
+
+        // The gamepad release arriving later must not release it again
+        input.begin_frame();
+        input.handle_event(&gamepad_up(0, GamepadButton::X));
+        assert_eq!(state(&input, x), (false, false, false));
+    }
+
+    #[test]
+    fn action_map_from_ron() {
+        let actions = ActionMap::from_ron(
+            r#"{ "jump": ["Space", "GamepadA"], "fire": ["MouseLeft"] }"#,
+            "test.ron",
+        )
+        .unwrap();
+
+        assert_eq!(actions.actions().collect::<Vec<_>>(), ["fire", "jump"]);
+        assert_eq!(
+            actions.bindings("jump"),
+            [Button::Key(Keycode::Space), Button::Gamepad(GamepadButton::A)]
+        );
+        assert_eq!(actions.bindings("fire"), [Button::Mouse(MouseButton::Left)]);
+        assert!(actions.bindings("crouch").is_empty());
+
+        match ActionMap::from_ron(r#"{ "jump": ["Spacebar"] }"#, "test.ron") {
+            Err(Error::UnknownButton { action, button, .. }) => {
+                assert_eq!((action.as_str(), button.as_str()), ("jump", "Spacebar"))
+            }
+            other => panic!("expected an unknown button error, got {:?}", other),
+        }
+        assert!(matches!(
+            ActionMap::from_ron(r#"{ "jump": "Space" }"#, "test.ron"),
+            Err(Error::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rebind_changes_the_buttons_of_an_action() {
+        let mut input = Input::new();
+        input.actions = ActionMap::from_ron(r#"{ "jump": ["Space"] }"#, "test.ron").unwrap();
+
+        input.handle_event(&key_down(Keycode::Space, false));
+        assert!(input.action_pressed("jump"));
+
+        input.actions.rebind("jump", vec![Button::Key(Keycode::J)]);
+        assert!(!input.action_pressed("jump"));
+        assert!(!input.action_held("jump"));
+
+        input.handle_event(&key_down(Keycode::J, false));
+        assert!(input.action_pressed("jump"));
+
+        input.begin_frame();
+        input.handle_event(&key_up(Keycode::J));
+        assert!(input.action_released("jump"));
+        assert!(!input.action_held("jump"));
+    }
+
+    #[test]
+    fn button_names_round_trip() {
+        for button in [
+            Button::Key(Keycode::Space),
+            Button::Key(Keycode::LShift),
+            Button::Mouse(MouseButton::X1),
+            Button::Gamepad(GamepadButton::DPadUp),
+        ] {
+            assert_eq!(button.to_string().parse::<Button>(), Ok(button));
+        }
+    }
+}
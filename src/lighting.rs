@@ -0,0 +1,370 @@
+use crate::buffer::{Std430, UniformBuffer};
+use crate::camera::Camera;
+use crate::math::{Mat3, Mat4, Vec3};
+use crate::program;
+use crate::resources::Resources;
+use crate::{ibl, shadow, Program};
+use gl::types::*;
+
+/// Lights packed per draw; must match `MAX_LIGHTS` in `shaders/lights.glsl`
+pub const MAX_LIGHTS: usize = 8;
+
+/// Uniform buffer binding point of the `Lights` block
+pub const LIGHTS_BINDING: GLuint = 0;
+
+/// Distance falloff `1 / (constant + linear * d + quadratic * d^2)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub const NONE: Attenuation = Attenuation {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+
+    /// Falloff dropping to roughly 1% at `range`
+    pub fn from_range(range: f32) -> Self {
+        Attenuation {
+            constant: 1.0,
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+
+    /// Distance where the falloff drops to 1%, infinite without falloff
+    pub fn range(&self) -> f32 {
+        let c = self.constant - 100.0;
+        if self.quadratic > 0.0 {
+            let discriminant = self.linear * self.linear - 4.0 * self.quadratic * c;
+            (-self.linear + discriminant.sqrt()) / (2.0 * self.quadratic)
+        } else if self.linear > 0.0 {
+            -c / self.linear
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+/// Light source, in world space.
+/// Lights with `cast_shadows` get a shadow map from `shadow::ShadowMaps`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    /// Constant light reaching every surface
+    Ambient { color: Vec3, intensity: f32 },
+    /// Parallel rays travelling along `direction`, e.g. the sun
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        cast_shadows: bool,
+    },
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        cast_shadows: bool,
+    },
+    /// Cone of light fading out between `inner_angle` and `outer_angle`
+    /// (half angles in radians)
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+        cast_shadows: bool,
+    },
+}
+
+impl Light {
+    pub fn ambient(color: Vec3) -> Self {
+        Light::Ambient {
+            color,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn directional(direction: Vec3, color: Vec3) -> Self {
+        Light::Directional {
+            direction: direction.normalize(),
+            color,
+            intensity: 1.0,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn point(position: Vec3, color: Vec3, range: f32) -> Self {
+        Light::Point {
+            position,
+            color,
+            intensity: 1.0,
+            attenuation: Attenuation::from_range(range),
+            cast_shadows: false,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Light::Spot {
+            position,
+            direction: direction.normalize(),
+            color,
+            intensity: 1.0,
+            attenuation: Attenuation::from_range(range),
+            inner_angle,
+            outer_angle,
+            cast_shadows: false,
+        }
+    }
+
+    /// The same light casting shadows; ambient lights never do
+    pub fn with_shadows(mut self) -> Self {
+        match &mut self {
+            Light::Ambient { .. } => {}
+            Light::Directional { cast_shadows, .. }
+            | Light::Point { cast_shadows, .. }
+            | Light::Spot { cast_shadows, .. } => *cast_shadows = true,
+        }
+        self
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        match *self {
+            Light::Ambient { .. } => false,
+            Light::Directional { cast_shadows, .. }
+            | Light::Point { cast_shadows, .. }
+            | Light::Spot { cast_shadows, .. } => cast_shadows,
+        }
+    }
+
+    /// Rough brightness contributed at `point`, used to pick the lights of a draw
+    fn influence(&self, point: Vec3) -> f32 {
+        let brightness = |color: Vec3, intensity: f32| color.x.max(color.y).max(color.z) * intensity;
+        match *self {
+            Light::Ambient { .. } | Light::Directional { .. } => f32::INFINITY,
+            Light::Point {
+                position,
+                color,
+                intensity,
+                attenuation,
+                ..
+            }
+            | Light::Spot {
+                position,
+                color,
+                intensity,
+                attenuation,
+                ..
+            } => brightness(color, intensity) * attenuation.at(position.distance(point)),
+        }
+    }
+
+    /// `shadow_slot` is the index from `shadow::shadow_slots`, -1 for none
+    pub(crate) fn to_gpu(self, shadow_slot: i32) -> GpuLight {
+        let slot = shadow_slot as f32;
+        let radiance = |color: Vec3, intensity: f32| color * intensity;
+        match self {
+            Light::Ambient { .. } => GpuLight::default(),
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+                ..
+            } => GpuLight {
+                position: [0.0, 0.0, 0.0, LIGHT_DIRECTIONAL],
+                direction: direction.extend(0.0).to_array(),
+                color: radiance(color, intensity).extend(0.0).to_array(),
+                attenuation: [1.0, 0.0, 0.0, slot],
+            },
+            Light::Point {
+                position,
+                color,
+                intensity,
+                attenuation: a,
+                ..
+            } => GpuLight {
+                position: position.extend(LIGHT_POINT).to_array(),
+                direction: [0.0; 4],
+                color: radiance(color, intensity).extend(0.0).to_array(),
+                attenuation: [a.constant, a.linear, a.quadratic, slot],
+            },
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                attenuation: a,
+                inner_angle,
+                outer_angle,
+                ..
+            } => GpuLight {
+                position: position.extend(LIGHT_SPOT).to_array(),
+                direction: direction.extend(inner_angle.cos()).to_array(),
+                color: radiance(color, intensity).extend(outer_angle.cos()).to_array(),
+                attenuation: [a.constant, a.linear, a.quadratic, slot],
+            },
+        }
+    }
+}
+
+const LIGHT_DIRECTIONAL: f32 = 0.0;
+const LIGHT_POINT: f32 = 1.0;
+const LIGHT_SPOT: f32 = 2.0;
+
+/// `struct Light` of `shaders/light_types.glsl`, identical in std140 and std430
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct GpuLight {
+    /// xyz position, w light type
+    position: [f32; 4],
+    /// xyz direction, w cosine of the spot inner angle
+    direction: [f32; 4],
+    /// rgb radiance, w cosine of the spot outer angle
+    color: [f32; 4],
+    /// constant, linear, quadratic, w shadow slot
+    attenuation: [f32; 4],
+}
+
+unsafe impl Std430 for GpuLight {}
+
+/// `uniform Lights` block of `shaders/lights.glsl`, std140
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LightBlock {
+    ambient: [f32; 4],
+    count: [i32; 4],
+    lights: [GpuLight; MAX_LIGHTS],
+}
+
+/// Uniform buffer feeding the `Lights` block of the lit shaders
+pub struct LightBuffer {
+    buffer: UniformBuffer,
+}
+
+impl LightBuffer {
+    pub fn new(gl: &gl::Gl) -> Self {
+        let buffer = UniformBuffer::new(gl);
+        buffer.bind();
+        unsafe {
+            gl.BufferData(
+                gl::UNIFORM_BUFFER,
+                std::mem::size_of::<LightBlock>() as GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+        }
+        buffer.unbind();
+
+        LightBuffer { buffer }
+    }
+
+    /// Packs the ambient lights and the `MAX_LIGHTS` other lights
+    /// with the most influence around `center`, along with their shadow slots
+    /// # Returns
+    /// Number of non-ambient lights uploaded
+    pub fn upload(&self, lights: &[Light], center: Vec3) -> usize {
+        let mut ambient = Vec3::ZERO;
+        let slots = shadow::shadow_slots(lights);
+        let mut selected: Vec<(f32, &Light, i32)> = Vec::with_capacity(lights.len());
+        for (light, slot) in lights.iter().zip(slots) {
+            match *light {
+                Light::Ambient { color, intensity } => ambient += color * intensity,
+                _ => selected.push((light.influence(center), light, slot)),
+            }
+        }
+        if selected.len() > MAX_LIGHTS {
+            selected.sort_by(|a, b| b.0.total_cmp(&a.0));
+            selected.truncate(MAX_LIGHTS);
+        }
+
+        let mut block = LightBlock {
+            ambient: ambient.extend(0.0).to_array(),
+            count: [selected.len() as i32, 0, 0, 0],
+            lights: [GpuLight::default(); MAX_LIGHTS],
+        };
+        for (gpu, (_, light, slot)) in block.lights.iter_mut().zip(&selected) {
+            *gpu = light.to_gpu(*slot);
+        }
+
+        self.buffer.bind();
+        self.buffer.sub_data(0, std::slice::from_ref(&block));
+        self.buffer.unbind();
+
+        selected.len()
+    }
+
+    /// Binds the buffer to `LIGHTS_BINDING`
+    pub fn bind(&self) {
+        self.buffer.bind_base(LIGHTS_BINDING);
+    }
+}
+
+/// Loads the built-in Blinn-Phong program (`lit.vert` + `lit.frag`)
+/// with its `Lights` block assigned to `LIGHTS_BINDING`.
+/// # Uniforms
+/// * `u_diffuse_color` (vec4), `u_specular_color` (vec3), `u_shininess` (float)
+/// * `u_diffuse_map` / `u_has_diffuse_map` - sRGB albedo texture
+/// * `u_normal_map` / `u_has_normal_map` - tangent space normal map
+/// * the transforms set by `set_transform_uniforms`
+/// * the shadow lookups set by `shadow::ShadowMaps::set_uniforms`
+pub fn lit_program(gl: &gl::Gl, res: &Resources) -> Result<Program, program::Error> {
+    load_lit_program(gl, res, "lit")
+}
+
+/// Loads the built-in metallic-roughness program (`pbr.vert` + `pbr.frag`)
+/// with its `Lights` block assigned to `LIGHTS_BINDING`.
+/// Material uniforms follow the glTF names (`u_base_color_factor`,
+/// `u_metallic_roughness_map`, ...); image based lighting is enabled by
+/// `ibl::Environment::set_uniforms` and shadows by `shadow::ShadowMaps::set_uniforms`.
+pub fn pbr_program(gl: &gl::Gl, res: &Resources) -> Result<Program, program::Error> {
+    load_lit_program(gl, res, "pbr")
+}
+
+/// Binds the `Lights` block and gives the renderer-wide samplers their own
+/// texture units: samplers of different types left on unit 0 would make
+/// every draw fail, even when unused
+fn load_lit_program(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Program, program::Error> {
+    let program = Program::from_resources(gl, res, name)?;
+    program.bind_uniform_block("Lights", LIGHTS_BINDING);
+
+    let units = [
+        ("u_irradiance_map", ibl::IRRADIANCE_UNIT),
+        ("u_prefiltered_map", ibl::PREFILTERED_UNIT),
+        ("u_brdf_lut", ibl::BRDF_LUT_UNIT),
+        ("u_cascade_maps", shadow::CASCADE_UNIT),
+        ("u_spot_shadow_maps", shadow::SPOT_UNIT),
+        ("u_point_shadow_maps", shadow::POINT_UNIT),
+    ];
+    for (name, unit) in units {
+        program.set_uniform(name, &(unit as i32));
+    }
+    Ok(program)
+}
+
+/// Sets `u_model`, `u_normal_matrix`, `u_view_projection`,
+/// `u_camera_position` and `u_camera_forward` for drawing with `model` from `camera`
+pub fn set_transform_uniforms(program: &Program, camera: &Camera, model: &Mat4) {
+    program.set_uniform("u_model", model);
+    program.set_uniform("u_normal_matrix", &Mat3::normal_matrix(model));
+    program.set_uniform("u_view_projection", &camera.view_projection());
+    program.set_uniform("u_camera_position", &camera.position);
+    program.set_uniform("u_camera_forward", &camera.forward());
+}
@@ -5,114 +5,156 @@
 extern crate gl;
 extern crate ogl_main;
 extern crate sdl2;
+extern crate color;
+extern crate ogl_runtime;
 #[macro_use] extern crate failure;
 
 mod program;
 mod shader;
 mod util;
-mod resources;
 mod from_resource;
+mod buffer;
+mod compute;
+mod image;
+mod texture;
+mod framebuffer;
+mod render_state;
+mod math;
+mod uniform;
+mod camera;
+mod mesh;
+mod obj;
+mod gltf_loader;
+mod scene;
+mod material;
+mod lighting;
+mod ibl;
+mod shadow;
+mod deferred;
+mod postprocess;
+mod text;
+mod packer;
+mod sprite;
+mod debug_draw;
+mod app;
+mod timing;
+mod input;
 
 use gl::types::*;
 use ogl_main::ogl_main;
+use ogl_runtime::resources;
 use std::ffi::{CStr, CString};
 use std::path::Path;
 
+use app::{App, Context, Frame};
 use program::Program;
 use shader::Shader;
 use crate::program::Error;
 
 #[ogl_main(title = "Gamer", window = "800x600", bg_color = "0.3 0.3 0.5 1.0")]
 fn main() {
-    // Load resources
-    let res = resources::Resources::from_rel_path(Path::new("shaders")).unwrap();
-
-    // Create shader program from resources loaded
-    let shader_program = match Program::from_resources(&gl, &res, "triangle") {
-        Ok(sp) => sp,
-        Err(e) => { println!("{}", e); return; }
-    };
-    shader_program.set_used();
-
-    // Create a vertex array object
-    let vertices: Vec<f32> = vec![
-       // Positions        // Colors
-       -0.5, -0.5, 0.0,    1.0, 0.0, 0.0, // Bottom-left
-        0.5, -0.5, 0.0,    0.0, 1.0, 0.0, // Bottom-right
-        0.0,  0.5, 0.0,    0.0, 0.0, 1.0, // Top
-    ];
-
-    // Create bindings for shader rendering
-    let mut vbo: GLuint = 0;
-    let mut vao: GLuint = 0;
-    unsafe {
-        // Create a vertex buffer object to host our vertices
-        gl.GenBuffers(1, &mut vbo);
-        gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl.BufferData(
-            gl::ARRAY_BUFFER,                      // target
-            util::sizeof(&vertices) as GLsizeiptr, // mem alloc (buffer size in bytes)
-            vertices.as_ptr() as *const GLvoid,    // void pointer to data
-            gl::STATIC_DRAW,                       // usage
-        );
-
-        // Create a vertex array object
-        gl.GenVertexArrays(1, &mut vao);
-        gl.BindVertexArray(vao);
-
-        // Setup vertex attributes
-        gl.EnableVertexAttribArray(0); // layout (position = 0) in shader
-        gl.VertexAttribPointer(
-            0,                                               // layout (position = 0) in shader
-            3,                                               // number of attributes
-            gl::FLOAT,                                       // type of data (f32)
-            gl::FALSE,                                       // normalized?
-            (6 * std::mem::size_of::<GLfloat>()) as GLsizei, // stride (0 = tightly packed)
-            std::ptr::null(),                                // offset of component
-        );
-        gl.EnableVertexAttribArray(1); // layout (position = 0) in shader
-        gl.VertexAttribPointer(
-            1,                                                 // layout (position = 0) in shader
-            3,                                                 // number of attributes
-            gl::FLOAT,                                         // type of data (f32)
-            gl::FALSE,                                         // normalized?
-            (6 * std::mem::size_of::<f32>()) as GLsizei,       // stride (0 = tightly packed)
-            (3 * std::mem::size_of::<f32>()) as *const GLvoid, // offset of component
-        );
-
-        // Bind cleanup
-        gl.BindBuffer(gl::ARRAY_BUFFER, 0);
-        gl.BindVertexArray(0);
+    let context = Context::new(sdl, video_subsystem, window, gl_context, gl);
+    if let Err(e) = app::run::<Triangle>(context) {
+        println!("{}", e);
     }
+}
 
-    // Handle user input
-    let mut event_pump = sdl.event_pump().unwrap();
-    'render: loop {
-        for event in event_pump.poll_iter() {
-            // Handle any event
-            match event {
-                sdl2::event::Event::Quit { .. } => break 'render,
-                _ => {}
-            }
-        }
+/// Draws a single coloured triangle
+struct Triangle {
+    gl: gl::Gl,
+    shader_program: Program,
+    vbo: GLuint,
+    vao: GLuint,
+}
+
+impl App for Triangle {
+    fn init(context: &mut Context) -> Result<Self, failure::Error> {
+        let gl = &context.gl;
+
+        // Load resources
+        let res = resources::Resources::from_rel_path(Path::new("shaders"))?;
 
-        // Render window contents
+        // Create shader program from resources loaded
+        let shader_program = Program::from_resources(gl, &res, "triangle")?;
+        shader_program.set_used();
+
+        // Create a vertex array object
+        let vertices: Vec<f32> = vec![
+           // Positions        // Colors
+           -0.5, -0.5, 0.0,    1.0, 0.0, 0.0, // Bottom-left
+            0.5, -0.5, 0.0,    0.0, 1.0, 0.0, // Bottom-right
+            0.0,  0.5, 0.0,    0.0, 0.0, 1.0, // Top
+        ];
+
+        // Create bindings for shader rendering
+        let mut vbo: GLuint = 0;
+        let mut vao: GLuint = 0;
         unsafe {
-            gl.Clear(gl::COLOR_BUFFER_BIT);
+            // Create a vertex buffer object to host our vertices
+            gl.GenBuffers(1, &mut vbo);
+            gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl.BufferData(
+                gl::ARRAY_BUFFER,                      // target
+                util::sizeof(&vertices) as GLsizeiptr, // mem alloc (buffer size in bytes)
+                vertices.as_ptr() as *const GLvoid,    // void pointer to data
+                gl::STATIC_DRAW,                       // usage
+            );
+
+            // Create a vertex array object
+            gl.GenVertexArrays(1, &mut vao);
+            gl.BindVertexArray(vao);
+
+            // Setup vertex attributes
+            gl.EnableVertexAttribArray(0); // layout (position = 0) in shader
+            gl.VertexAttribPointer(
+                0,                                               // layout (position = 0) in shader
+                3,                                               // number of attributes
+                gl::FLOAT,                                       // type of data (f32)
+                gl::FALSE,                                       // normalized?
+                (6 * std::mem::size_of::<GLfloat>()) as GLsizei, // stride (0 = tightly packed)
+                std::ptr::null(),                                // offset of component
+            );
+            gl.EnableVertexAttribArray(1); // layout (position = 0) in shader
+            gl.VertexAttribPointer(
+                1,                                                 // layout (position = 0) in shader
+                3,                                                 // number of attributes
+                gl::FLOAT,                                         // type of data (f32)
+                gl::FALSE,                                         // normalized?
+                (6 * std::mem::size_of::<f32>()) as GLsizei,       // stride (0 = tightly packed)
+                (3 * std::mem::size_of::<f32>()) as *const GLvoid, // offset of component
+            );
+
+            // Bind cleanup
+            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl.BindVertexArray(0);
         }
 
+        Ok(Triangle {
+            gl: gl.clone(),
+            shader_program,
+            vbo,
+            vao,
+        })
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
         // Draw
+        self.shader_program.set_used();
         unsafe {
-            gl.BindVertexArray(vao);
-            gl.DrawArrays(
+            frame.gl.BindVertexArray(self.vao);
+            frame.gl.DrawArrays(
                 gl::TRIANGLES, // mode
                 0,             // starting index in loaded array
                 3,             // number of vertices to draw
             );
-            gl.BindVertexArray(0);
+            frame.gl.BindVertexArray(0);
         }
+    }
 
-        // Swap window pixels (redraw)
-        window.gl_swap_window();
+    fn shutdown(&mut self, context: &mut Context) {
+        unsafe {
+            self.gl.DeleteVertexArrays(1, &self.vao);
+            self.gl.DeleteBuffers(1, &self.vbo);
+        }
     }
 }
@@ -0,0 +1,437 @@
+use crate::image::ImageBuffer;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::program::{self, ActiveUniform};
+use crate::render_state::{Blend, PolygonMode, RenderState, StateCache};
+use crate::resources::{self, Resources};
+use crate::texture::{Sampler, SamplerDesc, Texture2D};
+use crate::uniform::Uniform;
+use crate::Program;
+use gl::types::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to parse material {}: {}", name, message)]
+    Parse { name: String, message: String },
+    #[fail(display = "Failed to load program for material {}: {}", name, inner)]
+    Program { name: String, inner: program::Error },
+    #[fail(display = "Failed to decode image {}: {}", name, message)]
+    Image { name: String, message: String },
+    #[fail(display = "Material {} does not match its program: {}", name, message)]
+    Validation { name: String, message: String },
+}
+
+/// Value of a material parameter
+#[derive(Clone)]
+pub enum MaterialValue {
+    Float(f32),
+    Int(i32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+    /// Texture bound to the next free unit, with an optional sampler
+    /// overriding its own filtering and wrapping
+    Texture {
+        texture: Rc<Texture2D>,
+        sampler: Option<Rc<Sampler>>,
+    },
+}
+
+impl MaterialValue {
+    /// Whether a uniform of GLSL type `kind` can hold this value
+    fn matches(&self, kind: GLenum) -> bool {
+        match self {
+            MaterialValue::Float(_) => kind == gl::FLOAT,
+            MaterialValue::Int(_) => kind == gl::INT || kind == gl::BOOL,
+            MaterialValue::Vec2(_) => kind == gl::FLOAT_VEC2,
+            MaterialValue::Vec3(_) => kind == gl::FLOAT_VEC3,
+            MaterialValue::Vec4(_) => kind == gl::FLOAT_VEC4,
+            MaterialValue::Mat4(_) => kind == gl::FLOAT_MAT4,
+            MaterialValue::Texture { texture, .. } => {
+                if texture.samples() > 0 {
+                    kind == gl::SAMPLER_2D_MULTISAMPLE
+                } else {
+                    kind == gl::SAMPLER_2D || kind == gl::SAMPLER_2D_SHADOW
+                }
+            }
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            MaterialValue::Float(_) => "float",
+            MaterialValue::Int(_) => "int",
+            MaterialValue::Vec2(_) => "vec2",
+            MaterialValue::Vec3(_) => "vec3",
+            MaterialValue::Vec4(_) => "vec4",
+            MaterialValue::Mat4(_) => "mat4",
+            MaterialValue::Texture { .. } => "sampler2D",
+        }
+    }
+}
+
+struct Param {
+    name: String,
+    location: Option<GLint>,
+    value: MaterialValue,
+}
+
+/// Program, parameters and fixed-function state used to draw a surface.
+/// Textures take texture units from 0 in the order they were set; shared
+/// renderer textures should use higher units.
+pub struct Material {
+    gl: gl::Gl,
+    pub name: String,
+    program: Rc<Program>,
+    params: Vec<Param>,
+    pub state: RenderState,
+}
+
+impl Material {
+    /// Creates an opaque material without parameters
+    pub fn new(gl: &gl::Gl, name: &str, program: Rc<Program>) -> Self {
+        Material {
+            gl: gl.clone(),
+            name: name.to_string(),
+            program,
+            params: Vec::new(),
+            state: RenderState::opaque(),
+        }
+    }
+
+    /// Loads a material description (see `MaterialLoader::load`)
+    pub fn from_resources(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Material, Error> {
+        MaterialLoader::new(gl, res).load(name)
+    }
+
+    pub fn program(&self) -> &Rc<Program> {
+        &self.program
+    }
+
+    /// Sets parameter `name`, replacing any previous value
+    pub fn set(&mut self, name: &str, value: MaterialValue) {
+        match self.params.iter_mut().find(|p| p.name == name) {
+            Some(param) => param.value = value,
+            None => self.params.push(Param {
+                name: name.to_string(),
+                location: self.program.uniform_location(name),
+                value,
+            }),
+        }
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: Rc<Texture2D>, sampler: Option<Rc<Sampler>>) {
+        self.set(name, MaterialValue::Texture { texture, sampler });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MaterialValue> {
+        self.params.iter().find(|p| p.name == name).map(|p| &p.value)
+    }
+
+    /// Checks every parameter against the active uniforms of the program.
+    /// Uniforms the material leaves unset (matrices, lights, ...) are
+    /// expected to come from the renderer and are not reported.
+    pub fn validate(&self) -> Result<(), Error> {
+        let uniforms = self.program.active_uniforms();
+        let problems: Vec<String> = self
+            .params
+            .iter()
+            .filter_map(|param| {
+                match uniforms.iter().find(|u: &&ActiveUniform| u.name == param.name) {
+                    None => Some(format!("no active uniform '{}'", param.name)),
+                    Some(u) if !param.value.matches(u.kind) => Some(format!(
+                        "'{}' is set as {} but has GL type 0x{:04X}",
+                        param.name,
+                        param.value.type_name(),
+                        u.kind
+                    )),
+                    Some(_) => None,
+                }
+            })
+            .collect();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation {
+                name: self.name.clone(),
+                message: problems.join("; "),
+            })
+        }
+    }
+
+    /// Applies the render state, uses the program and binds every parameter
+    pub fn bind(&self, cache: &mut StateCache) {
+        cache.apply(&self.state);
+        self.program.set_used();
+        self.bind_params(&self.program, |param| param.location);
+    }
+
+    /// Like `bind`, but with `program` in place of the material's own, e.g. a
+    /// G-buffer program sharing its uniform names. Parameters are looked up
+    /// by name; those `program` does not use are skipped.
+    pub fn bind_to(&self, program: &Program, cache: &mut StateCache) {
+        cache.apply(&self.state);
+        program.set_used();
+        self.bind_params(program, |param| program.uniform_location(&param.name));
+    }
+
+    fn bind_params<F>(&self, program: &Program, location_of: F)
+    where
+        F: Fn(&Param) -> Option<GLint>,
+    {
+        let program = program.id();
+        let mut unit: GLuint = 0;
+        for param in &self.params {
+            let location = match location_of(param) {
+                Some(location) => location,
+                None => continue,
+            };
+
+            match &param.value {
+                MaterialValue::Float(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Int(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Vec2(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Vec3(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Vec4(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Mat4(v) => v.set_uniform(&self.gl, program, location),
+                MaterialValue::Texture { texture, sampler } => {
+                    texture.bind(unit);
+                    match sampler {
+                        Some(sampler) => sampler.bind(unit),
+                        None => unsafe { self.gl.BindSampler(unit, 0) },
+                    }
+                    (unit as i32).set_uniform(&self.gl, program, location);
+                    unit += 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+enum StatePreset {
+    #[default]
+    Opaque,
+    Transparent,
+    Additive,
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub(crate) enum Filter {
+    Nearest,
+    Linear,
+    #[default]
+    Trilinear,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub(crate) enum Wrap {
+    #[default]
+    Repeat,
+    ClampToEdge,
+    MirroredRepeat,
+}
+
+/// Parameter value as written in RON descriptions
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) enum ParamDesc {
+    Float(f32),
+    Int(i32),
+    Vec2(f32, f32),
+    Vec3(f32, f32, f32),
+    Vec4(f32, f32, f32, f32),
+    Texture {
+        path: String,
+        #[serde(default)]
+        srgb: bool,
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default)]
+        wrap: Wrap,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MaterialDesc {
+    program: String,
+    #[serde(default)]
+    state: StatePreset,
+    #[serde(default)]
+    double_sided: bool,
+    #[serde(default)]
+    wireframe: bool,
+    #[serde(default)]
+    params: BTreeMap<String, ParamDesc>,
+}
+
+/// Loads material descriptions, sharing programs, textures and samplers
+/// between the materials it creates.
+pub struct MaterialLoader<'a> {
+    gl: gl::Gl,
+    res: &'a Resources,
+    programs: HashMap<String, Rc<Program>>,
+    textures: HashMap<(String, bool), Rc<Texture2D>>,
+    samplers: HashMap<SamplerDesc, Rc<Sampler>>,
+}
+
+impl<'a> MaterialLoader<'a> {
+    pub fn new(gl: &gl::Gl, res: &'a Resources) -> Self {
+        MaterialLoader {
+            gl: gl.clone(),
+            res,
+            programs: HashMap::new(),
+            textures: HashMap::new(),
+            samplers: HashMap::new(),
+        }
+    }
+
+    /// Loads and validates a RON material description such as
+    /// ```text
+    /// (
+    ///     program: "lit",
+    ///     state: Transparent,      // Opaque (default), Transparent, Additive, Overlay
+    ///     double_sided: true,
+    ///     params: {
+    ///         "u_tint": Vec4(1.0, 0.8, 0.8, 0.5),
+    ///         "u_albedo": Texture(path: "brick.png", srgb: true, filter: Trilinear, wrap: Repeat),
+    ///     },
+    /// )
+    /// ```
+    /// The program name is relative to the resource root, texture paths
+    /// are relative to the material file.
+    pub fn load(&mut self, name: &str) -> Result<Material, Error> {
+        let source = self
+            .res
+            .load(name)
+            .map_err(|e| Error::ResourceLoad {
+                name: name.to_string(),
+                inner: e,
+            })?;
+        let desc: MaterialDesc =
+            ron::from_str(&source.to_string_lossy()).map_err(|e| Error::Parse {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+
+        let program = self.program(&desc.program).map_err(|e| Error::Program {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        let mut material = Material::new(&self.gl, name, program);
+
+        material.state = match desc.state {
+            StatePreset::Opaque => RenderState::opaque(),
+            StatePreset::Transparent => RenderState::transparent(),
+            StatePreset::Additive => RenderState {
+                blend: Some(Blend::ADDITIVE),
+                ..RenderState::transparent()
+            },
+            StatePreset::Overlay => RenderState::overlay(),
+        };
+        if desc.double_sided {
+            material.state.cull_face = None;
+        }
+        if desc.wireframe {
+            material.state.polygon_mode = PolygonMode::Line;
+        }
+
+        for (param, value) in desc.params {
+            let value = self.value(name, value)?;
+            material.set(&param, value);
+        }
+
+        material.validate()?;
+        Ok(material)
+    }
+
+    /// Converts a parameter read from a description; texture paths are
+    /// relative to the resource `base`
+    pub(crate) fn value(&mut self, base: &str, desc: ParamDesc) -> Result<MaterialValue, Error> {
+        Ok(match desc {
+            ParamDesc::Float(x) => MaterialValue::Float(x),
+            ParamDesc::Int(x) => MaterialValue::Int(x),
+            ParamDesc::Vec2(x, y) => MaterialValue::Vec2(Vec2::new(x, y)),
+            ParamDesc::Vec3(x, y, z) => MaterialValue::Vec3(Vec3::new(x, y, z)),
+            ParamDesc::Vec4(x, y, z, w) => MaterialValue::Vec4(Vec4::new(x, y, z, w)),
+            ParamDesc::Texture {
+                path,
+                srgb,
+                filter,
+                wrap,
+            } => {
+                let texture = self.texture(&resources::sibling_name(base, &path), srgb)?;
+                let (min_filter, mag_filter) = match filter {
+                    Filter::Nearest => (gl::NEAREST, gl::NEAREST),
+                    Filter::Linear => (gl::LINEAR, gl::LINEAR),
+                    Filter::Trilinear => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+                };
+                let wrap = match wrap {
+                    Wrap::Repeat => gl::REPEAT,
+                    Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+                    Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+                };
+                let sampler = self.sampler(SamplerDesc {
+                    min_filter,
+                    mag_filter,
+                    wrap_s: wrap,
+                    wrap_t: wrap,
+                });
+                MaterialValue::Texture {
+                    texture,
+                    sampler: Some(sampler),
+                }
+            }
+        })
+    }
+
+    /// Program `name` (`name.vert` + `name.frag`), compiled once
+    pub fn program(&mut self, name: &str) -> Result<Rc<Program>, program::Error> {
+        if let Some(program) = self.programs.get(name) {
+            return Ok(program.clone());
+        }
+        let program = Rc::new(Program::from_resources(&self.gl, self.res, name)?);
+        self.programs.insert(name.to_string(), program.clone());
+        Ok(program)
+    }
+
+    /// Mipmapped texture decoded from image resource `name`, uploaded once
+    pub fn texture(&mut self, name: &str, srgb: bool) -> Result<Rc<Texture2D>, Error> {
+        let key = (name.to_string(), srgb);
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let bytes = self.res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        let mut image = ImageBuffer::decode(&bytes).map_err(|message| Error::Image {
+            name: name.to_string(),
+            message,
+        })?;
+        image.flip_vertical();
+
+        let texture = Rc::new(if srgb {
+            Texture2D::from_image_srgb(&self.gl, &image)
+        } else {
+            Texture2D::from_image(&self.gl, &image)
+        });
+        self.textures.insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    pub fn sampler(&mut self, desc: SamplerDesc) -> Rc<Sampler> {
+        let gl = &self.gl;
+        self.samplers
+            .entry(desc)
+            .or_insert_with(|| Rc::new(Sampler::new(gl, desc)))
+            .clone()
+    }
+}
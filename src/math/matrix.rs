@@ -0,0 +1,562 @@
+use super::{Quat, Vec2, Vec3, Vec4};
+use std::ops::Mul;
+
+/// 3x3 matrix stored column-major, as GLSL expects
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub cols: [Vec3; 3],
+}
+
+/// 4x4 matrix stored column-major, as GLSL expects
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub cols: [Vec4; 4],
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::IDENTITY
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::Z);
+
+    pub const fn from_cols(x: Vec3, y: Vec3, z: Vec3) -> Self {
+        Mat3 { cols: [x, y, z] }
+    }
+
+    /// The upper-left 3x3 block, i.e. the linear part of an affine transform
+    pub fn from_mat4(m: &Mat4) -> Self {
+        Mat3::from_cols(
+            m.cols[0].truncate(),
+            m.cols[1].truncate(),
+            m.cols[2].truncate(),
+        )
+    }
+
+    pub fn from_quat(q: Quat) -> Self {
+        Mat3::from_mat4(&Mat4::from_quat(q))
+    }
+
+    /// Inverse-transpose of the model matrix's linear part,
+    /// used to transform normals under non-uniform scale
+    pub fn normal_matrix(model: &Mat4) -> Self {
+        Mat3::from_mat4(model)
+            .inverse()
+            .unwrap_or(Mat3::IDENTITY)
+            .transpose()
+    }
+
+    /// Element at `row`, `col`
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.cols[col][row]
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        Vec3::new(self.cols[0][i], self.cols[1][i], self.cols[2][i])
+    }
+
+    pub fn transpose(&self) -> Self {
+        Mat3::from_cols(self.row(0), self.row(1), self.row(2))
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.cols[0].dot(self.cols[1].cross(self.cols[2]))
+    }
+
+    /// `None` when the matrix is singular or has non-finite elements
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let [a, b, c] = self.cols;
+        let r0 = b.cross(c) / det;
+        let r1 = c.cross(a) / det;
+        let r2 = a.cross(b) / det;
+        Some(Mat3::from_cols(r0, r1, r2).transpose())
+    }
+
+    pub fn to_cols_array(self) -> [f32; 9] {
+        let [x, y, z] = self.cols;
+        [x.x, x.y, x.z, y.x, y.y, y.z, z.x, z.y, z.z]
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self as *const Mat3 as *const f32
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        Mat3::from_cols(self * rhs.cols[0], self * rhs.cols[1], self * rhs.cols[2])
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.cols[0] * v.x + self.cols[1] * v.y + self.cols[2] * v.z
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4::from_cols(Vec4::X, Vec4::Y, Vec4::Z, Vec4::W);
+
+    pub const fn from_cols(x: Vec4, y: Vec4, z: Vec4, w: Vec4) -> Self {
+        Mat4 { cols: [x, y, z, w] }
+    }
+
+    /// Builds a matrix from 16 floats in column-major order (as stored by glTF)
+    pub fn from_cols_array(a: &[f32; 16]) -> Self {
+        Mat4::from_cols(
+            Vec4::new(a[0], a[1], a[2], a[3]),
+            Vec4::new(a[4], a[5], a[6], a[7]),
+            Vec4::new(a[8], a[9], a[10], a[11]),
+            Vec4::new(a[12], a[13], a[14], a[15]),
+        )
+    }
+
+    pub fn from_translation(t: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.cols[3] = t.extend(1.0);
+        m
+    }
+
+    pub fn from_scale(s: Vec3) -> Self {
+        Mat4::from_cols(Vec4::X * s.x, Vec4::Y * s.y, Vec4::Z * s.z, Vec4::W)
+    }
+
+    pub fn from_rotation_x(angle: f32) -> Self {
+        Mat4::from_quat(Quat::from_axis_angle(Vec3::X, angle))
+    }
+
+    pub fn from_rotation_y(angle: f32) -> Self {
+        Mat4::from_quat(Quat::from_axis_angle(Vec3::Y, angle))
+    }
+
+    pub fn from_rotation_z(angle: f32) -> Self {
+        Mat4::from_quat(Quat::from_axis_angle(Vec3::Z, angle))
+    }
+
+    /// Rotation matrix for a unit quaternion
+    pub fn from_quat(q: Quat) -> Self {
+        let (x2, y2, z2) = (q.x + q.x, q.y + q.y, q.z + q.z);
+        let (xx, xy, xz) = (q.x * x2, q.x * y2, q.x * z2);
+        let (yy, yz, zz) = (q.y * y2, q.y * z2, q.z * z2);
+        let (wx, wy, wz) = (q.w * x2, q.w * y2, q.w * z2);
+
+        Mat4::from_cols(
+            Vec4::new(1.0 - (yy + zz), xy + wz, xz - wy, 0.0),
+            Vec4::new(xy - wz, 1.0 - (xx + zz), yz + wx, 0.0),
+            Vec4::new(xz + wy, yz - wx, 1.0 - (xx + yy), 0.0),
+            Vec4::W,
+        )
+    }
+
+    /// `translation * rotation * scale`
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        let r = Mat4::from_quat(rotation);
+        Mat4::from_cols(
+            r.cols[0] * scale.x,
+            r.cols[1] * scale.y,
+            r.cols[2] * scale.z,
+            translation.extend(1.0),
+        )
+    }
+
+    /// Right-handed perspective projection mapping depth to `[-1, 1]`
+    /// # Arguments
+    /// * `fov_y` - Vertical field of view in radians.
+    /// * `aspect` - Viewport width divided by height.
+    /// * `near`, `far` - Distances to the clip planes, both positive.
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fov_y / 2.0).tan();
+        let range = near - far;
+
+        Mat4::from_cols(
+            Vec4::new(f / aspect, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, f, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, (far + near) / range, -1.0),
+            Vec4::new(0.0, 0.0, 2.0 * far * near / range, 0.0),
+        )
+    }
+
+    /// Right-handed orthographic projection mapping depth to `[-1, 1]`
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let w = right - left;
+        let h = top - bottom;
+        let d = far - near;
+
+        Mat4::from_cols(
+            Vec4::new(2.0 / w, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 2.0 / h, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, -2.0 / d, 0.0),
+            Vec4::new(
+                -(right + left) / w,
+                -(top + bottom) / h,
+                -(far + near) / d,
+                1.0,
+            ),
+        )
+    }
+
+    /// Right-handed view matrix for an eye at `eye` looking at `target`
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let f = (target - eye).normalize();
+        let s = f.cross(up).normalize();
+        let u = s.cross(f);
+
+        Mat4::from_cols(
+            Vec4::new(s.x, u.x, -f.x, 0.0),
+            Vec4::new(s.y, u.y, -f.y, 0.0),
+            Vec4::new(s.z, u.z, -f.z, 0.0),
+            Vec4::new(-s.dot(eye), -u.dot(eye), f.dot(eye), 1.0),
+        )
+    }
+
+    /// Element at `row`, `col`
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.cols[col][row]
+    }
+
+    pub fn row(&self, i: usize) -> Vec4 {
+        Vec4::new(
+            self.cols[0][i],
+            self.cols[1][i],
+            self.cols[2][i],
+            self.cols[3][i],
+        )
+    }
+
+    pub fn transpose(&self) -> Self {
+        Mat4::from_cols(self.row(0), self.row(1), self.row(2), self.row(3))
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = |r: usize, c: usize| self.get(r, c);
+        let s0 = m(0, 0) * m(1, 1) - m(1, 0) * m(0, 1);
+        let s1 = m(0, 0) * m(1, 2) - m(1, 0) * m(0, 2);
+        let s2 = m(0, 0) * m(1, 3) - m(1, 0) * m(0, 3);
+        let s3 = m(0, 1) * m(1, 2) - m(1, 1) * m(0, 2);
+        let s4 = m(0, 1) * m(1, 3) - m(1, 1) * m(0, 3);
+        let s5 = m(0, 2) * m(1, 3) - m(1, 2) * m(0, 3);
+        let c5 = m(2, 2) * m(3, 3) - m(3, 2) * m(2, 3);
+        let c4 = m(2, 1) * m(3, 3) - m(3, 1) * m(2, 3);
+        let c3 = m(2, 1) * m(3, 2) - m(3, 1) * m(2, 2);
+        let c2 = m(2, 0) * m(3, 3) - m(3, 0) * m(2, 3);
+        let c1 = m(2, 0) * m(3, 2) - m(3, 0) * m(2, 2);
+        let c0 = m(2, 0) * m(3, 1) - m(3, 0) * m(2, 1);
+
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    /// `None` when the matrix is singular or has non-finite elements
+    pub fn inverse(&self) -> Option<Self> {
+        let m = |r: usize, c: usize| self.get(r, c);
+        let s0 = m(0, 0) * m(1, 1) - m(1, 0) * m(0, 1);
+        let s1 = m(0, 0) * m(1, 2) - m(1, 0) * m(0, 2);
+        let s2 = m(0, 0) * m(1, 3) - m(1, 0) * m(0, 3);
+        let s3 = m(0, 1) * m(1, 2) - m(1, 1) * m(0, 2);
+        let s4 = m(0, 1) * m(1, 3) - m(1, 1) * m(0, 3);
+        let s5 = m(0, 2) * m(1, 3) - m(1, 2) * m(0, 3);
+        let c5 = m(2, 2) * m(3, 3) - m(3, 2) * m(2, 3);
+        let c4 = m(2, 1) * m(3, 3) - m(3, 1) * m(2, 3);
+        let c3 = m(2, 1) * m(3, 2) - m(3, 1) * m(2, 2);
+        let c2 = m(2, 0) * m(3, 3) - m(3, 0) * m(2, 3);
+        let c1 = m(2, 0) * m(3, 2) - m(3, 0) * m(2, 2);
+        let c0 = m(2, 0) * m(3, 1) - m(3, 0) * m(2, 1);
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let inv = 1.0 / det;
+
+        // Adjugate, built row by row and transposed into columns
+        let rows = [
+            [
+                m(1, 1) * c5 - m(1, 2) * c4 + m(1, 3) * c3,
+                -m(0, 1) * c5 + m(0, 2) * c4 - m(0, 3) * c3,
+                m(3, 1) * s5 - m(3, 2) * s4 + m(3, 3) * s3,
+                -m(2, 1) * s5 + m(2, 2) * s4 - m(2, 3) * s3,
+            ],
+            [
+                -m(1, 0) * c5 + m(1, 2) * c2 - m(1, 3) * c1,
+                m(0, 0) * c5 - m(0, 2) * c2 + m(0, 3) * c1,
+                -m(3, 0) * s5 + m(3, 2) * s2 - m(3, 3) * s1,
+                m(2, 0) * s5 - m(2, 2) * s2 + m(2, 3) * s1,
+            ],
+            [
+                m(1, 0) * c4 - m(1, 1) * c2 + m(1, 3) * c0,
+                -m(0, 0) * c4 + m(0, 1) * c2 - m(0, 3) * c0,
+                m(3, 0) * s4 - m(3, 1) * s2 + m(3, 3) * s0,
+                -m(2, 0) * s4 + m(2, 1) * s2 - m(2, 3) * s0,
+            ],
+            [
+                -m(1, 0) * c3 + m(1, 1) * c1 - m(1, 2) * c0,
+                m(0, 0) * c3 - m(0, 1) * c1 + m(0, 2) * c0,
+                -m(3, 0) * s3 + m(3, 1) * s1 - m(3, 2) * s0,
+                m(2, 0) * s3 - m(2, 1) * s1 + m(2, 2) * s0,
+            ],
+        ];
+
+        let row = |r: usize| Vec4::from(rows[r]) * inv;
+        Some(Mat4::from_cols(row(0), row(1), row(2), row(3)).transpose())
+    }
+
+    /// Transforms a point, applying translation and the perspective divide
+    pub fn transform_point3(&self, p: Vec3) -> Vec3 {
+        let v = *self * p.extend(1.0);
+        if v.w != 0.0 && v.w != 1.0 {
+            v.truncate() / v.w
+        } else {
+            v.truncate()
+        }
+    }
+
+    /// Transforms a direction, ignoring translation
+    pub fn transform_vector3(&self, v: Vec3) -> Vec3 {
+        (*self * v.extend(0.0)).truncate()
+    }
+
+    /// The translation part of an affine transform
+    pub fn translation(&self) -> Vec3 {
+        self.cols[3].truncate()
+    }
+
+    pub fn to_cols_array(self) -> [f32; 16] {
+        let mut out = [0.0; 16];
+        for (i, col) in self.cols.iter().enumerate() {
+            out[i * 4..i * 4 + 4].copy_from_slice(&col.to_array());
+        }
+        out
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self as *const Mat4 as *const f32
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        Mat4::from_cols(
+            self * rhs.cols[0],
+            self * rhs.cols[1],
+            self * rhs.cols[2],
+            self * rhs.cols[3],
+        )
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        self.cols[0] * v.x + self.cols[1] * v.y + self.cols[2] * v.z + self.cols[3] * v.w
+    }
+}
+
+impl From<Mat3> for Mat4 {
+    fn from(m: Mat3) -> Mat4 {
+        Mat4::from_cols(
+            m.cols[0].extend(0.0),
+            m.cols[1].extend(0.0),
+            m.cols[2].extend(0.0),
+            Vec4::W,
+        )
+    }
+}
+
+/// 2D affine transform helper, returned as a `Mat3` in homogeneous form
+pub fn affine2(translation: Vec2, angle: f32, scale: Vec2) -> Mat3 {
+    let (s, c) = angle.sin_cos();
+    Mat3::from_cols(
+        Vec3::new(c * scale.x, s * scale.x, 0.0),
+        Vec3::new(-s * scale.y, c * scale.y, 0.0),
+        translation.extend(1.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{approx_eq, radians};
+
+    fn assert_cols_eq(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(approx_eq(*a, *e, 1e-5), "element {}: {} != {}", i, a, e);
+        }
+    }
+
+    fn assert_vec3_eq(actual: Vec3, expected: Vec3) {
+        assert_cols_eq(&actual.to_array(), &expected.to_array());
+    }
+
+    #[test]
+    fn to_cols_array_is_column_major() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        #[rustfmt::skip]
+        let expected = [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            1.0, 2.0, 3.0, 1.0,
+        ];
+        assert_eq!(m.to_cols_array(), expected);
+        assert_eq!(m.get(0, 3), 1.0);
+        assert_eq!(Mat4::from_cols_array(&m.to_cols_array()), m);
+
+        let m = Mat3::from_cols(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(4.0, 5.0, 6.0),
+            Vec3::new(7.0, 8.0, 9.0),
+        );
+        assert_eq!(
+            m.to_cols_array(),
+            [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        );
+        assert_eq!(m.get(0, 1), 4.0);
+    }
+
+    #[test]
+    fn perspective_matches_gl_reference() {
+        // glm::perspective(radians(90), 2, 1, 10)
+        let m = Mat4::perspective(radians(90.0), 2.0, 1.0, 10.0);
+        #[rustfmt::skip]
+        let expected = [
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, -11.0 / 9.0, -1.0,
+            0.0, 0.0, -20.0 / 9.0, 0.0,
+        ];
+        assert_cols_eq(&m.to_cols_array(), &expected);
+
+        // The near and far planes land on the ends of the NDC depth range
+        assert!(approx_eq(
+            m.transform_point3(Vec3::new(0.0, 0.0, -1.0)).z,
+            -1.0,
+            1e-5
+        ));
+        assert!(approx_eq(
+            m.transform_point3(Vec3::new(0.0, 0.0, -10.0)).z,
+            1.0,
+            1e-5
+        ));
+    }
+
+    #[test]
+    fn orthographic_matches_gl_reference() {
+        // glOrtho(-2, 2, -1, 1, 0.5, 10.5)
+        let m = Mat4::orthographic(-2.0, 2.0, -1.0, 1.0, 0.5, 10.5);
+        #[rustfmt::skip]
+        let expected = [
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, -0.2, 0.0,
+            0.0, 0.0, -1.1, 1.0,
+        ];
+        assert_cols_eq(&m.to_cols_array(), &expected);
+
+        let m = Mat4::orthographic(0.0, 800.0, 600.0, 0.0, -1.0, 1.0);
+        assert_vec3_eq(
+            m.transform_point3(Vec3::new(0.0, 0.0, 0.0)),
+            Vec3::new(-1.0, 1.0, 0.0),
+        );
+        assert_vec3_eq(
+            m.transform_point3(Vec3::new(800.0, 600.0, 0.0)),
+            Vec3::new(1.0, -1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn look_at_matches_gl_reference() {
+        // gluLookAt(0, 0, 5, 0, 0, 0, 0, 1, 0) is a plain translation
+        let m = Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        assert_cols_eq(
+            &m.to_cols_array(),
+            &Mat4::from_translation(Vec3::new(0.0, 0.0, -5.0)).to_cols_array(),
+        );
+
+        // gluLookAt(1, 0, 0, 0, 0, 0, 0, 1, 0) turns to look down -X
+        let m = Mat4::look_at(Vec3::X, Vec3::ZERO, Vec3::Y);
+        #[rustfmt::skip]
+        let expected = [
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            -1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, -1.0, 1.0,
+        ];
+        assert_cols_eq(&m.to_cols_array(), &expected);
+
+        let eye = Vec3::new(3.0, 4.0, 12.0);
+        let target = Vec3::new(-1.0, 0.5, 2.0);
+        let m = Mat4::look_at(eye, target, Vec3::Y);
+        assert_vec3_eq(m.transform_point3(eye), Vec3::ZERO);
+        let distance = (target - eye).length();
+        assert_vec3_eq(m.transform_point3(target), Vec3::new(0.0, 0.0, -distance));
+    }
+
+    #[test]
+    fn inverse_times_matrix_is_identity() {
+        let matrices = [
+            Mat4::from_trs(
+                Vec3::new(1.0, -2.0, 3.0),
+                Quat::from_euler(0.3, -1.1, 2.0),
+                Vec3::new(2.0, 0.5, 3.0),
+            ),
+            Mat4::perspective(radians(60.0), 1.5, 0.1, 100.0),
+            Mat4::look_at(Vec3::new(3.0, 4.0, 12.0), Vec3::ZERO, Vec3::Y),
+            // Tiny but perfectly invertible scale
+            Mat4::from_scale(Vec3::splat(1e-3)),
+        ];
+        for m in matrices {
+            let inverse = m.inverse().expect("matrix should be invertible");
+            assert_cols_eq(
+                &(m * inverse).to_cols_array(),
+                &Mat4::IDENTITY.to_cols_array(),
+            );
+            assert_cols_eq(
+                &(inverse * m).to_cols_array(),
+                &Mat4::IDENTITY.to_cols_array(),
+            );
+
+            let m3 = Mat3::from_mat4(&m);
+            let inverse = m3.inverse().expect("matrix should be invertible");
+            assert_cols_eq(
+                &(m3 * inverse).to_cols_array(),
+                &Mat3::IDENTITY.to_cols_array(),
+            );
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert_eq!(Mat3::from_cols(Vec3::X, Vec3::X, Vec3::Z).inverse(), None);
+        assert_eq!(Mat4::from_scale(Vec3::splat(f32::NAN)).inverse(), None);
+    }
+
+    #[test]
+    fn normal_matrix_undoes_non_uniform_scale() {
+        let model = Mat4::from_scale(Vec3::new(2.0, 1.0, 1.0));
+        let n = Mat3::normal_matrix(&model);
+        assert_vec3_eq(n * Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.5, 1.0, 0.0));
+    }
+}
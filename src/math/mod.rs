@@ -0,0 +1,17 @@
+mod matrix;
+mod quaternion;
+mod vector;
+
+pub use matrix::{affine2, Mat3, Mat4};
+pub use quaternion::Quat;
+pub use vector::{Vec2, Vec3, Vec4};
+
+/// Degrees to radians
+pub fn radians(degrees: f32) -> f32 {
+    degrees.to_radians()
+}
+
+/// Approximate float comparison with an absolute tolerance
+pub fn approx_eq(a: f32, b: f32, epsilon: f32) -> bool {
+    (a - b).abs() <= epsilon
+}
@@ -0,0 +1,279 @@
+use super::{Mat3, Vec3, Vec4};
+use std::ops::{Mul, MulAssign, Neg};
+
+/// Rotation quaternion, `w` being the scalar part
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat::from_xyzw(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    /// Rotation of `angle` radians around the unit vector `axis`
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let (s, c) = (angle * 0.5).sin_cos();
+        let v = axis * s;
+        Quat::from_xyzw(v.x, v.y, v.z, c)
+    }
+
+    /// Rotation applying `yaw` (around Y), then `pitch` (around X), then `roll` (around Z)
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Self {
+        Quat::from_axis_angle(Vec3::Y, yaw)
+            * Quat::from_axis_angle(Vec3::X, pitch)
+            * Quat::from_axis_angle(Vec3::Z, roll)
+    }
+
+    /// Shortest rotation taking unit vector `from` to unit vector `to`
+    pub fn from_rotation_arc(from: Vec3, to: Vec3) -> Self {
+        let d = from.dot(to);
+        if d < -1.0 + 1e-6 {
+            // Opposite vectors: rotate half a turn around any perpendicular axis
+            let mut axis = Vec3::X.cross(from);
+            if axis.length_squared() < 1e-6 {
+                axis = Vec3::Y.cross(from);
+            }
+            return Quat::from_axis_angle(axis.normalize(), std::f32::consts::PI);
+        }
+
+        let c = from.cross(to);
+        Quat::from_xyzw(c.x, c.y, c.z, 1.0 + d).normalize()
+    }
+
+    /// Rotation part of an orthonormal matrix
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let (m00, m11, m22) = (m.get(0, 0), m.get(1, 1), m.get(2, 2));
+        let trace = m00 + m11 + m22;
+
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::from_xyzw(
+                (m.get(2, 1) - m.get(1, 2)) / s,
+                (m.get(0, 2) - m.get(2, 0)) / s,
+                (m.get(1, 0) - m.get(0, 1)) / s,
+                0.25 * s,
+            )
+        } else if m00 > m11 && m00 > m22 {
+            let s = (1.0 + m00 - m11 - m22).sqrt() * 2.0;
+            Quat::from_xyzw(
+                0.25 * s,
+                (m.get(0, 1) + m.get(1, 0)) / s,
+                (m.get(0, 2) + m.get(2, 0)) / s,
+                (m.get(2, 1) - m.get(1, 2)) / s,
+            )
+        } else if m11 > m22 {
+            let s = (1.0 + m11 - m00 - m22).sqrt() * 2.0;
+            Quat::from_xyzw(
+                (m.get(0, 1) + m.get(1, 0)) / s,
+                0.25 * s,
+                (m.get(1, 2) + m.get(2, 1)) / s,
+                (m.get(0, 2) - m.get(2, 0)) / s,
+            )
+        } else {
+            let s = (1.0 + m22 - m00 - m11).sqrt() * 2.0;
+            Quat::from_xyzw(
+                (m.get(0, 2) + m.get(2, 0)) / s,
+                (m.get(1, 2) + m.get(2, 1)) / s,
+                0.25 * s,
+                (m.get(1, 0) - m.get(0, 1)) / s,
+            )
+        }
+    }
+
+    pub fn dot(self, rhs: Quat) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        Quat::from_xyzw(self.x / len, self.y / len, self.z / len, self.w / len)
+    }
+
+    pub fn conjugate(self) -> Self {
+        Quat::from_xyzw(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn inverse(self) -> Self {
+        let n = self.dot(self);
+        let c = self.conjugate();
+        Quat::from_xyzw(c.x / n, c.y / n, c.z / n, c.w / n)
+    }
+
+    /// Spherical interpolation along the shortest arc
+    pub fn slerp(self, mut rhs: Quat, t: f32) -> Self {
+        let mut d = self.dot(rhs);
+        if d < 0.0 {
+            rhs = -rhs;
+            d = -d;
+        }
+
+        // Nearly parallel: fall back to normalized lerp to avoid dividing by ~0
+        if d > 0.9995 {
+            let a = Vec4::from(self.to_array());
+            let b = Vec4::from(rhs.to_array());
+            let v = a.lerp(b, t);
+            return Quat::from_xyzw(v.x, v.y, v.z, v.w).normalize();
+        }
+
+        let theta = d.acos();
+        let sin_theta = theta.sin();
+        let wa = ((1.0 - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+
+        Quat::from_xyzw(
+            self.x * wa + rhs.x * wb,
+            self.y * wa + rhs.y * wb,
+            self.z * wa + rhs.z * wb,
+            self.w * wa + rhs.w * wb,
+        )
+    }
+
+    /// Rotates `v` by this unit quaternion
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+}
+
+impl From<[f32; 4]> for Quat {
+    fn from(a: [f32; 4]) -> Self {
+        Quat::from_xyzw(a[0], a[1], a[2], a[3])
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// Hamilton product: `a * b` applies `b` first, then `a`
+    fn mul(self, b: Quat) -> Quat {
+        let a = self;
+        Quat::from_xyzw(
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, rhs: Quat) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.rotate(v)
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat::from_xyzw(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{approx_eq, radians, Mat4};
+
+    /// `q` and `-q` are the same rotation
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        assert!(approx_eq(a.dot(b).abs(), 1.0, 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let rotations = [
+            Quat::IDENTITY,
+            Quat::from_axis_angle(Vec3::X, radians(90.0)),
+            Quat::from_axis_angle(Vec3::Y, radians(180.0)),
+            Quat::from_axis_angle(Vec3::new(1.0, 1.0, 1.0).normalize(), radians(-120.0)),
+            Quat::from_euler(0.3, -1.1, 2.9),
+        ];
+        for q in rotations {
+            let m = Mat3::from_quat(q);
+            assert_same_rotation(Quat::from_mat3(&m), q);
+
+            let v = Vec3::new(0.5, -2.0, 3.0);
+            assert_vec3_eq(m * v, q.rotate(v));
+            assert_vec3_eq(Mat4::from_quat(q).transform_vector3(v), q * v);
+        }
+    }
+
+    #[test]
+    fn axis_angle_matches_rotation_matrices() {
+        let angle = radians(30.0);
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_vec3_eq(
+            Quat::from_axis_angle(Vec3::Z, angle).rotate(v),
+            Vec3::new(
+                v.x * angle.cos() - v.y * angle.sin(),
+                v.x * angle.sin() + v.y * angle.cos(),
+                v.z,
+            ),
+        );
+        assert_vec3_eq(
+            Quat::from_axis_angle(Vec3::Y, radians(90.0)).rotate(Vec3::X),
+            -Vec3::Z,
+        );
+    }
+
+    #[test]
+    fn slerp_endpoints_and_midpoint() {
+        let a = Quat::from_axis_angle(Vec3::Y, radians(10.0));
+        let b = Quat::from_axis_angle(Vec3::Y, radians(130.0));
+
+        assert_same_rotation(a.slerp(b, 0.0), a);
+        assert_same_rotation(a.slerp(b, 1.0), b);
+        assert_same_rotation(
+            a.slerp(b, 0.5),
+            Quat::from_axis_angle(Vec3::Y, radians(70.0)),
+        );
+
+        // Takes the short way round even when the quaternions are in opposite hemispheres
+        assert_same_rotation(a.slerp(-b, 1.0), b);
+        assert_same_rotation(
+            a.slerp(-b, 0.5),
+            Quat::from_axis_angle(Vec3::Y, radians(70.0)),
+        );
+
+        // Nearly parallel inputs use the normalized lerp fallback
+        let c = Quat::from_axis_angle(Vec3::Y, radians(10.5));
+        assert_same_rotation(a.slerp(c, 1.0), c);
+        assert!(approx_eq(a.slerp(c, 0.5).length(), 1.0, 1e-6));
+    }
+}
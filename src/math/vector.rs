@@ -0,0 +1,263 @@
+use crate::color::Color;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
+
+/// Implements the component-wise operators and common helpers
+/// shared by every vector type
+macro_rules! impl_vector {
+    ($name:ident, $n:expr, $($field:ident),+) => {
+        impl $name {
+            pub const ZERO: $name = $name { $($field: 0.0),+ };
+            pub const ONE: $name = $name { $($field: 1.0),+ };
+
+            pub const fn new($($field: f32),+) -> Self {
+                $name { $($field),+ }
+            }
+
+            /// Vector with every component set to `v`
+            pub const fn splat(v: f32) -> Self {
+                $name { $($field: v),+ }
+            }
+
+            pub fn dot(self, rhs: $name) -> f32 {
+                0.0 $(+ self.$field * rhs.$field)+
+            }
+
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> f32 {
+                self.length_squared().sqrt()
+            }
+
+            pub fn distance(self, rhs: $name) -> f32 {
+                (self - rhs).length()
+            }
+
+            /// Unit vector in the same direction, or zero for a zero vector
+            pub fn normalize(self) -> Self {
+                let len = self.length();
+                if len > 0.0 {
+                    self / len
+                } else {
+                    $name::ZERO
+                }
+            }
+
+            pub fn lerp(self, rhs: $name, t: f32) -> Self {
+                self + (rhs - self) * t
+            }
+
+            pub fn min(self, rhs: $name) -> Self {
+                $name { $($field: self.$field.min(rhs.$field)),+ }
+            }
+
+            pub fn max(self, rhs: $name) -> Self {
+                $name { $($field: self.$field.max(rhs.$field)),+ }
+            }
+
+            pub fn abs(self) -> Self {
+                $name { $($field: self.$field.abs()),+ }
+            }
+
+            pub fn to_array(self) -> [f32; $n] {
+                [$(self.$field),+]
+            }
+
+            pub fn as_ptr(&self) -> *const f32 {
+                self as *const $name as *const f32
+            }
+        }
+
+        impl From<[f32; $n]> for $name {
+            fn from(a: [f32; $n]) -> Self {
+                let [$($field),+] = a;
+                $name { $($field),+ }
+            }
+        }
+
+        impl From<$name> for [f32; $n] {
+            fn from(v: $name) -> Self {
+                v.to_array()
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = f32;
+
+            fn index(&self, i: usize) -> &f32 {
+                [$(&self.$field),+][i]
+            }
+        }
+
+        impl IndexMut<usize> for $name {
+            fn index_mut(&mut self, i: usize) -> &mut f32 {
+                [$(&mut self.$field),+].into_iter().nth(i).expect("vector index out of range")
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, rhs: $name) -> $name {
+                $name { $($field: self.$field + rhs.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, rhs: $name) -> $name {
+                $name { $($field: self.$field - rhs.$field),+ }
+            }
+        }
+
+        impl Mul for $name {
+            type Output = $name;
+            fn mul(self, rhs: $name) -> $name {
+                $name { $($field: self.$field * rhs.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+            fn mul(self, rhs: f32) -> $name {
+                $name { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+            fn mul(self, rhs: $name) -> $name {
+                rhs * self
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+            fn div(self, rhs: f32) -> $name {
+                $name { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: $name) {
+                $(self.$field += rhs.$field;)+
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, rhs: $name) {
+                $(self.$field -= rhs.$field;)+
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, rhs: f32) {
+                $(self.$field *= rhs;)+
+            }
+        }
+
+        impl DivAssign<f32> for $name {
+            fn div_assign(&mut self, rhs: f32) {
+                $(self.$field /= rhs;)+
+            }
+        }
+    };
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl_vector!(Vec2, 2, x, y);
+impl_vector!(Vec3, 3, x, y, z);
+impl_vector!(Vec4, 4, x, y, z, w);
+
+impl Vec2 {
+    pub const X: Vec2 = Vec2::new(1.0, 0.0);
+    pub const Y: Vec2 = Vec2::new(0.0, 1.0);
+
+    /// The z component of the 3D cross product
+    pub fn perp_dot(self, rhs: Vec2) -> f32 {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    pub fn extend(self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+}
+
+impl Vec3 {
+    pub const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    pub const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    pub const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    pub fn cross(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    pub fn truncate(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+impl Vec4 {
+    pub const X: Vec4 = Vec4::new(1.0, 0.0, 0.0, 0.0);
+    pub const Y: Vec4 = Vec4::new(0.0, 1.0, 0.0, 0.0);
+    pub const Z: Vec4 = Vec4::new(0.0, 0.0, 1.0, 0.0);
+    pub const W: Vec4 = Vec4::new(0.0, 0.0, 0.0, 1.0);
+
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+/// Linear components of the colour
+impl From<Color> for Vec4 {
+    fn from(color: Color) -> Self {
+        Vec4::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+/// Linear colour from `x, y, z, w` as `r, g, b, a`
+impl From<Vec4> for Color {
+    fn from(v: Vec4) -> Self {
+        Color::rgba(v.x, v.y, v.z, v.w)
+    }
+}
@@ -0,0 +1,253 @@
+use crate::buffer::{ArrayBuffer, ElementArrayBuffer, VertexArray};
+use crate::math::{Vec2, Vec3, Vec4};
+use gl::types::*;
+
+/// Interleaved vertex layout shared by every loaded mesh
+/// # Attributes
+/// * `layout (location = 0)` - position
+/// * `layout (location = 1)` - normal
+/// * `layout (location = 2)` - texture coordinates
+/// * `layout (location = 3)` - tangent, `w` giving the bitangent sign
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    pub tangent: Vec4,
+}
+
+impl Vertex {
+    /// Describes the fields to the currently bound VAO and array buffer
+    pub fn vertex_attrib_pointers(gl: &gl::Gl) {
+        let stride = std::mem::size_of::<Vertex>();
+        let attributes: [(GLuint, GLint, usize); 4] = [
+            (0, 3, std::mem::offset_of!(Vertex, position)),
+            (1, 3, std::mem::offset_of!(Vertex, normal)),
+            (2, 2, std::mem::offset_of!(Vertex, uv)),
+            (3, 4, std::mem::offset_of!(Vertex, tangent)),
+        ];
+
+        for (location, size, offset) in attributes {
+            unsafe {
+                gl.EnableVertexAttribArray(location);
+                gl.VertexAttribPointer(
+                    location,                // layout (location = N) in shader
+                    size,                    // number of components
+                    gl::FLOAT,               // type of data (f32)
+                    gl::FALSE,               // normalized?
+                    stride as GLsizei,       // stride between vertices
+                    offset as *const GLvoid, // offset of component
+                );
+            }
+        }
+    }
+}
+
+/// Range of indices drawn with a single material
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubMesh {
+    pub name: String,
+    /// Index into the material list the mesh was loaded with
+    pub material: Option<usize>,
+    /// First index in `MeshData::indices`
+    pub start: usize,
+    pub count: usize,
+}
+
+/// Indexed triangle mesh on the CPU
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<SubMesh>,
+}
+
+impl MeshData {
+    /// Sphere centered at the origin with `segments` slices around Y and
+    /// `rings` stacks from pole to pole, with normals, UVs and tangents
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let mut mesh = MeshData::default();
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let theta = v * std::f32::consts::PI;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let phi = u * std::f32::consts::TAU;
+                let normal = Vec3::new(phi.cos() * theta.sin(), theta.cos(), -phi.sin() * theta.sin());
+                mesh.vertices.push(Vertex {
+                    position: normal * radius,
+                    normal,
+                    uv: Vec2::new(u, 1.0 - v),
+                    tangent: Vec4::new(-phi.sin(), 0.0, -phi.cos(), 1.0),
+                });
+            }
+        }
+
+        let stride = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * stride + segment;
+                let b = a + stride;
+                mesh.indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+
+        mesh.submeshes.push(SubMesh {
+            name: String::from("sphere"),
+            material: None,
+            start: 0,
+            count: mesh.indices.len(),
+        });
+        mesh
+    }
+
+    /// Axis-aligned bounds of the vertex positions
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold((first, first), |(min, max), v| {
+            (min.min(v.position), max.max(v.position))
+        }))
+    }
+
+    /// Replaces all normals with area-weighted averages of the face normals
+    pub fn compute_normals(&mut self) {
+        for v in &mut self.vertices {
+            v.normal = Vec3::ZERO;
+        }
+
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let (pa, pb, pc) = (
+                self.vertices[a].position,
+                self.vertices[b].position,
+                self.vertices[c].position,
+            );
+            let n = (pb - pa).cross(pc - pa);
+            self.vertices[a].normal += n;
+            self.vertices[b].normal += n;
+            self.vertices[c].normal += n;
+        }
+
+        for v in &mut self.vertices {
+            v.normal = v.normal.normalize();
+        }
+    }
+
+    /// Replaces all tangents with per-vertex averages of the triangle
+    /// tangents derived from the UVs, orthogonalized against the normals.
+    /// Vertices without usable UVs get an arbitrary tangent.
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let (va, vb, vc) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+            let (e1, e2) = (vb.position - va.position, vc.position - va.position);
+            let (d1, d2) = (vb.uv - va.uv, vc.uv - va.uv);
+
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = (e1 * d2.y - e2 * d1.y) * r;
+            let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+
+            for i in [a, b, c] {
+                tangents[i] += tangent;
+                bitangents[i] += bitangent;
+            }
+        }
+
+        for (i, v) in self.vertices.iter_mut().enumerate() {
+            let n = v.normal;
+            let mut t = tangents[i] - n * n.dot(tangents[i]);
+            if t.length_squared() < 1e-12 {
+                // Any vector perpendicular to the normal
+                let axis = if n.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+                t = n.cross(axis);
+            }
+            let t = t.normalize();
+            let sign = if n.cross(t).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            v.tangent = t.extend(sign);
+        }
+    }
+}
+
+/// Mesh uploaded to the GPU as a VAO with vertex and index buffers
+pub struct Mesh {
+    gl: gl::Gl,
+    vao: VertexArray,
+    _vbo: ArrayBuffer,
+    _ebo: ElementArrayBuffer,
+    index_count: usize,
+    submeshes: Vec<SubMesh>,
+}
+
+impl Mesh {
+    pub fn new(gl: &gl::Gl, data: &MeshData) -> Self {
+        let vbo = ArrayBuffer::new(gl);
+        vbo.bind();
+        vbo.static_draw_data(&data.vertices);
+
+        let vao = VertexArray::new(gl);
+        vao.bind();
+        Vertex::vertex_attrib_pointers(gl);
+
+        // The element buffer binding is recorded in the VAO
+        let ebo = ElementArrayBuffer::new(gl);
+        ebo.bind();
+        ebo.static_draw_data(&data.indices);
+
+        vao.unbind();
+        vbo.unbind();
+        ebo.unbind();
+
+        Mesh {
+            gl: gl.clone(),
+            vao,
+            _vbo: vbo,
+            _ebo: ebo,
+            index_count: data.indices.len(),
+            submeshes: data.submeshes.clone(),
+        }
+    }
+
+    pub fn submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.index_count
+    }
+
+    /// Draws every triangle of the mesh
+    pub fn draw(&self) {
+        self.draw_range(0, self.index_count);
+    }
+
+    /// Draws the triangles of submesh `index`
+    pub fn draw_submesh(&self, index: usize) {
+        let submesh = &self.submeshes[index];
+        self.draw_range(submesh.start, submesh.count);
+    }
+
+    fn draw_range(&self, start: usize, count: usize) {
+        self.vao.bind();
+        unsafe {
+            self.gl.DrawElements(
+                gl::TRIANGLES,
+                count as GLsizei,
+                gl::UNSIGNED_INT,
+                (start * std::mem::size_of::<u32>()) as *const GLvoid,
+            );
+        }
+        self.vao.unbind();
+    }
+}
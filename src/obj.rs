@@ -0,0 +1,363 @@
+use crate::math::{Vec2, Vec3, Vec4};
+use crate::mesh::{MeshData, SubMesh, Vertex};
+use crate::resources::{self, Resources};
+use std::collections::HashMap;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad {
+        name: String,
+        inner: resources::Error,
+    },
+    #[fail(display = "{}:{}: {}", name, line, message)]
+    Parse {
+        name: String,
+        line: usize,
+        message: String,
+    },
+}
+
+/// Material parsed from a `.mtl` library
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub emissive: Vec3,
+    pub shininess: f32,
+    /// Opacity, 1.0 being fully opaque
+    pub dissolve: f32,
+    /// Texture resource names, relative to the resource root
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub normal_map: Option<String>,
+    pub alpha_map: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: Vec3::ZERO,
+            diffuse: Vec3::splat(0.8),
+            specular: Vec3::ZERO,
+            emissive: Vec3::ZERO,
+            shininess: 0.0,
+            dissolve: 1.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            alpha_map: None,
+        }
+    }
+}
+
+/// Indexed mesh and the materials its submeshes refer to
+#[derive(Debug, Clone, Default)]
+pub struct ObjModel {
+    pub mesh: MeshData,
+    pub materials: Vec<ObjMaterial>,
+}
+
+/// Loads `name` and the material libraries it references from the resources
+pub fn load(res: &Resources, name: &str) -> Result<ObjModel, Error> {
+    let source = load_text(res, name)?;
+    let mut materials = Vec::new();
+    let (mut mesh, material_names) = parse_obj(name, &source, |library| {
+        let library = resources::sibling_name(name, library);
+        let source = load_text(res, &library)?;
+        materials.extend(parse_mtl(&library, &source)?);
+        Ok(())
+    })?;
+
+    // Resolve material names now that every library is loaded
+    for submesh in &mut mesh.submeshes {
+        submesh.material = submesh
+            .material
+            .and_then(|i| materials.iter().position(|m| m.name == material_names[i]));
+    }
+
+    Ok(ObjModel { mesh, materials })
+}
+
+fn load_text(res: &Resources, name: &str) -> Result<String, Error> {
+    res.load(name)
+        .map(|s| s.to_string_lossy().into_owned())
+        .map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })
+}
+
+struct LineParser<'a> {
+    name: &'a str,
+    line: usize,
+}
+
+impl LineParser<'_> {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Parse {
+            name: self.name.to_string(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn floats<const N: usize>(&self, args: &[&str], keyword: &str) -> Result<[f32; N], Error> {
+        if args.len() < N {
+            return Err(self.error(format!(
+                "'{}' expects {} numbers, found {}",
+                keyword,
+                N,
+                args.len()
+            )));
+        }
+
+        let mut out = [0.0; N];
+        for (value, arg) in out.iter_mut().zip(args) {
+            *value = arg
+                .parse()
+                .map_err(|_| self.error(format!("invalid number '{}' in '{}'", arg, keyword)))?;
+        }
+        Ok(out)
+    }
+
+    /// Resolves a 1-based (or negative, relative) OBJ index against `len` elements
+    fn index(&self, token: &str, len: usize, kind: &str) -> Result<usize, Error> {
+        let value: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index '{}'", kind, token)))?;
+
+        let resolved = if value < 0 {
+            len as i64 + value
+        } else {
+            value - 1
+        };
+
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!(
+                "{} index {} out of range (have {})",
+                kind, value, len
+            )));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+/// Parses OBJ source into an indexed mesh.
+/// Faces are fan-triangulated and vertices sharing the same
+/// position/uv/normal triple are merged. `mtllib` statements are passed to
+/// `load_library`.
+/// # Returns
+/// The mesh and the `usemtl` names in order of first appearance,
+/// which the submesh `material` fields index.
+pub fn parse_obj<F>(
+    name: &str,
+    source: &str,
+    mut load_library: F,
+) -> Result<(MeshData, Vec<String>), Error>
+where
+    F: FnMut(&str) -> Result<(), Error>,
+{
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+
+    let mut mesh = MeshData::default();
+    let mut unique: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut material_names: Vec<String> = Vec::new();
+    let mut missing_normals = false;
+
+    let mut group = String::from("default");
+    let mut material: Option<usize> = None;
+    let mut current = SubMesh {
+        name: group.clone(),
+        material: None,
+        start: 0,
+        count: 0,
+    };
+
+    for (number, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            name,
+            line: number + 1,
+        };
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => positions.push(Vec3::from(parser.floats::<3>(&args, "v")?)),
+            "vn" => normals.push(Vec3::from(parser.floats::<3>(&args, "vn")?)),
+            "vt" => uvs.push(Vec2::from(parser.floats::<2>(&args, "vt")?)),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parser.error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        args.len()
+                    )));
+                }
+
+                let mut face: Vec<u32> = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let position =
+                        parser.index(parts.next().unwrap_or(""), positions.len(), "position")?;
+                    let uv = match parts.next() {
+                        Some("") | None => None,
+                        Some(token) => Some(parser.index(token, uvs.len(), "texture")?),
+                    };
+                    let normal = match parts.next() {
+                        Some("") | None => None,
+                        Some(token) => Some(parser.index(token, normals.len(), "normal")?),
+                    };
+                    if parts.next().is_some() {
+                        return Err(parser.error(format!("malformed face vertex '{}'", arg)));
+                    }
+                    missing_normals |= normal.is_none();
+
+                    let index = *unique.entry((position, uv, normal)).or_insert_with(|| {
+                        mesh.vertices.push(Vertex {
+                            position: positions[position],
+                            normal: normal.map_or(Vec3::ZERO, |n| normals[n]),
+                            uv: uv.map_or(Vec2::ZERO, |t| uvs[t]),
+                            tangent: Vec4::ZERO,
+                        });
+                        (mesh.vertices.len() - 1) as u32
+                    });
+                    face.push(index);
+                }
+
+                for i in 1..face.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            "g" | "o" | "usemtl" => {
+                if keyword == "usemtl" {
+                    let name = args.join(" ");
+                    if name.is_empty() {
+                        return Err(parser.error("'usemtl' needs a material name"));
+                    }
+                    material = Some(match material_names.iter().position(|n| *n == name) {
+                        Some(i) => i,
+                        None => {
+                            material_names.push(name);
+                            material_names.len() - 1
+                        }
+                    });
+                } else {
+                    group = if args.is_empty() {
+                        String::from("default")
+                    } else {
+                        args.join(" ")
+                    };
+                }
+
+                let start = mesh.indices.len();
+                current.count = start - current.start;
+                let finished = std::mem::replace(
+                    &mut current,
+                    SubMesh {
+                        name: group.clone(),
+                        material,
+                        start,
+                        count: 0,
+                    },
+                );
+                if finished.count > 0 {
+                    mesh.submeshes.push(finished);
+                }
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(parser.error("'mtllib' needs a file name"));
+                }
+                load_library(&args.join(" "))?;
+            }
+            // Smoothing groups, curves and other statements are not used for rendering
+            _ => {}
+        }
+    }
+
+    current.count = mesh.indices.len() - current.start;
+    if current.count > 0 {
+        mesh.submeshes.push(current);
+    }
+
+    // Smooth normals for the vertices the file gave none
+    if missing_normals {
+        let given: Vec<Vec3> = mesh.vertices.iter().map(|v| v.normal).collect();
+        mesh.compute_normals();
+        for (vertex, normal) in mesh.vertices.iter_mut().zip(given) {
+            if normal != Vec3::ZERO {
+                vertex.normal = normal;
+            }
+        }
+    }
+    mesh.compute_tangents();
+
+    Ok((mesh, material_names))
+}
+
+/// Parses a `.mtl` material library
+pub fn parse_mtl(name: &str, source: &str) -> Result<Vec<ObjMaterial>, Error> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            name,
+            line: number + 1,
+        };
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parser.error("'newmtl' needs a material name"));
+            }
+            materials.push(ObjMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let material = materials
+            .last_mut()
+            .ok_or_else(|| parser.error(format!("'{}' before any 'newmtl'", keyword)))?;
+
+        // Texture statements may carry options (`-bm 1.0 file.png`); the file is last
+        let texture = || {
+            args.last()
+                .map(|file| resources::sibling_name(name, file))
+                .ok_or_else(|| parser.error(format!("'{}' needs a file name", keyword)))
+        };
+
+        match keyword {
+            "Ka" => material.ambient = Vec3::from(parser.floats::<3>(&args, keyword)?),
+            "Kd" => material.diffuse = Vec3::from(parser.floats::<3>(&args, keyword)?),
+            "Ks" => material.specular = Vec3::from(parser.floats::<3>(&args, keyword)?),
+            "Ke" => material.emissive = Vec3::from(parser.floats::<3>(&args, keyword)?),
+            "Ns" => material.shininess = parser.floats::<1>(&args, keyword)?[0],
+            "d" => material.dissolve = parser.floats::<1>(&args, keyword)?[0],
+            "Tr" => material.dissolve = 1.0 - parser.floats::<1>(&args, keyword)?[0],
+            "map_Kd" => material.diffuse_map = Some(texture()?),
+            "map_Ks" => material.specular_map = Some(texture()?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_map = Some(texture()?),
+            "map_d" => material.alpha_map = Some(texture()?),
+            // Illumination model, optical density, reflection maps, ... are unused
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
@@ -0,0 +1,243 @@
+// Rectangle bin packing shared by the sprite atlases and `build.rs`,
+// which includes this file directly: it must not depend on the rest of the crate.
+
+/// Horizontal segment of the skyline, the top edge of the area filled so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Skyline bottom-left packer placing rectangles into a fixed-size bin.
+/// Each rectangle is followed by `padding` empty texels on the right and bottom.
+#[derive(Debug, Clone)]
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    skyline: Vec<Segment>,
+}
+
+impl RectPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        RectPacker {
+            width,
+            height,
+            padding,
+            skyline: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// Top-left corner for a `width` x `height` rectangle, `None` when the bin is full
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (width, height) = (width + self.padding, height + self.padding);
+
+        // Lowest position, then the narrowest segment
+        let mut best: Option<(usize, u32)> = None;
+        for index in 0..self.skyline.len() {
+            if let Some(y) = self.fit(index, width, height) {
+                let better = match best {
+                    None => true,
+                    Some((best_index, best_y)) => {
+                        y < best_y
+                            || (y == best_y && self.skyline[index].width < self.skyline[best_index].width)
+                    }
+                };
+                if better {
+                    best = Some((index, y));
+                }
+            }
+        }
+
+        let (index, y) = best?;
+        let x = self.skyline[index].x;
+        self.skyline.insert(
+            index,
+            Segment {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+
+        // Cut the segments now covered by the new one
+        let end = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() && self.skyline[next].x < end {
+            let segment = &mut self.skyline[next];
+            let segment_end = segment.x + segment.width;
+            if segment_end <= end {
+                self.skyline.remove(next);
+            } else {
+                segment.width = segment_end - end;
+                segment.x = end;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((x, y))
+    }
+
+    /// Top edge of a rectangle placed at the start of segment `index`
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = width as i64;
+        for segment in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment.y);
+            remaining -= segment.width as i64;
+        }
+
+        if y + height > self.height {
+            None
+        } else {
+            Some(y)
+        }
+    }
+}
+
+/// Result of `pack`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packing {
+    pub width: u32,
+    pub height: u32,
+    /// Top-left corner of each rectangle, in input order
+    pub positions: Vec<(u32, u32)>,
+}
+
+/// Packs rectangles of the given sizes into the smallest power-of-two bin
+/// no larger than `max_size` on a side
+pub fn pack(sizes: &[(u32, u32)], max_size: u32, padding: u32) -> Option<Packing> {
+    // Tall rectangles first keep the skyline flat
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((sizes[i].1, sizes[i].0)));
+
+    let area: u64 = sizes
+        .iter()
+        .map(|&(w, h)| (w + padding) as u64 * (h + padding) as u64)
+        .sum();
+    let mut width = 1;
+    let mut height = 1;
+    while (width as u64 * height as u64) < area {
+        if width <= height {
+            width *= 2;
+        } else {
+            height *= 2;
+        }
+    }
+
+    while width <= max_size && height <= max_size {
+        let mut packer = RectPacker::new(width, height, padding);
+        let mut positions = vec![(0, 0); sizes.len()];
+        let placed = order.iter().all(|&i| match packer.insert(sizes[i].0, sizes[i].1) {
+            Some(position) => {
+                positions[i] = position;
+                true
+            }
+            None => false,
+        });
+        if placed {
+            return Some(Packing {
+                width,
+                height,
+                positions,
+            });
+        }
+
+        if width <= height {
+            width *= 2;
+        } else {
+            height *= 2;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mixed sizes, including ones wider than tall and single texels
+    fn sizes() -> Vec<(u32, u32)> {
+        (0..40u32)
+            .map(|i| (1 + (i * 7) % 23, 1 + (i * 11) % 17))
+            .collect()
+    }
+
+    fn overlap(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool {
+        a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+    }
+
+    #[test]
+    fn rects_do_not_overlap_including_padding() {
+        let sizes = sizes();
+        let padding = 2;
+        let packing = pack(&sizes, 1024, padding).unwrap();
+
+        let padded: Vec<_> = packing
+            .positions
+            .iter()
+            .zip(&sizes)
+            .map(|(&(x, y), &(w, h))| (x, y, w + padding, h + padding))
+            .collect();
+        for (i, &a) in padded.iter().enumerate() {
+            for &b in &padded[i + 1..] {
+                assert!(!overlap(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn rects_stay_inside_the_bin() {
+        let sizes = sizes();
+        let padding = 2;
+        let packing = pack(&sizes, 1024, padding).unwrap();
+
+        assert!(packing.width.is_power_of_two() && packing.height.is_power_of_two());
+        for (&(x, y), &(w, h)) in packing.positions.iter().zip(&sizes) {
+            assert!(x + w + padding <= packing.width, "x {} + {} past {}", x, w, packing.width);
+            assert!(y + h + padding <= packing.height, "y {} + {} past {}", y, h, packing.height);
+        }
+    }
+
+    #[test]
+    fn padding_separates_neighbours() {
+        let mut packer = RectPacker::new(16, 16, 3);
+        assert_eq!(packer.insert(4, 4), Some((0, 0)));
+        assert_eq!(packer.insert(4, 4), Some((7, 0)));
+        // 14 + 4 + 3 padding does not fit in 16, so the next one goes below
+        assert_eq!(packer.insert(4, 4), Some((0, 7)));
+    }
+
+    #[test]
+    fn fails_when_the_rects_do_not_fit() {
+        assert_eq!(pack(&[(65, 8)], 64, 0), None);
+        assert_eq!(pack(&[(32, 32); 5], 64, 0), None);
+        // Fits exactly without padding, but not with it
+        assert!(pack(&[(32, 32); 4], 64, 0).is_some());
+        assert_eq!(pack(&[(32, 32); 4], 64, 1), None);
+
+        let mut packer = RectPacker::new(8, 8, 0);
+        assert_eq!(packer.insert(8, 8), Some((0, 0)));
+        assert_eq!(packer.insert(1, 1), None);
+    }
+}
@@ -0,0 +1,485 @@
+use crate::buffer::VertexArray;
+use crate::framebuffer::{self, Framebuffer, FramebufferDesc};
+use crate::material::{self, Material, MaterialLoader, MaterialValue, ParamDesc};
+use crate::program;
+use crate::render_state::{Blend, RenderState, StateCache};
+use crate::resources::{self, Resources};
+use crate::texture::{Texture2D, TextureFormat};
+use crate::Program;
+use gl::types::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to parse post-processing chain {}: {}", name, message)]
+    Parse { name: String, message: String },
+    #[fail(display = "Failed to load post-processing pass {}: {}", name, inner)]
+    Program { name: String, inner: program::Error },
+    #[fail(display = "Invalid post-processing parameters: {}", inner)]
+    Material { inner: material::Error },
+    #[fail(display = "Post-processing framebuffer is incomplete: {}", inner)]
+    Framebuffer { inner: framebuffer::Error },
+}
+
+impl From<material::Error> for Error {
+    fn from(inner: material::Error) -> Self {
+        Error::Material { inner }
+    }
+}
+
+impl From<framebuffer::Error> for Error {
+    fn from(inner: framebuffer::Error) -> Self {
+        Error::Framebuffer { inner }
+    }
+}
+
+/// Texture units of the chain inputs (`shaders/post.glsl`), above the units
+/// taken by pass parameters
+pub const INPUT_UNIT: GLuint = 8;
+pub const SCENE_UNIT: GLuint = 9;
+pub const BLOOM_UNIT: GLuint = 10;
+
+/// Half resolution levels of the bloom blur
+const BLOOM_LEVELS: usize = 6;
+
+/// Shader run by a pass
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum Effect {
+    /// HDR to display range; `u_operator` 0 Reinhard, 1 ACES (default), 2 exponential
+    Tonemap,
+    Gamma,
+    Fxaa,
+    /// Blurred highlights added back to the image; `u_threshold`, `u_knee`,
+    /// `u_radius` and `u_intensity`
+    Bloom,
+    Vignette,
+    /// 3D LUT given as a `u_lut` strip texture of `u_lut_size` slices
+    ColorGrading,
+    /// User fragment shader resource including `post.glsl`
+    Custom(String),
+}
+
+impl Effect {
+    /// Fragment shader resource drawing the pass
+    pub fn shader(&self) -> &str {
+        match self {
+            Effect::Tonemap => "tonemap.frag",
+            Effect::Gamma => "gamma.frag",
+            Effect::Fxaa => "fxaa.frag",
+            Effect::Bloom => "bloom_composite.frag",
+            Effect::Vignette => "vignette.frag",
+            Effect::ColorGrading => "color_grading.frag",
+            Effect::Custom(name) => name,
+        }
+    }
+
+    fn default_name(&self) -> String {
+        match self {
+            Effect::Tonemap => "tonemap".to_string(),
+            Effect::Gamma => "gamma".to_string(),
+            Effect::Fxaa => "fxaa".to_string(),
+            Effect::Bloom => "bloom".to_string(),
+            Effect::Vignette => "vignette".to_string(),
+            Effect::ColorGrading => "color_grading".to_string(),
+            Effect::Custom(name) => Path::new(name)
+                .file_stem()
+                .map_or_else(|| name.clone(), |stem| stem.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+/// Fullscreen pass of a `PostProcess` chain.
+/// Its parameters are set on `material` like those of any material.
+pub struct Pass {
+    pub name: String,
+    pub enabled: bool,
+    pub effect: Effect,
+    pub material: Material,
+    bloom: Option<BloomChain>,
+}
+
+impl Pass {
+    pub fn set(&mut self, name: &str, value: MaterialValue) {
+        self.material.set(name, value);
+    }
+}
+
+/// Programs and mip chain of the bloom blur, run before its composite pass
+struct BloomChain {
+    threshold: Rc<Program>,
+    downsample: Rc<Program>,
+    upsample: Rc<Program>,
+    levels: Vec<Framebuffer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ChainDesc {
+    passes: Vec<PassDesc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PassDesc {
+    effect: Effect,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+    #[serde(default)]
+    params: BTreeMap<String, ParamDesc>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Stack of fullscreen passes ping-ponging between two HDR framebuffers.
+///
+/// Every pass draws a fullscreen triangle with `post.vert` and its effect's
+/// fragment shader, which includes `shaders/post.glsl` for the common
+/// inputs: `u_input` (previous pass), `u_scene` (chain input),
+/// `u_resolution`, `u_texel_size` and `u_time`. User passes follow the same
+/// conventions. Passes can be reordered, toggled and edited through `passes_mut`.
+pub struct PostProcess {
+    gl: gl::Gl,
+    buffers: [Framebuffer; 2],
+    passes: Vec<Pass>,
+    programs: HashMap<String, Rc<Program>>,
+    copy: Rc<Program>,
+    empty: VertexArray,
+    /// Seconds passed to the `u_time` uniform
+    pub time: f32,
+}
+
+impl PostProcess {
+    /// Creates an empty chain for `width` x `height` images
+    pub fn new(gl: &gl::Gl, res: &Resources, width: u32, height: u32) -> Result<Self, Error> {
+        let buffer = || {
+            Framebuffer::new(
+                gl,
+                FramebufferDesc {
+                    width,
+                    height,
+                    colors: vec![TextureFormat::RGBA16F],
+                    depth: None,
+                    samples: 0,
+                },
+            )
+        };
+        let buffers = [buffer()?, buffer()?];
+
+        let mut programs = HashMap::new();
+        let copy = load_program(gl, res, &mut programs, "post_copy.frag")?;
+        Ok(PostProcess {
+            gl: gl.clone(),
+            buffers,
+            passes: Vec::new(),
+            programs,
+            copy,
+            empty: VertexArray::new(gl),
+            time: 0.0,
+        })
+    }
+
+    /// Loads a chain from a RON description such as
+    /// ```text
+    /// (
+    ///     passes: [
+    ///         (effect: Bloom, params: {"u_threshold": Float(1.0), "u_intensity": Float(0.04)}),
+    ///         (effect: Tonemap, params: {"u_operator": Int(1)}),
+    ///         (effect: Gamma),
+    ///         (effect: Fxaa),
+    ///         (effect: Vignette, enabled: false),
+    ///         (effect: ColorGrading, params: {
+    ///             "u_lut": Texture(path: "warm.png", filter: Linear, wrap: ClampToEdge),
+    ///         }),
+    ///         (effect: Custom("scanlines.frag"), name: Some("scanlines")),
+    ///     ],
+    /// )
+    /// ```
+    /// Parameters use the material syntax; texture paths are relative to the
+    /// chain file, shader names to the resource root.
+    pub fn from_resources(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let source = res.load(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        let desc: ChainDesc =
+            ron::from_str(&source.to_string_lossy()).map_err(|e| Error::Parse {
+                name: name.to_string(),
+                message: e.to_string(),
+            })?;
+
+        let mut chain = PostProcess::new(gl, res, width, height)?;
+        let mut loader = MaterialLoader::new(gl, res);
+        for pass_desc in desc.passes {
+            let mut pass = chain.create_pass(res, pass_desc.effect)?;
+            if let Some(name) = pass_desc.name {
+                pass.name = name;
+            }
+            pass.enabled = pass_desc.enabled;
+            for (param, value) in pass_desc.params {
+                let value = loader.value(name, value)?;
+                pass.set(&param, value);
+            }
+            // Bloom parameters are spread over several programs
+            if pass.bloom.is_none() {
+                pass.material.validate()?;
+            }
+            chain.passes.push(pass);
+        }
+        Ok(chain)
+    }
+
+    /// Creates a pass running `effect`, not yet part of the chain
+    pub fn create_pass(&mut self, res: &Resources, effect: Effect) -> Result<Pass, Error> {
+        let program = load_program(&self.gl, res, &mut self.programs, effect.shader())?;
+        let bloom = if effect == Effect::Bloom {
+            Some(BloomChain {
+                threshold: load_program(&self.gl, res, &mut self.programs, "bloom_threshold.frag")?,
+                downsample: load_program(&self.gl, res, &mut self.programs, "bloom_downsample.frag")?,
+                upsample: load_program(&self.gl, res, &mut self.programs, "bloom_upsample.frag")?,
+                levels: Vec::new(),
+            })
+        } else {
+            None
+        };
+
+        let name = effect.default_name();
+        let mut material = Material::new(&self.gl, &name, program);
+        material.state = RenderState {
+            depth_write: false,
+            ..RenderState::default()
+        };
+        Ok(Pass {
+            name,
+            enabled: true,
+            effect,
+            material,
+            bloom,
+        })
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// The passes in the order they run; insert, remove or reorder freely
+    pub fn passes_mut(&mut self) -> &mut Vec<Pass> {
+        &mut self.passes
+    }
+
+    /// First pass called `name`
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Pass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    /// Moves the pass at `from` so it runs at position `to`
+    pub fn move_pass(&mut self, from: usize, to: usize) {
+        let pass = self.passes.remove(from);
+        self.passes.insert(to, pass);
+    }
+
+    /// Recreates the intermediate framebuffers at the new size
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        for buffer in &mut self.buffers {
+            buffer.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the enabled passes on `input`, the last one drawing into `target`
+    /// (or the default framebuffer of size `window_size`)
+    pub fn run(
+        &mut self,
+        cache: &mut StateCache,
+        input: &Texture2D,
+        target: Option<&Framebuffer>,
+        window_size: (u32, u32),
+    ) -> Result<(), Error> {
+        let enabled: Vec<usize> = (0..self.passes.len())
+            .filter(|&i| self.passes[i].enabled)
+            .collect();
+        input.bind(SCENE_UNIT);
+        self.empty.bind();
+
+        let gl = &self.gl;
+        let buffers = &self.buffers;
+        let time = self.time;
+        let mut source = input;
+
+        if enabled.is_empty() {
+            cache.apply(&RenderState {
+                depth_write: false,
+                ..RenderState::default()
+            });
+            self.copy.set_used();
+            draw_into(gl, target, window_size, || {
+                set_inputs(&self.copy, source, target_size(target, window_size), time);
+            });
+        }
+
+        for (n, &index) in enabled.iter().enumerate() {
+            let pass = &mut self.passes[index];
+            if let Some(bloom) = &mut pass.bloom {
+                bloom.render(gl, cache, source, &pass.material, time)?;
+                bloom.levels[0].color(0).bind(BLOOM_UNIT);
+            }
+
+            pass.material.bind(cache);
+            let program = pass.material.program();
+            if n + 1 == enabled.len() {
+                draw_into(gl, target, window_size, || {
+                    set_inputs(program, source, target_size(target, window_size), time);
+                });
+            } else {
+                let output = &buffers[n % 2];
+                draw_into(gl, Some(output), window_size, || {
+                    set_inputs(program, source, (output.width(), output.height()), time);
+                });
+                source = output.color(0);
+            }
+        }
+
+        self.empty.unbind();
+        Ok(())
+    }
+}
+
+impl BloomChain {
+    /// Blurs the highlights of `input` into `levels[0]`, applying the bloom
+    /// parameters of `material` to every step
+    fn render(
+        &mut self,
+        gl: &gl::Gl,
+        cache: &mut StateCache,
+        input: &Texture2D,
+        material: &Material,
+        time: f32,
+    ) -> Result<(), Error> {
+        let width = (input.width() / 2).max(1);
+        let height = (input.height() / 2).max(1);
+        if self.levels.first().map(|l| (l.width(), l.height())) != Some((width, height)) {
+            self.levels.clear();
+            for level in 0..BLOOM_LEVELS {
+                let level_width = (width >> level).max(1);
+                let level_height = (height >> level).max(1);
+                self.levels.push(Framebuffer::new(
+                    gl,
+                    FramebufferDesc {
+                        width: level_width,
+                        height: level_height,
+                        colors: vec![TextureFormat::RGBA16F],
+                        depth: None,
+                        samples: 0,
+                    },
+                )?);
+                if level_width == 1 && level_height == 1 {
+                    break;
+                }
+            }
+        }
+
+        let levels = &self.levels;
+        let run = |cache: &mut StateCache, program: &Program, source: &Texture2D, target: &Framebuffer| {
+            material.bind_to(program, cache);
+            draw_into(gl, Some(target), (0, 0), || {
+                set_inputs(program, source, (target.width(), target.height()), time);
+            });
+        };
+
+        run(cache, &self.threshold, input, &levels[0]);
+        for i in 1..levels.len() {
+            run(cache, &self.downsample, levels[i - 1].color(0), &levels[i]);
+        }
+
+        // Added on top of the downsampled image of each level
+        let mut additive = material.state;
+        additive.blend = Some(Blend::ADDITIVE);
+        for i in (0..levels.len() - 1).rev() {
+            material.bind_to(&self.upsample, cache);
+            cache.apply(&additive);
+            draw_into(gl, Some(&levels[i]), (0, 0), || {
+                set_inputs(
+                    &self.upsample,
+                    levels[i + 1].color(0),
+                    (levels[i].width(), levels[i].height()),
+                    time,
+                );
+            });
+        }
+        Ok(())
+    }
+}
+
+fn target_size(target: Option<&Framebuffer>, window_size: (u32, u32)) -> (u32, u32) {
+    target.map_or(window_size, |fb| (fb.width(), fb.height()))
+}
+
+/// Binds `target` (the default framebuffer when `None`), runs `set_up` and
+/// draws the fullscreen triangle
+fn draw_into<F: FnOnce()>(gl: &gl::Gl, target: Option<&Framebuffer>, window_size: (u32, u32), set_up: F) {
+    let _guard = match target {
+        Some(framebuffer) => Some(framebuffer.bind()),
+        None => {
+            unsafe {
+                gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl.Viewport(0, 0, window_size.0 as GLsizei, window_size.1 as GLsizei);
+            }
+            None
+        }
+    };
+    set_up();
+    unsafe { gl.DrawArrays(gl::TRIANGLES, 0, 3) };
+}
+
+/// Sets the `shaders/post.glsl` inputs, with `source` as `u_input`
+fn set_inputs(program: &Program, source: &Texture2D, output_size: (u32, u32), time: f32) {
+    source.bind(INPUT_UNIT);
+    program.set_uniform(
+        "u_resolution",
+        &[output_size.0 as f32, output_size.1 as f32],
+    );
+    program.set_uniform(
+        "u_texel_size",
+        &[1.0 / source.width() as f32, 1.0 / source.height() as f32],
+    );
+    program.set_uniform("u_time", &time);
+}
+
+/// `post.vert` + fragment shader `frag`, compiled once per chain, with the
+/// input samplers assigned to their units
+fn load_program(
+    gl: &gl::Gl,
+    res: &Resources,
+    programs: &mut HashMap<String, Rc<Program>>,
+    frag: &str,
+) -> Result<Rc<Program>, Error> {
+    if let Some(program) = programs.get(frag) {
+        return Ok(program.clone());
+    }
+
+    let program = Program::from_resource_files(gl, res, &["post.vert", frag]).map_err(|inner| {
+        Error::Program {
+            name: frag.to_string(),
+            inner,
+        }
+    })?;
+    program.set_uniform("u_input", &(INPUT_UNIT as i32));
+    program.set_uniform("u_scene", &(SCENE_UNIT as i32));
+    program.set_uniform("u_bloom", &(BLOOM_UNIT as i32));
+
+    let program = Rc::new(program);
+    programs.insert(frag.to_string(), program.clone());
+    Ok(program)
+}
@@ -1,5 +1,8 @@
-use crate::{resources, Shader, util};
+use crate::resources::{self, Resources};
+use crate::{Shader, util};
+use crate::uniform::Uniform;
 use gl::types::*;
+use std::ffi::CString;
 
 /// Wrapper for OpenGL program
 pub struct Program {
@@ -19,6 +22,18 @@ pub enum Error {
     LinkError { name: String, message: String },
 }
 
+/// Uniform reported by `glGetActiveUniform`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveUniform {
+    /// Name without the `[0]` suffix of arrays
+    pub name: String,
+    pub location: GLint,
+    /// GLSL type, e.g. `gl::FLOAT_VEC3` or `gl::SAMPLER_2D`
+    pub kind: GLenum,
+    /// Number of array elements, 1 for non-arrays
+    pub size: GLint,
+}
+
 impl Program {
     pub fn id(&self) -> GLuint {
         self.id
@@ -30,6 +45,89 @@ impl Program {
         }
     }
 
+    /// Location of the uniform `name`, or `None` if it is not active
+    pub fn uniform_location(&self, name: &str) -> Option<GLint> {
+        let name = CString::new(name).ok()?;
+        let location = unsafe { self.gl.GetUniformLocation(self.id, name.as_ptr()) };
+        if location < 0 {
+            None
+        } else {
+            Some(location)
+        }
+    }
+
+    /// Sets the uniform `name`, returning false if the program does not use it
+    pub fn set_uniform<U: Uniform + ?Sized>(&self, name: &str, value: &U) -> bool {
+        match self.uniform_location(name) {
+            Some(location) => {
+                value.set_uniform(&self.gl, self.id, location);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Assigns the uniform block `name` to buffer binding point `binding`,
+    /// returning false if the program has no such block
+    pub fn bind_uniform_block(&self, name: &str, binding: GLuint) -> bool {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        unsafe {
+            let index = self.gl.GetUniformBlockIndex(self.id, name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return false;
+            }
+            self.gl.UniformBlockBinding(self.id, index, binding);
+        }
+        true
+    }
+
+    /// Lists the active uniforms outside of uniform blocks
+    pub fn active_uniforms(&self) -> Vec<ActiveUniform> {
+        let mut count: GLint = 0;
+        let mut max_len: GLint = 0;
+        unsafe {
+            self.gl.GetProgramiv(self.id, gl::ACTIVE_UNIFORMS, &mut count);
+            self.gl
+                .GetProgramiv(self.id, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_len);
+        }
+
+        let mut uniforms = Vec::new();
+        for index in 0..count as GLuint {
+            let mut name = vec![0u8; max_len.max(1) as usize];
+            let mut len: GLsizei = 0;
+            let mut size: GLint = 0;
+            let mut kind: GLenum = 0;
+            unsafe {
+                self.gl.GetActiveUniform(
+                    self.id,
+                    index,
+                    max_len,
+                    &mut len,
+                    &mut size,
+                    &mut kind,
+                    name.as_mut_ptr() as *mut GLchar,
+                );
+            }
+            name.truncate(len as usize);
+            let name = String::from_utf8_lossy(&name);
+            let name = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+
+            // Members of uniform blocks have no location
+            if let Some(location) = self.uniform_location(&name) {
+                uniforms.push(ActiveUniform {
+                    name,
+                    location,
+                    kind,
+                    size,
+                });
+            }
+        }
+        uniforms
+    }
+
     /// Creates a new program from shaders
     pub fn from_shaders(gl: &gl::Gl, shaders: &[Shader]) -> Result<Self, String> {
         let id = unsafe { gl.CreateProgram() };
@@ -62,6 +160,23 @@ impl Program {
 
         Ok(Program { gl: gl.clone(), id })
     }
+
+    /// Compiles the shader resources `names`, each stage picked by its
+    /// extension, and links them, e.g. `&["post.vert", "bloom.frag"]`
+    pub fn from_resource_files(
+        gl: &gl::Gl,
+        res: &Resources,
+        names: &[&str],
+    ) -> Result<Self, Error> {
+        let shaders = names
+            .iter()
+            .map(|name| Shader::from_resources(gl, res, name))
+            .collect::<Result<Vec<_>, _>>()?;
+        Program::from_shaders(gl, &shaders).map_err(|message| Error::LinkError {
+            name: names.join(" + "),
+            message,
+        })
+    }
 }
 
 impl Drop for Program {
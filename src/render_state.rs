@@ -0,0 +1,692 @@
+use gl::types::*;
+
+/// Comparison used by depth and stencil tests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    fn as_gl(self) -> GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+impl BlendFactor {
+    fn as_gl(self) -> GLenum {
+        match self {
+            BlendFactor::Zero => gl::ZERO,
+            BlendFactor::One => gl::ONE,
+            BlendFactor::SrcColor => gl::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+            BlendFactor::DstColor => gl::DST_COLOR,
+            BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+            BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstAlpha => gl::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendEquation {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+impl BlendEquation {
+    fn as_gl(self) -> GLenum {
+        match self {
+            BlendEquation::Add => gl::FUNC_ADD,
+            BlendEquation::Subtract => gl::FUNC_SUBTRACT,
+            BlendEquation::ReverseSubtract => gl::FUNC_REVERSE_SUBTRACT,
+            BlendEquation::Min => gl::MIN,
+            BlendEquation::Max => gl::MAX,
+        }
+    }
+}
+
+/// Blending applied to colour writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blend {
+    pub src_rgb: BlendFactor,
+    pub dst_rgb: BlendFactor,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+    pub equation: BlendEquation,
+}
+
+impl Blend {
+    /// Classic `src * a + dst * (1 - a)` transparency
+    pub const ALPHA: Blend = Blend {
+        src_rgb: BlendFactor::SrcAlpha,
+        dst_rgb: BlendFactor::OneMinusSrcAlpha,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        equation: BlendEquation::Add,
+    };
+    /// Transparency for colours already multiplied by alpha
+    pub const PREMULTIPLIED: Blend = Blend {
+        src_rgb: BlendFactor::One,
+        dst_rgb: BlendFactor::OneMinusSrcAlpha,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        equation: BlendEquation::Add,
+    };
+    /// `src + dst`, for particles and light accumulation
+    pub const ADDITIVE: Blend = Blend {
+        src_rgb: BlendFactor::One,
+        dst_rgb: BlendFactor::One,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::One,
+        equation: BlendEquation::Add,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CullFace {
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+impl CullFace {
+    fn as_gl(self) -> GLenum {
+        match self {
+            CullFace::Front => gl::FRONT,
+            CullFace::Back => gl::BACK,
+            CullFace::FrontAndBack => gl::FRONT_AND_BACK,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+impl PolygonMode {
+    fn as_gl(self) -> GLenum {
+        match self {
+            PolygonMode::Fill => gl::FILL,
+            PolygonMode::Line => gl::LINE,
+            PolygonMode::Point => gl::POINT,
+        }
+    }
+}
+
+/// Scissor rectangle in window coordinates, origin at the bottom-left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scissor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Incr,
+    IncrWrap,
+    Decr,
+    DecrWrap,
+    Invert,
+}
+
+impl StencilOp {
+    fn as_gl(self) -> GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Incr => gl::INCR,
+            StencilOp::IncrWrap => gl::INCR_WRAP,
+            StencilOp::Decr => gl::DECR,
+            StencilOp::DecrWrap => gl::DECR_WRAP,
+            StencilOp::Invert => gl::INVERT,
+        }
+    }
+}
+
+/// Stencil test, applied identically to front and back faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stencil {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    pub stencil_fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+/// Depth offset of filled polygons, `factor * slope + units * r` as in
+/// `glPolygonOffset`, where `r` is the smallest resolvable depth difference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PolygonOffset {
+    pub factor: f32,
+    pub units: f32,
+}
+
+/// Immutable description of the fixed-function state used by a draw.
+/// `Default` matches the initial OpenGL state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderState {
+    pub blend: Option<Blend>,
+    pub depth_test: Option<CompareFunc>,
+    pub depth_write: bool,
+    pub cull_face: Option<CullFace>,
+    pub polygon_mode: PolygonMode,
+    pub polygon_offset: Option<PolygonOffset>,
+    pub scissor: Option<Scissor>,
+    pub stencil: Option<Stencil>,
+    pub color_mask: [bool; 4],
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        RenderState {
+            blend: None,
+            depth_test: None,
+            depth_write: true,
+            cull_face: None,
+            polygon_mode: PolygonMode::Fill,
+            polygon_offset: None,
+            scissor: None,
+            stencil: None,
+            color_mask: [true; 4],
+        }
+    }
+}
+
+impl RenderState {
+    /// Depth tested, back-face culled, no blending
+    pub fn opaque() -> Self {
+        RenderState {
+            depth_test: Some(CompareFunc::Less),
+            cull_face: Some(CullFace::Back),
+            ..RenderState::default()
+        }
+    }
+
+    /// Alpha blended, depth tested without depth writes
+    pub fn transparent() -> Self {
+        RenderState {
+            blend: Some(Blend::ALPHA),
+            depth_test: Some(CompareFunc::Less),
+            depth_write: false,
+            ..RenderState::default()
+        }
+    }
+
+    /// No depth test and alpha blending, for overlays drawn last
+    pub fn overlay() -> Self {
+        RenderState {
+            blend: Some(Blend::ALPHA),
+            depth_write: false,
+            ..RenderState::default()
+        }
+    }
+}
+
+/// Shadow copy of the GL fixed-function state.
+/// `apply` only issues the calls needed to go from the cached state
+/// to the requested one.
+pub struct StateCache {
+    gl: gl::Gl,
+    current: RenderState,
+}
+
+impl StateCache {
+    /// Creates a cache assuming the context is in its initial state
+    pub fn new(gl: &gl::Gl) -> Self {
+        StateCache {
+            gl: gl.clone(),
+            current: RenderState::default(),
+        }
+    }
+
+    pub fn current(&self) -> &RenderState {
+        &self.current
+    }
+
+    /// Re-applies every piece of state, for when raw GL calls may have
+    /// changed it behind the cache's back
+    pub fn reset(&mut self, state: &RenderState) {
+        let gl = &self.gl;
+        set_capability(gl, gl::BLEND, state.blend.is_some());
+        if let Some(blend) = state.blend {
+            apply_blend(gl, &blend);
+        }
+        set_capability(gl, gl::DEPTH_TEST, state.depth_test.is_some());
+        if let Some(func) = state.depth_test {
+            unsafe { gl.DepthFunc(func.as_gl()) };
+        }
+        unsafe { gl.DepthMask(state.depth_write as GLboolean) };
+        set_capability(gl, gl::CULL_FACE, state.cull_face.is_some());
+        if let Some(face) = state.cull_face {
+            unsafe { gl.CullFace(face.as_gl()) };
+        }
+        unsafe { gl.PolygonMode(gl::FRONT_AND_BACK, state.polygon_mode.as_gl()) };
+        set_capability(gl, gl::POLYGON_OFFSET_FILL, state.polygon_offset.is_some());
+        if let Some(offset) = state.polygon_offset {
+            unsafe { gl.PolygonOffset(offset.factor, offset.units) };
+        }
+        set_capability(gl, gl::SCISSOR_TEST, state.scissor.is_some());
+        if let Some(scissor) = state.scissor {
+            apply_scissor(gl, &scissor);
+        }
+        set_capability(gl, gl::STENCIL_TEST, state.stencil.is_some());
+        match state.stencil {
+            Some(stencil) => apply_stencil(gl, &stencil),
+            None => reset_stencil_mask(gl),
+        }
+        apply_color_mask(gl, state.color_mask);
+
+        self.current = *state;
+        self.debug_validate();
+    }
+
+    /// Switches the GL state to `state`, skipping everything that already matches
+    pub fn apply(&mut self, state: &RenderState) {
+        if *state == self.current {
+            return;
+        }
+
+        let gl = &self.gl;
+        let current = self.current;
+
+        if state.blend != current.blend {
+            match state.blend {
+                Some(blend) => {
+                    if current.blend.is_none() {
+                        set_capability(gl, gl::BLEND, true);
+                    }
+                    apply_blend(gl, &blend);
+                }
+                None => set_capability(gl, gl::BLEND, false),
+            }
+        }
+
+        if state.depth_test != current.depth_test {
+            match state.depth_test {
+                Some(func) => {
+                    if current.depth_test.is_none() {
+                        set_capability(gl, gl::DEPTH_TEST, true);
+                    }
+                    unsafe { gl.DepthFunc(func.as_gl()) };
+                }
+                None => set_capability(gl, gl::DEPTH_TEST, false),
+            }
+        }
+
+        if state.depth_write != current.depth_write {
+            unsafe { gl.DepthMask(state.depth_write as GLboolean) };
+        }
+
+        if state.cull_face != current.cull_face {
+            match state.cull_face {
+                Some(face) => {
+                    if current.cull_face.is_none() {
+                        set_capability(gl, gl::CULL_FACE, true);
+                    }
+                    unsafe { gl.CullFace(face.as_gl()) };
+                }
+                None => set_capability(gl, gl::CULL_FACE, false),
+            }
+        }
+
+        if state.polygon_mode != current.polygon_mode {
+            unsafe { gl.PolygonMode(gl::FRONT_AND_BACK, state.polygon_mode.as_gl()) };
+        }
+
+        if state.polygon_offset != current.polygon_offset {
+            match state.polygon_offset {
+                Some(offset) => {
+                    if current.polygon_offset.is_none() {
+                        set_capability(gl, gl::POLYGON_OFFSET_FILL, true);
+                    }
+                    unsafe { gl.PolygonOffset(offset.factor, offset.units) };
+                }
+                None => set_capability(gl, gl::POLYGON_OFFSET_FILL, false),
+            }
+        }
+
+        if state.scissor != current.scissor {
+            match state.scissor {
+                Some(scissor) => {
+                    if current.scissor.is_none() {
+                        set_capability(gl, gl::SCISSOR_TEST, true);
+                    }
+                    apply_scissor(gl, &scissor);
+                }
+                None => set_capability(gl, gl::SCISSOR_TEST, false),
+            }
+        }
+
+        if state.stencil != current.stencil {
+            match state.stencil {
+                Some(stencil) => {
+                    if current.stencil.is_none() {
+                        set_capability(gl, gl::STENCIL_TEST, true);
+                    }
+                    apply_stencil(gl, &stencil);
+                }
+                None => {
+                    set_capability(gl, gl::STENCIL_TEST, false);
+                    reset_stencil_mask(gl);
+                }
+            }
+        }
+
+        if state.color_mask != current.color_mask {
+            apply_color_mask(gl, state.color_mask);
+        }
+
+        self.current = *state;
+        self.debug_validate();
+    }
+
+    /// Compares the cached state against the values reported by `glGet*`,
+    /// returning a description of every mismatch
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let gl = &self.gl;
+        let state = &self.current;
+        let mut mismatches = Vec::new();
+        let mut check = |name: &str, expected: GLint, actual: GLint| {
+            if expected != actual {
+                mismatches.push(format!(
+                    "{}: cached 0x{:X}, GL reports 0x{:X}",
+                    name, expected, actual
+                ));
+            }
+        };
+
+        check(
+            "GL_BLEND",
+            state.blend.is_some() as GLint,
+            is_enabled(gl, gl::BLEND),
+        );
+        if let Some(blend) = state.blend {
+            check(
+                "GL_BLEND_SRC_RGB",
+                blend.src_rgb.as_gl() as GLint,
+                get_integer(gl, gl::BLEND_SRC_RGB),
+            );
+            check(
+                "GL_BLEND_DST_RGB",
+                blend.dst_rgb.as_gl() as GLint,
+                get_integer(gl, gl::BLEND_DST_RGB),
+            );
+            check(
+                "GL_BLEND_SRC_ALPHA",
+                blend.src_alpha.as_gl() as GLint,
+                get_integer(gl, gl::BLEND_SRC_ALPHA),
+            );
+            check(
+                "GL_BLEND_DST_ALPHA",
+                blend.dst_alpha.as_gl() as GLint,
+                get_integer(gl, gl::BLEND_DST_ALPHA),
+            );
+            check(
+                "GL_BLEND_EQUATION_RGB",
+                blend.equation.as_gl() as GLint,
+                get_integer(gl, gl::BLEND_EQUATION_RGB),
+            );
+        }
+
+        check(
+            "GL_DEPTH_TEST",
+            state.depth_test.is_some() as GLint,
+            is_enabled(gl, gl::DEPTH_TEST),
+        );
+        if let Some(func) = state.depth_test {
+            check(
+                "GL_DEPTH_FUNC",
+                func.as_gl() as GLint,
+                get_integer(gl, gl::DEPTH_FUNC),
+            );
+        }
+        check(
+            "GL_DEPTH_WRITEMASK",
+            state.depth_write as GLint,
+            get_integer(gl, gl::DEPTH_WRITEMASK),
+        );
+
+        check(
+            "GL_CULL_FACE",
+            state.cull_face.is_some() as GLint,
+            is_enabled(gl, gl::CULL_FACE),
+        );
+        if let Some(face) = state.cull_face {
+            check(
+                "GL_CULL_FACE_MODE",
+                face.as_gl() as GLint,
+                get_integer(gl, gl::CULL_FACE_MODE),
+            );
+        }
+
+        let mut polygon_mode: [GLint; 2] = [0; 2];
+        unsafe { gl.GetIntegerv(gl::POLYGON_MODE, polygon_mode.as_mut_ptr()) };
+        check(
+            "GL_POLYGON_MODE",
+            state.polygon_mode.as_gl() as GLint,
+            polygon_mode[0],
+        );
+
+        check(
+            "GL_SCISSOR_TEST",
+            state.scissor.is_some() as GLint,
+            is_enabled(gl, gl::SCISSOR_TEST),
+        );
+        if let Some(scissor) = state.scissor {
+            let mut scissor_box: [GLint; 4] = [0; 4];
+            unsafe { gl.GetIntegerv(gl::SCISSOR_BOX, scissor_box.as_mut_ptr()) };
+            let expected = [scissor.x, scissor.y, scissor.width, scissor.height];
+            for (i, (&e, &a)) in expected.iter().zip(scissor_box.iter()).enumerate() {
+                check(&format!("GL_SCISSOR_BOX[{}]", i), e, a);
+            }
+        }
+
+        check(
+            "GL_STENCIL_TEST",
+            state.stencil.is_some() as GLint,
+            is_enabled(gl, gl::STENCIL_TEST),
+        );
+        if let Some(stencil) = state.stencil {
+            check(
+                "GL_STENCIL_FUNC",
+                stencil.func.as_gl() as GLint,
+                get_integer(gl, gl::STENCIL_FUNC),
+            );
+            check(
+                "GL_STENCIL_REF",
+                stencil.reference,
+                get_integer(gl, gl::STENCIL_REF),
+            );
+            check(
+                "GL_STENCIL_VALUE_MASK",
+                stencil.read_mask as GLint,
+                get_integer(gl, gl::STENCIL_VALUE_MASK),
+            );
+            check(
+                "GL_STENCIL_WRITEMASK",
+                (stencil.write_mask & STENCIL_BITS) as GLint,
+                get_integer(gl, gl::STENCIL_WRITEMASK) & STENCIL_BITS as GLint,
+            );
+            check(
+                "GL_STENCIL_FAIL",
+                stencil.stencil_fail.as_gl() as GLint,
+                get_integer(gl, gl::STENCIL_FAIL),
+            );
+            check(
+                "GL_STENCIL_PASS_DEPTH_FAIL",
+                stencil.depth_fail.as_gl() as GLint,
+                get_integer(gl, gl::STENCIL_PASS_DEPTH_FAIL),
+            );
+            check(
+                "GL_STENCIL_PASS_DEPTH_PASS",
+                stencil.pass.as_gl() as GLint,
+                get_integer(gl, gl::STENCIL_PASS_DEPTH_PASS),
+            );
+        } else {
+            check(
+                "GL_STENCIL_WRITEMASK",
+                STENCIL_BITS as GLint,
+                get_integer(gl, gl::STENCIL_WRITEMASK) & STENCIL_BITS as GLint,
+            );
+        }
+
+        let mut color_mask: [GLboolean; 4] = [0; 4];
+        unsafe { gl.GetBooleanv(gl::COLOR_WRITEMASK, color_mask.as_mut_ptr()) };
+        for (i, (&e, &a)) in state.color_mask.iter().zip(color_mask.iter()).enumerate() {
+            check(
+                &format!("GL_COLOR_WRITEMASK[{}]", i),
+                e as GLint,
+                a as GLint,
+            );
+        }
+
+        check(
+            "GL_POLYGON_OFFSET_FILL",
+            state.polygon_offset.is_some() as GLint,
+            is_enabled(gl, gl::POLYGON_OFFSET_FILL),
+        );
+        if let Some(offset) = state.polygon_offset {
+            let expected = [offset.factor, offset.units];
+            let mut actual = [0.0f32; 2];
+            unsafe {
+                gl.GetFloatv(gl::POLYGON_OFFSET_FACTOR, &mut actual[0]);
+                gl.GetFloatv(gl::POLYGON_OFFSET_UNITS, &mut actual[1]);
+            }
+            if expected != actual {
+                mismatches.push(format!(
+                    "GL_POLYGON_OFFSET: cached {:?}, GL reports {:?}",
+                    expected, actual
+                ));
+            }
+        }
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// With the `gl_debug` feature, panics when the cache and GL disagree
+    fn debug_validate(&self) {
+        #[cfg(feature = "gl_debug")]
+        if let Err(mismatches) = self.validate() {
+            panic!("Render state cache out of sync:\n{}", mismatches.join("\n"));
+        }
+    }
+}
+
+fn set_capability(gl: &gl::Gl, capability: GLenum, enabled: bool) {
+    unsafe {
+        if enabled {
+            gl.Enable(capability);
+        } else {
+            gl.Disable(capability);
+        }
+    }
+}
+
+fn apply_blend(gl: &gl::Gl, blend: &Blend) {
+    unsafe {
+        gl.BlendFuncSeparate(
+            blend.src_rgb.as_gl(),
+            blend.dst_rgb.as_gl(),
+            blend.src_alpha.as_gl(),
+            blend.dst_alpha.as_gl(),
+        );
+        gl.BlendEquation(blend.equation.as_gl());
+    }
+}
+
+fn apply_scissor(gl: &gl::Gl, scissor: &Scissor) {
+    unsafe { gl.Scissor(scissor.x, scissor.y, scissor.width, scissor.height) };
+}
+
+fn apply_stencil(gl: &gl::Gl, stencil: &Stencil) {
+    unsafe {
+        gl.StencilFunc(stencil.func.as_gl(), stencil.reference, stencil.read_mask);
+        gl.StencilMask(stencil.write_mask);
+        gl.StencilOp(
+            stencil.stencil_fail.as_gl(),
+            stencil.depth_fail.as_gl(),
+            stencil.pass.as_gl(),
+        );
+    }
+}
+
+/// Bits of the stencil masks compared by `validate`: GL reports them
+/// clamped to the stencil buffer depth, 8 bits in practice
+const STENCIL_BITS: u32 = 0xFF;
+
+/// The write mask also limits `glClear`, so it must not stay restricted
+/// once the stencil test is off
+fn reset_stencil_mask(gl: &gl::Gl) {
+    unsafe { gl.StencilMask(!0) };
+}
+
+fn apply_color_mask(gl: &gl::Gl, mask: [bool; 4]) {
+    unsafe {
+        gl.ColorMask(
+            mask[0] as GLboolean,
+            mask[1] as GLboolean,
+            mask[2] as GLboolean,
+            mask[3] as GLboolean,
+        )
+    };
+}
+
+fn is_enabled(gl: &gl::Gl, capability: GLenum) -> GLint {
+    unsafe { gl.IsEnabled(capability) as GLint }
+}
+
+fn get_integer(gl: &gl::Gl, name: GLenum) -> GLint {
+    let mut value: GLint = 0;
+    unsafe { gl.GetIntegerv(name, &mut value) };
+    value
+}
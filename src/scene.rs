@@ -0,0 +1,413 @@
+use crate::camera::Camera;
+use crate::gltf_loader::GltfScene;
+use crate::math::{Mat4, Quat, Vec3};
+
+/// Handle to a node of a `Scene`.
+/// Handles of removed nodes are detected through the slot generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// Local transform of a node relative to its parent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_trs(self.translation, self.rotation, self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+/// Components attached to a node.
+/// Each is an index into a list owned by the renderer (meshes, materials,
+/// lights, cameras), so the scene stays free of GL objects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Components {
+    pub mesh: Option<usize>,
+    pub material: Option<usize>,
+    pub light: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+struct Node {
+    name: String,
+    local: Transform,
+    world: Mat4,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    components: Components,
+    visible: bool,
+    /// Local transform changed since the last `update_transforms`
+    dirty: bool,
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// Node hierarchy stored in an arena.
+/// Nodes refer to each other by `NodeId`; world matrices are cached and
+/// recomputed by `update_transforms` for the subtrees whose local
+/// transforms changed.
+#[derive(Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+/// Mesh to draw, produced by `Scene::draw_list`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawItem {
+    pub node: NodeId,
+    pub mesh: usize,
+    pub material: Option<usize>,
+    pub world: Mat4,
+    /// Distance from the camera along its view direction
+    pub depth: f32,
+    pub transparent: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    /// Adds a node with an identity transform under `parent`, or as a root
+    pub fn create_node(&mut self, name: &str, parent: Option<NodeId>) -> NodeId {
+        let node = Node {
+            name: name.to_string(),
+            local: Transform::IDENTITY,
+            world: Mat4::IDENTITY,
+            parent: None,
+            children: Vec::new(),
+            components: Components::default(),
+            visible: true,
+            dirty: true,
+        };
+
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: (self.slots.len() - 1) as u32,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.attach(id, parent);
+        id
+    }
+
+    /// Removes `id` and all of its descendants
+    pub fn remove_node(&mut self, id: NodeId) {
+        if !self.contains(id) {
+            return;
+        }
+        self.detach(id);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(id.index);
+            }
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Iterates over every live node, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.node.as_ref().map(|_| NodeId {
+                index: index as u32,
+                generation: slot.generation,
+            })
+        })
+    }
+
+    /// First node called `name`
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|&id| self.node(id).map(|n| n.name.as_str()) == Some(name))
+    }
+
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.expect(id).name
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.expect(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.expect(id).children
+    }
+
+    /// Moves `id` under `parent` (or to the roots), keeping its local transform.
+    /// # Panics
+    /// If `parent` is `id` itself or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert!(a != id, "cannot parent a node to its own subtree");
+            ancestor = self.expect(a).parent;
+        }
+
+        self.detach(id);
+        self.attach(id, parent);
+    }
+
+    pub fn local(&self, id: NodeId) -> &Transform {
+        &self.expect(id).local
+    }
+
+    pub fn set_local(&mut self, id: NodeId, transform: Transform) {
+        *self.local_mut(id) = transform;
+    }
+
+    /// Mutable access to the local transform; marks the node dirty
+    pub fn local_mut(&mut self, id: NodeId) -> &mut Transform {
+        let node = self.expect_mut(id);
+        node.dirty = true;
+        &mut node.local
+    }
+
+    /// World matrix as of the last `update_transforms`
+    pub fn world(&self, id: NodeId) -> Mat4 {
+        self.expect(id).world
+    }
+
+    pub fn components(&self, id: NodeId) -> &Components {
+        &self.expect(id).components
+    }
+
+    pub fn components_mut(&mut self, id: NodeId) -> &mut Components {
+        &mut self.expect_mut(id).components
+    }
+
+    pub fn is_visible(&self, id: NodeId) -> bool {
+        self.expect(id).visible
+    }
+
+    /// Hidden nodes are skipped by `draw_list` together with their descendants
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        self.expect_mut(id).visible = visible;
+    }
+
+    /// Recomputes the world matrices of dirty nodes and their descendants
+    pub fn update_transforms(&mut self) {
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, Mat4::IDENTITY, false))
+            .collect();
+
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let node = self.expect_mut(id);
+            let changed = parent_changed || node.dirty;
+            if changed {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+            }
+
+            let world = node.world;
+            stack.extend(node.children.iter().rev().map(|&child| (child, world, changed)));
+        }
+    }
+
+    /// Collects the visible mesh nodes seen from `camera`.
+    /// Opaque items come first, grouped by material and mesh to limit state
+    /// changes, then front to back; transparent items follow back to front.
+    /// `is_transparent` tells which materials need blending.
+    /// Call `update_transforms` first.
+    pub fn draw_list<F>(&self, camera: &Camera, is_transparent: F) -> Vec<DrawItem>
+    where
+        F: Fn(Option<usize>) -> bool,
+    {
+        let eye = camera.position;
+        let forward = camera.forward();
+        let mut items = Vec::new();
+
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.expect(id);
+            if !node.visible {
+                continue;
+            }
+            stack.extend(node.children.iter().rev());
+
+            if let Some(mesh) = node.components.mesh {
+                let material = node.components.material;
+                items.push(DrawItem {
+                    node: id,
+                    mesh,
+                    material,
+                    world: node.world,
+                    depth: (node.world.translation() - eye).dot(forward),
+                    transparent: is_transparent(material),
+                });
+            }
+        }
+
+        items.sort_by(|a, b| {
+            a.transparent.cmp(&b.transparent).then_with(|| {
+                if a.transparent {
+                    b.depth.total_cmp(&a.depth)
+                } else {
+                    a.material
+                        .cmp(&b.material)
+                        .then(a.mesh.cmp(&b.mesh))
+                        .then(a.depth.total_cmp(&b.depth))
+                }
+            })
+        });
+        items
+    }
+
+    /// Instantiates the node hierarchy of an imported glTF scene under `parent`.
+    /// Mesh components index the primitives of `gltf.meshes` flattened in
+    /// order, as uploaded by `GltfScene::upload_meshes`; a mesh with several
+    /// primitives gets one child node per primitive.
+    /// # Returns
+    /// The scene node created for each glTF node, by glTF node index
+    pub fn add_gltf(&mut self, gltf: &GltfScene, parent: Option<NodeId>) -> Vec<Option<NodeId>> {
+        let mut first_primitive = Vec::with_capacity(gltf.meshes.len());
+        let mut count = 0;
+        for mesh in &gltf.meshes {
+            first_primitive.push(count);
+            count += mesh.primitives.len();
+        }
+
+        let mut ids = vec![None; gltf.nodes.len()];
+        let mut stack: Vec<(usize, Option<NodeId>)> =
+            gltf.roots.iter().rev().map(|&index| (index, parent)).collect();
+
+        while let Some((index, parent)) = stack.pop() {
+            let source = &gltf.nodes[index];
+            let id = self.create_node(&source.name, parent);
+            self.set_local(
+                id,
+                Transform {
+                    translation: source.translation,
+                    rotation: source.rotation,
+                    scale: source.scale,
+                },
+            );
+            self.components_mut(id).camera = source.camera;
+
+            if let Some(mesh) = source.mesh {
+                let primitives = &gltf.meshes[mesh].primitives;
+                if let [primitive] = primitives.as_slice() {
+                    let components = self.components_mut(id);
+                    components.mesh = Some(first_primitive[mesh]);
+                    components.material = primitive.material;
+                } else {
+                    for (i, primitive) in primitives.iter().enumerate() {
+                        let child = self.create_node(&format!("{}.{}", source.name, i), Some(id));
+                        let components = self.components_mut(child);
+                        components.mesh = Some(first_primitive[mesh] + i);
+                        components.material = primitive.material;
+                    }
+                }
+            }
+
+            ids[index] = Some(id);
+            stack.extend(source.children.iter().rev().map(|&child| (child, Some(id))));
+        }
+
+        ids
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    fn expect(&self, id: NodeId) -> &Node {
+        self.node(id).expect("stale NodeId")
+    }
+
+    fn expect_mut(&mut self, id: NodeId) -> &mut Node {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+            .expect("stale NodeId")
+    }
+
+    fn attach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        match parent {
+            Some(parent) => self.expect_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        let node = self.expect_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+    }
+
+    fn detach(&mut self, id: NodeId) {
+        let siblings = match self.expect(id).parent {
+            Some(parent) => &mut self.expect_mut(parent).children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&child| child != id);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::buffer::VertexArray;
+use crate::camera::{Camera, Projection};
+use crate::framebuffer;
+use crate::lighting::Light;
+use crate::math::{radians, Mat4, Vec3};
+use crate::program;
+use crate::render_state::{CompareFunc, PolygonOffset, RenderState, StateCache};
+use crate::resources::Resources;
+use crate::texture::{TextureArray, TextureFormat};
+use crate::Program;
+use gl::types::*;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load shadow program: {}", inner)]
+    Program { inner: program::Error },
+    #[fail(display = "Shadow framebuffer is incomplete: {}", inner)]
+    Framebuffer { inner: framebuffer::Error },
+}
+
+impl From<program::Error> for Error {
+    fn from(inner: program::Error) -> Self {
+        Error::Program { inner }
+    }
+}
+
+/// Shadow maps per kind of light; must match `shaders/shadows.glsl`
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_SPOT_SHADOWS: usize = 4;
+pub const MAX_POINT_SHADOWS: usize = 4;
+
+/// Texture units `ShadowMaps::bind` uses, below the IBL units
+pub const CASCADE_UNIT: GLuint = 10;
+pub const SPOT_UNIT: GLuint = 11;
+pub const POINT_UNIT: GLuint = 12;
+
+/// Near plane of the spot and point light projections
+const LIGHT_NEAR: f32 = 0.05;
+
+/// How the shadow distance is divided between cascades
+#[derive(Debug, Clone, PartialEq)]
+pub enum CascadeSplit {
+    /// Equal depth ranges
+    Uniform,
+    /// Ranges growing geometrically, matching perspective aliasing
+    Logarithmic,
+    /// Blend between uniform (0) and logarithmic (1) splits
+    Practical(f32),
+    /// Far end of each cascade as a fraction of the shadow distance
+    Manual(Vec<f32>),
+}
+
+impl CascadeSplit {
+    /// View depth of the far end of each of `count` cascades covering `near..far`
+    pub fn far_distances(&self, near: f32, far: f32, count: usize) -> Vec<f32> {
+        let uniform = |i: usize| near + (far - near) * i as f32 / count as f32;
+        let logarithmic = |i: usize| near * (far / near).powf(i as f32 / count as f32);
+
+        (1..=count)
+            .map(|i| match self {
+                CascadeSplit::Uniform => uniform(i),
+                CascadeSplit::Logarithmic => logarithmic(i),
+                CascadeSplit::Practical(lambda) => {
+                    lambda * logarithmic(i) + (1.0 - lambda) * uniform(i)
+                }
+                CascadeSplit::Manual(fractions) => {
+                    let fraction = if i == count {
+                        1.0
+                    } else {
+                        fractions.get(i - 1).copied().unwrap_or(1.0)
+                    };
+                    (far * fraction).max(near)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Depth offsets fighting shadow acne
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowBias {
+    /// `glPolygonOffset` units applied while rendering the depth maps;
+    /// the point light shader adds the same offset to its linear depth
+    pub constant: f32,
+    /// `glPolygonOffset` factor, scaled by the depth slope of each triangle
+    pub slope: f32,
+    /// World space offset along the surface normal when sampling
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowBias {
+    fn default() -> Self {
+        ShadowBias {
+            constant: 2.0,
+            slope: 2.5,
+            normal_offset: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowSettings {
+    /// Cascades of the directional light, up to `MAX_CASCADES`
+    pub cascade_count: usize,
+    pub cascade_size: u32,
+    pub split: CascadeSplit,
+    /// View distance covered by the cascades, and range limit of the other lights
+    pub max_distance: f32,
+    /// Distance behind each cascade from which objects still cast shadows into it
+    pub caster_margin: f32,
+    pub spot_size: u32,
+    /// Face size of the point light cube maps
+    pub point_size: u32,
+    pub bias: ShadowBias,
+    /// Kernel of `(2 * pcf_radius + 1)^2` texels, 0 for a single hardware filtered tap
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            cascade_count: 4,
+            cascade_size: 2048,
+            split: CascadeSplit::Practical(0.75),
+            max_distance: 100.0,
+            caster_margin: 50.0,
+            spot_size: 1024,
+            point_size: 512,
+            bias: ShadowBias::default(),
+            pcf_radius: 1,
+        }
+    }
+}
+
+/// Shadow slot of each light, -1 for none: 0 for the first shadow casting
+/// directional light (which owns the cascades), then the layer of each spot
+/// light and the cube of each point light, in order, up to the per-kind limits
+pub fn shadow_slots(lights: &[Light]) -> Vec<i32> {
+    let mut directional = false;
+    let mut spots = 0;
+    let mut points = 0;
+    lights
+        .iter()
+        .map(|light| {
+            if !light.casts_shadows() {
+                return -1;
+            }
+            match light {
+                Light::Directional { .. } if !directional => {
+                    directional = true;
+                    0
+                }
+                Light::Spot { .. } if spots < MAX_SPOT_SHADOWS => {
+                    spots += 1;
+                    spots as i32 - 1
+                }
+                Light::Point { .. } if points < MAX_POINT_SHADOWS => {
+                    points += 1;
+                    points as i32 - 1
+                }
+                _ => -1,
+            }
+        })
+        .collect()
+}
+
+/// Depth maps of the shadow casting lights: cascades in a 2D array for the
+/// directional light, one 2D array layer per spot light and one cube of a
+/// cube map array per point light
+pub struct ShadowMaps {
+    gl: gl::Gl,
+    settings: ShadowSettings,
+    depth_program: Program,
+    /// Writes the distance to the light, see `shadow_point_depth.frag`
+    point_depth_program: Program,
+    debug_program: Program,
+    framebuffer: GLuint,
+    empty: VertexArray,
+    cascades: TextureArray,
+    spots: TextureArray,
+    points: TextureArray,
+    cascade_matrices: Vec<Mat4>,
+    cascade_splits: Vec<f32>,
+    spot_matrices: Vec<Mat4>,
+    point_far: Vec<f32>,
+}
+
+impl ShadowMaps {
+    pub fn new(gl: &gl::Gl, res: &Resources, mut settings: ShadowSettings) -> Result<Self, Error> {
+        settings.cascade_count = settings.cascade_count.clamp(1, MAX_CASCADES);
+        let load = |names: &[&str]| Program::from_resource_files(gl, res, names);
+        let depth_program = load(&["shadow_depth.vert", "shadow_depth.frag"])?;
+        let point_depth_program = load(&["shadow_depth.vert", "shadow_point_depth.frag"])?;
+        let debug_program = load(&["shadow_debug.vert", "shadow_debug.frag"])?;
+        debug_program.set_uniform("u_maps", &0);
+        debug_program.set_uniform("u_cubes", &1);
+
+        let mut framebuffer: GLuint = 0;
+        unsafe {
+            gl.GenFramebuffers(1, &mut framebuffer);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl.DrawBuffer(gl::NONE);
+            gl.ReadBuffer(gl::NONE);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let (cascades, spots, points) = allocate_maps(gl, &settings);
+        Ok(ShadowMaps {
+            gl: gl.clone(),
+            settings,
+            depth_program,
+            point_depth_program,
+            debug_program,
+            framebuffer,
+            empty: VertexArray::new(gl),
+            cascades,
+            spots,
+            points,
+            cascade_matrices: Vec::new(),
+            cascade_splits: Vec::new(),
+            spot_matrices: Vec::new(),
+            point_far: Vec::new(),
+        })
+    }
+
+    pub fn settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    /// Replaces the settings, reallocating the maps if their sizes changed
+    pub fn set_settings(&mut self, mut settings: ShadowSettings) {
+        settings.cascade_count = settings.cascade_count.clamp(1, MAX_CASCADES);
+        let resized = settings.cascade_size != self.settings.cascade_size
+            || settings.spot_size != self.settings.spot_size
+            || settings.point_size != self.settings.point_size;
+        if resized {
+            let (cascades, spots, points) = allocate_maps(&self.gl, &settings);
+            self.cascades = cascades;
+            self.spots = spots;
+            self.points = points;
+        }
+        self.settings = settings;
+    }
+
+    pub fn cascades(&self) -> &TextureArray {
+        &self.cascades
+    }
+
+    pub fn spot_maps(&self) -> &TextureArray {
+        &self.spots
+    }
+
+    pub fn point_maps(&self) -> &TextureArray {
+        &self.points
+    }
+
+    /// View depth of the far end of each cascade, as of the last `render`
+    pub fn cascade_splits(&self) -> &[f32] {
+        &self.cascade_splits
+    }
+
+    /// World to light clip space matrix of each cascade, as of the last `render`
+    pub fn cascade_matrices(&self) -> &[Mat4] {
+        &self.cascade_matrices
+    }
+
+    /// Renders the depth maps of the lights given slots by `shadow_slots`.
+    /// `draw_casters` runs once per map with the depth program of the light
+    /// in use; it should set `u_model` and draw every shadow casting mesh.
+    /// The framebuffer and viewport are restored afterwards.
+    pub fn render<F>(
+        &mut self,
+        cache: &mut StateCache,
+        lights: &[Light],
+        camera: &Camera,
+        mut draw_casters: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Program),
+    {
+        self.cascade_matrices.clear();
+        self.cascade_splits.clear();
+        self.spot_matrices.clear();
+        self.point_far.clear();
+
+        let mut previous_framebuffer: GLint = 0;
+        let mut previous_viewport: [GLint; 4] = [0; 4];
+        unsafe {
+            self.gl
+                .GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+            self.gl.GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+        let bias = self.settings.bias;
+        cache.apply(&RenderState {
+            depth_test: Some(CompareFunc::Less),
+            color_mask: [false; 4],
+            polygon_offset: Some(PolygonOffset {
+                factor: bias.slope,
+                units: bias.constant,
+            }),
+            ..RenderState::default()
+        });
+        self.point_depth_program.set_used();
+        self.point_depth_program.set_uniform("u_bias_slope", &bias.slope);
+        self.point_depth_program.set_uniform("u_bias_constant", &bias.constant);
+
+        let result = self.render_lights(lights, camera, &mut draw_casters);
+
+        unsafe {
+            self.gl
+                .BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+            let [x, y, w, h] = previous_viewport;
+            self.gl.Viewport(x, y, w, h);
+        }
+        result
+    }
+
+    fn render_lights<F>(&mut self, lights: &[Light], camera: &Camera, draw_casters: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(&Program),
+    {
+        for (light, slot) in lights.iter().zip(shadow_slots(lights)) {
+            if slot < 0 {
+                continue;
+            }
+
+            match *light {
+                Light::Directional { direction, .. } => {
+                    let (near, far) = match camera.projection {
+                        Projection::Perspective { near, far, .. }
+                        | Projection::Orthographic { near, far, .. } => {
+                            (near, far.min(self.settings.max_distance))
+                        }
+                    };
+                    self.cascade_splits =
+                        self.settings
+                            .split
+                            .far_distances(near, far, self.settings.cascade_count);
+
+                    self.depth_program.set_used();
+                    let mut start = near;
+                    for (layer, &end) in self.cascade_splits.iter().enumerate() {
+                        let matrix = cascade_matrix(
+                            camera,
+                            start,
+                            end,
+                            direction,
+                            self.settings.cascade_size,
+                            self.settings.caster_margin,
+                        );
+                        self.cascade_matrices.push(matrix);
+                        start = end;
+
+                        self.attach(&self.cascades, layer as u32)?;
+                        self.depth_program.set_uniform("u_light_view_projection", &matrix);
+                        draw_casters(&self.depth_program);
+                    }
+                }
+                Light::Spot {
+                    position,
+                    direction,
+                    attenuation,
+                    outer_angle,
+                    ..
+                } => {
+                    let far = attenuation.range().min(self.settings.max_distance);
+                    let fov = (2.0 * outer_angle).min(radians(170.0));
+                    let view = Mat4::look_at(position, position + direction, up_for(direction));
+                    let matrix = Mat4::perspective(fov, 1.0, LIGHT_NEAR, far) * view;
+                    self.spot_matrices.push(matrix);
+
+                    self.depth_program.set_used();
+                    self.attach(&self.spots, slot as u32)?;
+                    self.depth_program.set_uniform("u_light_view_projection", &matrix);
+                    draw_casters(&self.depth_program);
+                }
+                Light::Point {
+                    position,
+                    attenuation,
+                    ..
+                } => {
+                    const VIEWS: [([f32; 3], [f32; 3]); 6] = [
+                        ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+                        ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+                        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+                        ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+                        ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+                        ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+                    ];
+                    let far = attenuation.range().min(self.settings.max_distance);
+                    let projection = Mat4::perspective(radians(90.0), 1.0, LIGHT_NEAR, far);
+                    self.point_far.push(far);
+
+                    let program = &self.point_depth_program;
+                    program.set_used();
+                    program.set_uniform("u_light_position", &position);
+                    program.set_uniform("u_far", &far);
+                    for (face, (forward, up)) in VIEWS.iter().enumerate() {
+                        let view = Mat4::look_at(
+                            position,
+                            position + Vec3::from(*forward),
+                            Vec3::from(*up),
+                        );
+                        self.attach(&self.points, slot as u32 * 6 + face as u32)?;
+                        program.set_uniform("u_light_view_projection", &(projection * view));
+                        draw_casters(program);
+                    }
+                }
+                Light::Ambient { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Attaches `layer` of `target`, clears it and sets the viewport to its size
+    fn attach(&self, target: &TextureArray, layer: u32) -> Result<(), Error> {
+        unsafe {
+            self.gl.FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                target.id(),
+                0,
+                layer as GLint,
+            );
+            if let Some(inner) = framebuffer::Error::from_status(self.gl.CheckFramebufferStatus(gl::FRAMEBUFFER)) {
+                return Err(Error::Framebuffer { inner });
+            }
+            self.gl
+                .Viewport(0, 0, target.size() as GLsizei, target.size() as GLsizei);
+            self.gl.Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        Ok(())
+    }
+
+    /// Binds the maps to `CASCADE_UNIT`, `SPOT_UNIT` and `POINT_UNIT`
+    pub fn bind(&self) {
+        self.cascades.bind(CASCADE_UNIT);
+        self.spots.bind(SPOT_UNIT);
+        self.points.bind(POINT_UNIT);
+    }
+
+    /// Enables the shadow lookups of `shaders/shadows.glsl` in `program`
+    /// with the matrices of the last `render`
+    pub fn set_uniforms(&self, program: &Program) {
+        program.set_uniform("u_cascade_maps", &(CASCADE_UNIT as i32));
+        program.set_uniform("u_spot_shadow_maps", &(SPOT_UNIT as i32));
+        program.set_uniform("u_point_shadow_maps", &(POINT_UNIT as i32));
+        program.set_uniform("u_shadows_enabled", &true);
+        program.set_uniform("u_pcf_radius", &(self.settings.pcf_radius as i32));
+        program.set_uniform("u_shadow_normal_offset", &self.settings.bias.normal_offset);
+
+        program.set_uniform("u_cascade_count", &(self.cascade_matrices.len() as i32));
+        if !self.cascade_matrices.is_empty() {
+            program.set_uniform("u_cascade_matrices", self.cascade_matrices.as_slice());
+            program.set_uniform("u_cascade_splits", self.cascade_splits.as_slice());
+        }
+        if !self.spot_matrices.is_empty() {
+            program.set_uniform("u_spot_shadow_matrices", self.spot_matrices.as_slice());
+        }
+        if !self.point_far.is_empty() {
+            program.set_uniform("u_point_shadow_far", self.point_far.as_slice());
+        }
+    }
+
+    /// Draws the maps rendered by the last `render` as grey tiles along the
+    /// bottom of the default framebuffer: the cascades, the spot maps, then
+    /// the six faces of each point light cube
+    pub fn draw_debug(&self, cache: &mut StateCache, window_size: (u32, u32)) {
+        let (width, height) = window_size;
+        let tile = (height / 5).max(1);
+        let columns = (width / tile).max(1);
+
+        let mut tiles: Vec<(&TextureArray, i32, i32)> = Vec::new();
+        tiles.extend((0..self.cascade_matrices.len()).map(|layer| (&self.cascades, layer as i32, 0)));
+        tiles.extend((0..self.spot_matrices.len()).map(|layer| (&self.spots, layer as i32, 0)));
+        for cube in 0..self.point_far.len() {
+            tiles.extend((0..6).map(|face| (&self.points, cube as i32, face)));
+        }
+
+        cache.apply(&RenderState::overlay());
+        unsafe {
+            self.gl.Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+        for array in [&self.cascades, &self.spots, &self.points] {
+            array.set_depth_compare(false);
+        }
+        self.debug_program.set_used();
+        self.empty.bind();
+
+        let tile_w = 2.0 * tile as f32 / width as f32;
+        let tile_h = 2.0 * tile as f32 / height as f32;
+        for (index, (array, layer, face)) in tiles.into_iter().enumerate() {
+            let column = index as u32 % columns;
+            let row = index as u32 / columns;
+            let rect = [
+                -1.0 + column as f32 * tile_w,
+                -1.0 + row as f32 * tile_h,
+                tile_w,
+                tile_h,
+            ];
+
+            let cube = array.target() == gl::TEXTURE_CUBE_MAP_ARRAY;
+            array.bind(if cube { 1 } else { 0 });
+            self.debug_program.set_uniform("u_mode", &(cube as i32));
+            self.debug_program.set_uniform("u_layer", &layer);
+            self.debug_program.set_uniform("u_face", &face);
+            self.debug_program.set_uniform("u_rect", &rect);
+            unsafe { self.gl.DrawArrays(gl::TRIANGLE_STRIP, 0, 4) };
+        }
+
+        self.empty.unbind();
+        for array in [&self.cascades, &self.spots, &self.points] {
+            array.set_depth_compare(true);
+        }
+    }
+}
+
+impl Drop for ShadowMaps {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteFramebuffers(1, &self.framebuffer) };
+    }
+}
+
+/// Depth arrays for the cascades, spot lights and point lights, with depth
+/// comparison enabled
+fn allocate_maps(gl: &gl::Gl, settings: &ShadowSettings) -> (TextureArray, TextureArray, TextureArray) {
+    let cascades = TextureArray::new_2d(gl, settings.cascade_size, MAX_CASCADES as u32, TextureFormat::DEPTH32F);
+    let spots = TextureArray::new_2d(gl, settings.spot_size, MAX_SPOT_SHADOWS as u32, TextureFormat::DEPTH32F);
+    let points = TextureArray::new_cube(gl, settings.point_size, MAX_POINT_SHADOWS as u32, TextureFormat::DEPTH32F);
+    for array in [&cascades, &spots, &points] {
+        array.set_depth_compare(true);
+    }
+    (cascades, spots, points)
+}
+
+/// Up vector for a view looking along `direction`
+fn up_for(direction: Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    }
+}
+
+/// Orthographic light matrix enclosing the slice `near..far` of the camera
+/// frustum. The slice is bounded by a sphere and the projection snapped to
+/// whole texels, so the shadow edges stay still while the camera moves.
+fn cascade_matrix(camera: &Camera, near: f32, far: f32, direction: Vec3, size: u32, margin: f32) -> Mat4 {
+    let forward = camera.forward();
+    let right = camera.right();
+    let up = camera.up();
+    let half_height = |distance: f32| match camera.projection {
+        Projection::Perspective { fov_y, .. } => distance * (fov_y / 2.0).tan(),
+        Projection::Orthographic { height, .. } => height / 2.0,
+    };
+
+    let mut corners = Vec::with_capacity(8);
+    for distance in [near, far] {
+        let half_h = half_height(distance);
+        let half_w = half_h * camera.aspect();
+        let center = camera.position + forward * distance;
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            corners.push(center + right * (x * half_w) + up * (y * half_h));
+        }
+    }
+
+    let center = corners.iter().fold(Vec3::ZERO, |sum, &c| sum + c) / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|&c| c.distance(center))
+        .fold(0.0f32, f32::max);
+    // Quantized so the projection size does not flicker with rounding errors
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let eye = center - direction * (radius + margin);
+    let view = Mat4::look_at(eye, center, up_for(direction));
+    let projection = Mat4::orthographic(-radius, radius, -radius, radius, 0.0, 2.0 * radius + margin);
+    let matrix = projection * view;
+
+    let texels = size as f32 / 2.0;
+    let origin = matrix.transform_point3(Vec3::ZERO);
+    let offset = Vec3::new(
+        ((origin.x * texels).round() - origin.x * texels) / texels,
+        ((origin.y * texels).round() - origin.y * texels) / texels,
+        0.0,
+    );
+    Mat4::from_translation(offset) * matrix
+}
@@ -0,0 +1,443 @@
+use crate::buffer::{ArrayBuffer, ElementArrayBuffer, VertexArray};
+use crate::image::ImageBuffer;
+use crate::math::{Mat4, Vec2, Vec4};
+use crate::packer;
+use crate::program;
+use crate::render_state::{RenderState, StateCache};
+use crate::resources::{self, Resources};
+use crate::texture::Texture2D;
+use crate::Program;
+use gl::types::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to parse atlas {}: {}", name, message)]
+    Parse { name: String, message: String },
+    #[fail(display = "Failed to decode image {}: {}", name, message)]
+    Image { name: String, message: String },
+    #[fail(display = "Images do not fit in a {0}x{0} atlas", max_size)]
+    AtlasFull { max_size: u32 },
+    #[fail(display = "Failed to load sprite program: {}", inner)]
+    Program { inner: program::Error },
+}
+
+impl From<program::Error> for Error {
+    fn from(inner: program::Error) -> Self {
+        Error::Program { inner }
+    }
+}
+
+/// Image extensions picked up when packing a directory
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+/// Pixel rectangle of an atlas, y down from the top row of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Atlas description written next to the packed image, e.g. by `build.rs`:
+/// ```text
+/// (
+///     image: "sprites.png",
+///     regions: {
+///         "player": (x: 0, y: 0, width: 32, height: 48),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+struct AtlasDesc {
+    image: String,
+    regions: BTreeMap<String, AtlasRegion>,
+}
+
+/// Texture holding many images, addressed by name
+pub struct TextureAtlas {
+    texture: Rc<Texture2D>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    /// Loads an atlas description (`*.atlas.ron`) and its image.
+    /// `build.rs` generates one for every `shaders/<name>.atlas/` directory.
+    pub fn from_resources(gl: &gl::Gl, res: &Resources, name: &str) -> Result<Self, Error> {
+        let source = res.load(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        let desc: AtlasDesc = ron::from_str(&source.to_string_lossy()).map_err(|e| Error::Parse {
+            name: name.to_string(),
+            message: e.to_string(),
+        })?;
+
+        let image_name = resources::sibling_name(name, &desc.image);
+        let image = load_image(res, &image_name)?;
+        Ok(TextureAtlas {
+            texture: Rc::new(atlas_texture(gl, &image)),
+            regions: desc.regions.into_iter().collect(),
+        })
+    }
+
+    /// Packs every image in the resource directory `dir` at runtime,
+    /// naming regions after the file stems
+    pub fn from_directory(
+        gl: &gl::Gl,
+        res: &Resources,
+        dir: &str,
+        max_size: u32,
+        padding: u32,
+    ) -> Result<Self, Error> {
+        let names = res.list(dir).map_err(|e| Error::ResourceLoad {
+            name: dir.to_string(),
+            inner: e,
+        })?;
+
+        let mut images = Vec::new();
+        for name in names {
+            let path = Path::new(&name);
+            let is_image = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()));
+            if is_image {
+                let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+                images.push((stem, load_image(res, &name)?));
+            }
+        }
+        TextureAtlas::pack(gl, images, max_size, padding)
+    }
+
+    /// Packs named RGBA images (rows top-down) into one texture
+    pub fn pack(
+        gl: &gl::Gl,
+        images: Vec<(String, ImageBuffer<u8>)>,
+        max_size: u32,
+        padding: u32,
+    ) -> Result<Self, Error> {
+        let sizes: Vec<(u32, u32)> = images
+            .iter()
+            .map(|(_, image)| (image.width, image.height))
+            .collect();
+        let packing =
+            packer::pack(&sizes, max_size, padding).ok_or(Error::AtlasFull { max_size })?;
+
+        let mut atlas = ImageBuffer::new(packing.width, packing.height, 4);
+        let mut regions = HashMap::new();
+        for ((name, image), (x, y)) in images.into_iter().zip(packing.positions) {
+            for row in 0..image.height {
+                for column in 0..image.width {
+                    atlas
+                        .pixel_mut(x + column, y + row)
+                        .copy_from_slice(image.pixel(column, row));
+                }
+            }
+            regions.insert(
+                name,
+                AtlasRegion {
+                    x,
+                    y,
+                    width: image.width,
+                    height: image.height,
+                },
+            );
+        }
+
+        Ok(TextureAtlas {
+            texture: Rc::new(atlas_texture(gl, &atlas)),
+            regions,
+        })
+    }
+
+    pub fn texture(&self) -> &Rc<Texture2D> {
+        &self.texture
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+
+    pub fn region_names(&self) -> impl Iterator<Item = &str> {
+        self.regions.keys().map(String::as_str)
+    }
+
+    /// Texture coordinates of the top-left and bottom-right corners of `region`
+    pub fn uv(&self, region: AtlasRegion) -> (Vec2, Vec2) {
+        let (width, height) = (self.texture.width() as f32, self.texture.height() as f32);
+        let min = Vec2::new(region.x as f32 / width, region.y as f32 / height);
+        let max = Vec2::new(
+            (region.x + region.width) as f32 / width,
+            (region.y + region.height) as f32 / height,
+        );
+        (min, max)
+    }
+
+    /// Sprite of region `name` at its pixel size, `None` if there is no such region
+    pub fn sprite(&self, name: &str) -> Option<Sprite> {
+        let region = self.region(name)?;
+        let (uv_min, uv_max) = self.uv(region);
+        Some(Sprite {
+            size: Vec2::new(region.width as f32, region.height as f32),
+            uv_min,
+            uv_max,
+            ..Sprite::default()
+        })
+    }
+}
+
+fn load_image(res: &Resources, name: &str) -> Result<ImageBuffer<u8>, Error> {
+    let bytes = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+        name: name.to_string(),
+        inner: e,
+    })?;
+    ImageBuffer::decode(&bytes).map_err(|message| Error::Image {
+        name: name.to_string(),
+        message,
+    })
+}
+
+/// Uploads an atlas without flipping, so `v` grows downwards like the region
+/// rows; clamped and without mipmaps to keep neighbours from bleeding in
+fn atlas_texture(gl: &gl::Gl, image: &ImageBuffer<u8>) -> Texture2D {
+    let texture = Texture2D::from_image_without_mipmaps(gl, image);
+    texture.set_wrap(gl::CLAMP_TO_EDGE, gl::CLAMP_TO_EDGE);
+    texture
+}
+
+/// Textured, tinted and rotated quad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// Position of the pivot
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Pivot as a fraction of the size, `(0, 0)` being the top-left corner
+    pub origin: Vec2,
+    /// Radians, clockwise on screen (y down)
+    pub rotation: f32,
+    pub color: Vec4,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    /// Lower layers are drawn first
+    pub layer: i32,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite {
+            position: Vec2::ZERO,
+            size: Vec2::ONE,
+            origin: Vec2::ZERO,
+            rotation: 0.0,
+            color: Vec4::ONE,
+            uv_min: Vec2::ZERO,
+            uv_max: Vec2::ONE,
+            layer: 0,
+        }
+    }
+}
+
+/// Interleaved sprite vertex
+/// # Attributes
+/// * `layout (location = 0)` - position
+/// * `layout (location = 1)` - texture coordinates
+/// * `layout (location = 2)` - colour
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpriteVertex {
+    position: Vec2,
+    uv: Vec2,
+    color: Vec4,
+}
+
+/// Collects sprites and draws them sorted by layer, then texture.
+///
+/// Sprites are expanded into one streaming vertex buffer per flush and every
+/// run of sprites sharing a texture becomes a single draw call. Submission
+/// order is kept between sprites of the same layer and texture only.
+pub struct SpriteBatch {
+    gl: gl::Gl,
+    program: Program,
+    vao: VertexArray,
+    vbo: ArrayBuffer,
+    ebo: ElementArrayBuffer,
+    /// Sprites the index buffer has room for
+    capacity: usize,
+    sprites: Vec<(Rc<Texture2D>, Sprite)>,
+    /// Render state of every draw, alpha blended by default
+    pub state: RenderState,
+}
+
+impl SpriteBatch {
+    pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Self, Error> {
+        let program = Program::from_resources(gl, res, "sprite")?;
+        program.set_uniform("u_texture", &0);
+
+        let vbo = ArrayBuffer::new(gl);
+        let vao = VertexArray::new(gl);
+        vao.bind();
+        vbo.bind();
+        let stride = std::mem::size_of::<SpriteVertex>();
+        let attributes: [(GLuint, GLint, usize); 3] = [
+            (0, 2, std::mem::offset_of!(SpriteVertex, position)),
+            (1, 2, std::mem::offset_of!(SpriteVertex, uv)),
+            (2, 4, std::mem::offset_of!(SpriteVertex, color)),
+        ];
+        for (location, size, offset) in attributes {
+            unsafe {
+                gl.EnableVertexAttribArray(location);
+                gl.VertexAttribPointer(
+                    location,
+                    size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as GLsizei,
+                    offset as *const GLvoid,
+                );
+            }
+        }
+        // The element buffer binding is recorded in the VAO
+        let ebo = ElementArrayBuffer::new(gl);
+        ebo.bind();
+        vao.unbind();
+        vbo.unbind();
+        ebo.unbind();
+
+        Ok(SpriteBatch {
+            gl: gl.clone(),
+            program,
+            vao,
+            vbo,
+            ebo,
+            capacity: 0,
+            sprites: Vec::new(),
+            state: RenderState::overlay(),
+        })
+    }
+
+    /// Projection for sprites positioned in pixels, origin at the top-left
+    pub fn screen_projection(width: u32, height: u32) -> Mat4 {
+        Mat4::orthographic(0.0, width as f32, height as f32, 0.0, -1.0, 1.0)
+    }
+
+    /// Queues `sprite` textured with `texture`
+    pub fn draw(&mut self, texture: &Rc<Texture2D>, sprite: Sprite) {
+        self.sprites.push((texture.clone(), sprite));
+    }
+
+    /// Queues `sprite` with the texture and coordinates of atlas region `name`.
+    /// Returns false if the atlas has no such region.
+    pub fn draw_region(&mut self, atlas: &TextureAtlas, name: &str, sprite: Sprite) -> bool {
+        match atlas.region(name) {
+            Some(region) => {
+                let (uv_min, uv_max) = atlas.uv(region);
+                self.draw(atlas.texture(), Sprite { uv_min, uv_max, ..sprite });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of sprites waiting for `flush`
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Draws and clears the queued sprites. Returns the number of draw calls.
+    pub fn flush(&mut self, cache: &mut StateCache, projection: &Mat4) -> usize {
+        if self.sprites.is_empty() {
+            return 0;
+        }
+        self.sprites
+            .sort_by_key(|(texture, sprite)| (sprite.layer, texture.id()));
+
+        let mut vertices = Vec::with_capacity(self.sprites.len() * 4);
+        for (_, sprite) in &self.sprites {
+            push_quad(&mut vertices, sprite);
+        }
+        self.reserve(self.sprites.len());
+
+        cache.apply(&self.state);
+        self.program.set_used();
+        self.program.set_uniform("u_projection", projection);
+        self.vao.bind();
+        self.vbo.bind();
+        // Respecifying the whole store lets the driver orphan last frame's
+        self.vbo.dynamic_draw_data(&vertices);
+
+        let mut draws = 0;
+        let mut start = 0;
+        while start < self.sprites.len() {
+            let texture = &self.sprites[start].0;
+            let end = start
+                + self.sprites[start..]
+                    .iter()
+                    .take_while(|(other, _)| Rc::ptr_eq(other, texture))
+                    .count();
+
+            texture.bind(0);
+            unsafe {
+                self.gl.DrawElements(
+                    gl::TRIANGLES,
+                    ((end - start) * 6) as GLsizei,
+                    gl::UNSIGNED_INT,
+                    (start * 6 * std::mem::size_of::<u32>()) as *const GLvoid,
+                );
+            }
+            draws += 1;
+            start = end;
+        }
+
+        self.vao.unbind();
+        self.vbo.unbind();
+        self.sprites.clear();
+        draws
+    }
+
+    /// Grows the shared quad index buffer to cover `sprites` sprites
+    fn reserve(&mut self, sprites: usize) {
+        if sprites <= self.capacity {
+            return;
+        }
+        self.capacity = sprites.next_power_of_two();
+        let indices: Vec<u32> = (0..self.capacity as u32)
+            .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+            .collect();
+        self.vao.bind();
+        self.ebo.bind();
+        self.ebo.static_draw_data(&indices);
+        self.vao.unbind();
+        self.ebo.unbind();
+    }
+}
+
+/// Appends the four corners of `sprite`: top-left, top-right, bottom-right, bottom-left
+fn push_quad(vertices: &mut Vec<SpriteVertex>, sprite: &Sprite) {
+    let (sin, cos) = sprite.rotation.sin_cos();
+    let corners = [
+        (Vec2::new(0.0, 0.0), Vec2::new(sprite.uv_min.x, sprite.uv_min.y)),
+        (Vec2::new(1.0, 0.0), Vec2::new(sprite.uv_max.x, sprite.uv_min.y)),
+        (Vec2::new(1.0, 1.0), Vec2::new(sprite.uv_max.x, sprite.uv_max.y)),
+        (Vec2::new(0.0, 1.0), Vec2::new(sprite.uv_min.x, sprite.uv_max.y)),
+    ];
+    for (corner, uv) in corners {
+        let local = (corner - sprite.origin) * sprite.size;
+        let rotated = Vec2::new(local.x * cos - local.y * sin, local.x * sin + local.y * cos);
+        vertices.push(SpriteVertex {
+            position: sprite.position + rotated,
+            uv,
+            color: sprite.color,
+        });
+    }
+}
@@ -0,0 +1,599 @@
+use crate::buffer::{ArrayBuffer, VertexArray};
+use crate::camera::Camera;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::program;
+use crate::render_state::{RenderState, StateCache};
+use crate::resources::{self, Resources};
+use crate::texture::{Texture2D, TextureFormat};
+use crate::Program;
+use gl::types::*;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource: {}", name)]
+    ResourceLoad { name: String, inner: resources::Error },
+    #[fail(display = "Failed to parse font {}: {}", name, message)]
+    Font { name: String, message: String },
+    #[fail(display = "Failed to load text program: {}", inner)]
+    Program { inner: program::Error },
+}
+
+impl From<program::Error> for Error {
+    fn from(inner: program::Error) -> Self {
+        Error::Program { inner }
+    }
+}
+
+/// Width and height of a font's glyph atlas
+const ATLAS_SIZE: u32 = 1024;
+/// Empty texels kept around each glyph in the atlas
+const ATLAS_PADDING: u32 = 1;
+
+/// How glyphs are stored in the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphMode {
+    /// Coverage at the rasterized size, sharpest when drawn at that size
+    Bitmap,
+    /// Signed distance to the outline, clamped at `spread` texels, which
+    /// stays sharp when scaled up or transformed in world space
+    Sdf { spread: u32 },
+}
+
+/// Horizontal alignment of the lines of a block of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// How a string is laid out and coloured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Em size in pixels
+    pub size: f32,
+    pub color: Vec4,
+    /// Lines are wrapped at word boundaries, or mid-word for long words,
+    /// to stay narrower than this many pixels
+    pub max_width: Option<f32>,
+    /// Multiplier of the font's line height
+    pub line_spacing: f32,
+    pub align: Align,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            size: 16.0,
+            color: Vec4::ONE,
+            max_width: None,
+            line_spacing: 1.0,
+            align: Align::Left,
+        }
+    }
+}
+
+/// Atlas rectangle of a rasterized glyph
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphQuad {
+    uv_min: Vec2,
+    uv_max: Vec2,
+    /// Top-left corner relative to the pen on the baseline, y down, in raster pixels
+    offset: Vec2,
+    size: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Glyph {
+    /// In raster pixels
+    advance: f32,
+    /// `None` for blank glyphs and glyphs that did not fit in the atlas
+    quad: Option<GlyphQuad>,
+}
+
+/// Glyph placed by `Font::layout`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub character: char,
+    /// Corners in pixels, relative to the top-left of the text block with y down
+    pub min: Vec2,
+    pub max: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+/// Result of laying out a string
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Size of the text block in pixels
+    pub size: Vec2,
+    pub lines: usize,
+}
+
+/// TTF/OTF font rasterized on demand into a single-channel glyph atlas
+pub struct Font {
+    font: fontdue::Font,
+    size: f32,
+    mode: GlyphMode,
+    atlas: Rc<Texture2D>,
+    glyphs: HashMap<char, Glyph>,
+    /// Shelf packer state: pen position and height of the current row
+    cursor: (u32, u32),
+    row_height: u32,
+}
+
+impl Font {
+    /// Parses `bytes`, rasterizing glyphs at `size` pixels per em.
+    /// SDF fonts can be drawn at any size, so a size around 48 is plenty.
+    pub fn from_bytes(
+        gl: &gl::Gl,
+        name: &str,
+        bytes: &[u8],
+        size: f32,
+        mode: GlyphMode,
+    ) -> Result<Self, Error> {
+        let settings = fontdue::FontSettings {
+            scale: size,
+            ..fontdue::FontSettings::default()
+        };
+        let font = fontdue::Font::from_bytes(bytes, settings).map_err(|message| Error::Font {
+            name: name.to_string(),
+            message: message.to_string(),
+        })?;
+
+        let atlas = Texture2D::new(gl, ATLAS_SIZE, ATLAS_SIZE, TextureFormat::R8);
+        atlas.upload_region(
+            0,
+            0,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+            &vec![0; (ATLAS_SIZE * ATLAS_SIZE) as usize],
+        );
+
+        Ok(Font {
+            font,
+            size,
+            mode,
+            atlas: Rc::new(atlas),
+            glyphs: HashMap::new(),
+            cursor: (ATLAS_PADDING, ATLAS_PADDING),
+            row_height: 0,
+        })
+    }
+
+    pub fn from_resources(
+        gl: &gl::Gl,
+        res: &Resources,
+        name: &str,
+        size: f32,
+        mode: GlyphMode,
+    ) -> Result<Self, Error> {
+        let bytes = res.load_bytes(name).map_err(|e| Error::ResourceLoad {
+            name: name.to_string(),
+            inner: e,
+        })?;
+        Font::from_bytes(gl, name, &bytes, size, mode)
+    }
+
+    /// Pixels per em glyphs are rasterized at
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn mode(&self) -> GlyphMode {
+        self.mode
+    }
+
+    pub fn atlas(&self) -> &Texture2D {
+        &self.atlas
+    }
+
+    /// Distance between baselines at `size` pixels per em
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.font
+            .horizontal_line_metrics(size)
+            .map_or(size * 1.2, |metrics| metrics.new_line_size)
+    }
+
+    /// Size of the block `text` would take up
+    pub fn measure(&mut self, text: &str, style: &TextStyle) -> Vec2 {
+        self.layout(text, style).size
+    }
+
+    /// Positions the glyphs of `text`, applying kerning, line breaks on `\n`
+    /// and wrapping at `style.max_width`. Glyphs missing from the atlas are
+    /// rasterized.
+    pub fn layout(&mut self, text: &str, style: &TextStyle) -> TextLayout {
+        let scale = style.size / self.size;
+        let ascent = self
+            .font
+            .horizontal_line_metrics(style.size)
+            .map_or(style.size * 0.8, |metrics| metrics.ascent);
+        let line_height = self.line_height(style.size) * style.line_spacing;
+
+        let mut lines: Vec<(Vec<char>, f32)> = Vec::new();
+        for paragraph in text.split('\n') {
+            let chars: Vec<char> = paragraph.trim_end_matches('\r').chars().collect();
+            for range in self.wrap(&chars, style) {
+                let line = chars[range].to_vec();
+                let width = self.line_width(&line, style);
+                lines.push((line, width));
+            }
+        }
+
+        let widest = lines.iter().fold(0.0f32, |widest, line| widest.max(line.1));
+        let block_width = style.max_width.unwrap_or(widest);
+
+        let mut glyphs = Vec::new();
+        for (index, (line, width)) in lines.iter().enumerate() {
+            let mut pen = Vec2::new(
+                match style.align {
+                    Align::Left => 0.0,
+                    Align::Center => (block_width - width) * 0.5,
+                    Align::Right => block_width - width,
+                },
+                ascent + index as f32 * line_height,
+            );
+            let mut previous = None;
+            for &character in line {
+                pen.x += self.kerning(previous, character, style.size);
+                let glyph = self.glyph(character);
+                if let Some(quad) = glyph.quad {
+                    let min = pen + quad.offset * scale;
+                    glyphs.push(PositionedGlyph {
+                        character,
+                        min,
+                        max: min + quad.size * scale,
+                        uv_min: quad.uv_min,
+                        uv_max: quad.uv_max,
+                    });
+                }
+                pen.x += glyph.advance * scale;
+                previous = Some(character);
+            }
+        }
+
+        TextLayout {
+            glyphs,
+            size: Vec2::new(block_width, lines.len() as f32 * line_height),
+            lines: lines.len(),
+        }
+    }
+
+    /// Splits a paragraph into lines no wider than `style.max_width`
+    fn wrap(&mut self, chars: &[char], style: &TextStyle) -> Vec<Range<usize>> {
+        let max_width = style.max_width.unwrap_or(f32::INFINITY);
+        let scale = style.size / self.size;
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut x = 0.0;
+        let mut previous = None;
+        // Index after the last whitespace, where the line may break
+        let mut last_break = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let character = chars[i];
+            let advance =
+                self.kerning(previous, character, style.size) + self.glyph(character).advance * scale;
+            if x + advance > max_width && i > start && !character.is_whitespace() {
+                let end = last_break.filter(|&end| end > start).unwrap_or(i);
+                lines.push(start..end);
+                start = end;
+                while start < chars.len() && chars[start].is_whitespace() {
+                    start += 1;
+                }
+                i = start;
+                x = 0.0;
+                previous = None;
+                last_break = None;
+                continue;
+            }
+
+            x += advance;
+            previous = Some(character);
+            if character.is_whitespace() {
+                last_break = Some(i + 1);
+            }
+            i += 1;
+        }
+        if start < chars.len() || lines.is_empty() {
+            lines.push(start..chars.len());
+        }
+        lines
+    }
+
+    /// Advance of `line` without trailing whitespace, used for alignment
+    fn line_width(&mut self, line: &[char], style: &TextStyle) -> f32 {
+        let scale = style.size / self.size;
+        let trimmed = line.len() - line.iter().rev().take_while(|c| c.is_whitespace()).count();
+        let mut width = 0.0;
+        let mut previous = None;
+        for &character in &line[..trimmed] {
+            width += self.kerning(previous, character, style.size) + self.glyph(character).advance * scale;
+            previous = Some(character);
+        }
+        width
+    }
+
+    fn kerning(&self, previous: Option<char>, character: char, size: f32) -> f32 {
+        previous
+            .and_then(|previous| self.font.horizontal_kern(previous, character, size))
+            .unwrap_or(0.0)
+    }
+
+    /// Cached glyph, rasterized into the atlas on first use
+    fn glyph(&mut self, character: char) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&character) {
+            return *glyph;
+        }
+
+        let (metrics, coverage) = self.font.rasterize(character, self.size);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+        let (pixels, padding) = match self.mode {
+            GlyphMode::Bitmap => (coverage, 0),
+            GlyphMode::Sdf { spread } => (distance_field(&coverage, width, height, spread), spread),
+        };
+
+        let quad = if width == 0 || height == 0 {
+            None
+        } else {
+            let (width, height) = (width + 2 * padding, height + 2 * padding);
+            self.allocate(width, height).map(|(x, y)| {
+                self.atlas.upload_region(x, y, width, height, &pixels);
+                let atlas_size = ATLAS_SIZE as f32;
+                GlyphQuad {
+                    uv_min: Vec2::new(x as f32, y as f32) / atlas_size,
+                    uv_max: Vec2::new((x + width) as f32, (y + height) as f32) / atlas_size,
+                    // Bitmap rows run top-down from ymin + height above the baseline
+                    offset: Vec2::new(
+                        metrics.xmin as f32 - padding as f32,
+                        -(metrics.ymin as f32 + metrics.height as f32) - padding as f32,
+                    ),
+                    size: Vec2::new(width as f32, height as f32),
+                }
+            })
+        };
+
+        let glyph = Glyph {
+            advance: metrics.advance_width,
+            quad,
+        };
+        self.glyphs.insert(character, glyph);
+        glyph
+    }
+
+    /// Finds room for a `width` x `height` block on the current shelf or a new one
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + width + ATLAS_PADDING > ATLAS_SIZE {
+            self.cursor = (ATLAS_PADDING, self.cursor.1 + self.row_height + ATLAS_PADDING);
+            self.row_height = 0;
+        }
+        if self.cursor.0 + width + ATLAS_PADDING > ATLAS_SIZE
+            || self.cursor.1 + height + ATLAS_PADDING > ATLAS_SIZE
+        {
+            return None;
+        }
+
+        let position = self.cursor;
+        self.cursor.0 += width + ATLAS_PADDING;
+        self.row_height = self.row_height.max(height);
+        Some(position)
+    }
+}
+
+/// Converts glyph coverage into a signed distance field padded by `spread`
+/// texels: 0.5 on the outline, rising to 1 `spread` texels inside
+fn distance_field(coverage: &[u8], width: u32, height: u32, spread: u32) -> Vec<u8> {
+    let spread = spread.max(1) as i32;
+    let (width, height) = (width as i32, height as i32);
+    let inside = |x: i32, y: i32| {
+        x >= 0 && y >= 0 && x < width && y < height && coverage[(y * width + x) as usize] >= 128
+    };
+
+    let (out_width, out_height) = (width + 2 * spread, height + 2 * spread);
+    let mut field = Vec::with_capacity((out_width * out_height) as usize);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let (gx, gy) = (x - spread, y - spread);
+            let is_inside = inside(gx, gy);
+
+            // Nearest texel on the other side of the outline within the spread
+            let mut nearest = (spread * spread) as f32;
+            for dy in -spread..=spread {
+                for dx in -spread..=spread {
+                    let distance = (dx * dx + dy * dy) as f32;
+                    if distance < nearest && inside(gx + dx, gy + dy) != is_inside {
+                        nearest = distance;
+                    }
+                }
+            }
+
+            let distance = (nearest.sqrt() - 0.5).max(0.0);
+            let signed = if is_inside { distance } else { -distance };
+            let value = 0.5 + signed / (2.0 * spread as f32);
+            field.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    field
+}
+
+/// Interleaved glyph vertex
+/// # Attributes
+/// * `layout (location = 0)` - position
+/// * `layout (location = 1)` - atlas coordinates
+/// * `layout (location = 2)` - colour
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TextVertex {
+    position: Vec3,
+    uv: Vec2,
+    color: Vec4,
+}
+
+/// Where queued text is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextSpace {
+    /// Top-left corner in window pixels, y down
+    Screen(Vec2),
+    /// In the scene: the text block's top-left corner at the origin of
+    /// `transform`, x right and y up, `units_per_pixel` world units per pixel
+    World { transform: Mat4, units_per_pixel: f32 },
+}
+
+/// Glyph quads sharing an atlas and a space, drawn with one call
+struct Batch {
+    atlas: Rc<Texture2D>,
+    sdf: bool,
+    world: bool,
+    vertices: Vec<TextVertex>,
+}
+
+/// Collects text for a frame and draws it in as few calls as possible:
+/// one per font and space.
+///
+/// Screen text is drawn on top of everything; world text is depth tested
+/// against the scene without writing depth.
+pub struct TextRenderer {
+    gl: gl::Gl,
+    program: Program,
+    vao: VertexArray,
+    vbo: ArrayBuffer,
+    batches: Vec<Batch>,
+}
+
+impl TextRenderer {
+    pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Self, Error> {
+        let program = Program::from_resources(gl, res, "text")?;
+        program.set_uniform("u_atlas", &0);
+
+        let vbo = ArrayBuffer::new(gl);
+        let vao = VertexArray::new(gl);
+        vao.bind();
+        vbo.bind();
+        let stride = std::mem::size_of::<TextVertex>();
+        let attributes: [(GLuint, GLint, usize); 3] = [
+            (0, 3, std::mem::offset_of!(TextVertex, position)),
+            (1, 2, std::mem::offset_of!(TextVertex, uv)),
+            (2, 4, std::mem::offset_of!(TextVertex, color)),
+        ];
+        for (location, size, offset) in attributes {
+            unsafe {
+                gl.EnableVertexAttribArray(location);
+                gl.VertexAttribPointer(
+                    location,
+                    size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as GLsizei,
+                    offset as *const GLvoid,
+                );
+            }
+        }
+        vao.unbind();
+        vbo.unbind();
+
+        Ok(TextRenderer {
+            gl: gl.clone(),
+            program,
+            vao,
+            vbo,
+            batches: Vec::new(),
+        })
+    }
+
+    /// Lays out `text` and queues it for the next `flush`
+    pub fn queue(&mut self, font: &mut Font, text: &str, space: TextSpace, style: &TextStyle) {
+        let layout = font.layout(text, style);
+        self.queue_layout(font, &layout, space, style.color);
+    }
+
+    /// Queues a layout made with `font`, e.g. one kept from an earlier frame
+    pub fn queue_layout(&mut self, font: &Font, layout: &TextLayout, space: TextSpace, color: Vec4) {
+        let world = matches!(space, TextSpace::World { .. });
+        let to_space = |p: Vec2| match space {
+            TextSpace::Screen(origin) => Vec3::new(origin.x + p.x, origin.y + p.y, 0.0),
+            TextSpace::World {
+                transform,
+                units_per_pixel,
+            } => transform.transform_point3(Vec3::new(p.x, -p.y, 0.0) * units_per_pixel),
+        };
+
+        let index = match self
+            .batches
+            .iter()
+            .position(|batch| batch.world == world && Rc::ptr_eq(&batch.atlas, &font.atlas))
+        {
+            Some(index) => index,
+            None => {
+                self.batches.push(Batch {
+                    atlas: font.atlas.clone(),
+                    sdf: matches!(font.mode, GlyphMode::Sdf { .. }),
+                    world,
+                    vertices: Vec::new(),
+                });
+                self.batches.len() - 1
+            }
+        };
+
+        let vertices = &mut self.batches[index].vertices;
+        for glyph in &layout.glyphs {
+            let corners = [
+                (Vec2::new(glyph.min.x, glyph.min.y), Vec2::new(glyph.uv_min.x, glyph.uv_min.y)),
+                (Vec2::new(glyph.max.x, glyph.min.y), Vec2::new(glyph.uv_max.x, glyph.uv_min.y)),
+                (Vec2::new(glyph.max.x, glyph.max.y), Vec2::new(glyph.uv_max.x, glyph.uv_max.y)),
+                (Vec2::new(glyph.min.x, glyph.max.y), Vec2::new(glyph.uv_min.x, glyph.uv_max.y)),
+            ];
+            for corner in [0, 1, 2, 0, 2, 3] {
+                let (position, uv) = corners[corner];
+                vertices.push(TextVertex {
+                    position: to_space(position),
+                    uv,
+                    color,
+                });
+            }
+        }
+    }
+
+    /// Draws and clears the queued text. Screen text covers the viewport of
+    /// `camera`, world text is seen through it.
+    pub fn flush(&mut self, cache: &mut StateCache, camera: &Camera) {
+        let (width, height) = camera.viewport();
+        let screen = Mat4::orthographic(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        let view_projection = camera.view_projection();
+
+        self.program.set_used();
+        self.vao.bind();
+        self.vbo.bind();
+        // World text first, so overlays end up on top of it
+        self.batches.sort_by_key(|batch| !batch.world);
+        for batch in self.batches.drain(..) {
+            if batch.vertices.is_empty() {
+                continue;
+            }
+            cache.apply(&if batch.world {
+                RenderState::transparent()
+            } else {
+                RenderState::overlay()
+            });
+            self.program.set_uniform(
+                "u_transform",
+                if batch.world { &view_projection } else { &screen },
+            );
+            self.program.set_uniform("u_sdf", &batch.sdf);
+            batch.atlas.bind(0);
+
+            self.vbo.dynamic_draw_data(&batch.vertices);
+            unsafe {
+                self.gl
+                    .DrawArrays(gl::TRIANGLES, 0, batch.vertices.len() as GLsizei)
+            };
+        }
+        self.vbo.unbind();
+        self.vao.unbind();
+    }
+}
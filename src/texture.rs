@@ -0,0 +1,675 @@
+use crate::image::ImageBuffer;
+use gl::types::*;
+
+/// Internal format of a texture along with the pixel transfer format and type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureFormat {
+    pub internal: GLenum,
+    pub format: GLenum,
+    pub kind: GLenum,
+}
+
+impl TextureFormat {
+    pub const R8: TextureFormat = TextureFormat::new(gl::R8, gl::RED, gl::UNSIGNED_BYTE);
+    pub const RG8: TextureFormat = TextureFormat::new(gl::RG8, gl::RG, gl::UNSIGNED_BYTE);
+    pub const RGB8: TextureFormat = TextureFormat::new(gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE);
+    pub const RGBA8: TextureFormat = TextureFormat::new(gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE);
+    pub const SRGB8: TextureFormat = TextureFormat::new(gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE);
+    pub const SRGB8_ALPHA8: TextureFormat =
+        TextureFormat::new(gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE);
+    pub const R16F: TextureFormat = TextureFormat::new(gl::R16F, gl::RED, gl::FLOAT);
+    pub const RG16F: TextureFormat = TextureFormat::new(gl::RG16F, gl::RG, gl::FLOAT);
+    pub const RGB16F: TextureFormat = TextureFormat::new(gl::RGB16F, gl::RGB, gl::FLOAT);
+    pub const RGBA16F: TextureFormat = TextureFormat::new(gl::RGBA16F, gl::RGBA, gl::FLOAT);
+    pub const R32F: TextureFormat = TextureFormat::new(gl::R32F, gl::RED, gl::FLOAT);
+    pub const RGBA32F: TextureFormat = TextureFormat::new(gl::RGBA32F, gl::RGBA, gl::FLOAT);
+    pub const DEPTH24: TextureFormat =
+        TextureFormat::new(gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT);
+    pub const DEPTH32F: TextureFormat =
+        TextureFormat::new(gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT);
+    pub const DEPTH24_STENCIL8: TextureFormat = TextureFormat::new(
+        gl::DEPTH24_STENCIL8,
+        gl::DEPTH_STENCIL,
+        gl::UNSIGNED_INT_24_8,
+    );
+
+    pub const fn new(internal: GLenum, format: GLenum, kind: GLenum) -> Self {
+        TextureFormat {
+            internal,
+            format,
+            kind,
+        }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        self.format == gl::DEPTH_COMPONENT || self.format == gl::DEPTH_STENCIL
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        self.format == gl::DEPTH_STENCIL
+    }
+
+    /// Components per pixel in the transfer format
+    pub fn channels(&self) -> u32 {
+        match self.format {
+            gl::RG => 2,
+            gl::RGB => 3,
+            gl::RGBA => 4,
+            _ => 1,
+        }
+    }
+}
+
+/// Wrapper for OpenGL 2D textures, optionally multisampled
+pub struct Texture2D {
+    gl: gl::Gl,
+    id: GLuint,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    samples: u32,
+}
+
+impl Texture2D {
+    /// Allocates an uninitialized texture with linear filtering and edge clamping
+    pub fn new(gl: &gl::Gl, width: u32, height: u32, format: TextureFormat) -> Self {
+        let texture = Texture2D::create(gl, width, height, format, 0);
+        texture.upload::<u8>(None);
+        texture.set_filter(gl::LINEAR, gl::LINEAR);
+        texture.set_wrap(gl::CLAMP_TO_EDGE, gl::CLAMP_TO_EDGE);
+        texture
+    }
+
+    /// Allocates a `GL_TEXTURE_2D_MULTISAMPLE` texture with `samples` samples
+    pub fn new_multisample(
+        gl: &gl::Gl,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        samples: u32,
+    ) -> Self {
+        let texture = Texture2D::create(gl, width, height, format, samples);
+        texture.bind(0);
+        unsafe {
+            gl.TexImage2DMultisample(
+                gl::TEXTURE_2D_MULTISAMPLE,
+                samples as GLsizei,
+                format.internal,
+                width as GLsizei,
+                height as GLsizei,
+                gl::TRUE,
+            );
+            gl.BindTexture(gl::TEXTURE_2D_MULTISAMPLE, 0);
+        }
+        texture
+    }
+
+    /// Uploads an 8-bit image with 1 to 4 channels, rows ordered bottom-up
+    pub fn from_image(gl: &gl::Gl, image: &ImageBuffer<u8>) -> Self {
+        Texture2D::from_image_as(gl, image, unorm_format(image.channels), true)
+    }
+
+    /// Uploads an 8-bit image like `from_image`, but with plain linear filtering
+    /// and no mipmaps, which would blend the padding between atlas regions
+    pub fn from_image_without_mipmaps(gl: &gl::Gl, image: &ImageBuffer<u8>) -> Self {
+        Texture2D::from_image_as(gl, image, unorm_format(image.channels), false)
+    }
+
+    /// Uploads an 8-bit RGB or RGBA image holding sRGB encoded colors,
+    /// so sampling returns linear values
+    pub fn from_image_srgb(gl: &gl::Gl, image: &ImageBuffer<u8>) -> Self {
+        let format = match image.channels {
+            3 => TextureFormat::SRGB8,
+            4 => TextureFormat::SRGB8_ALPHA8,
+            channels => panic!("sRGB textures need 3 or 4 channels, got {}", channels),
+        };
+        Texture2D::from_image_as(gl, image, format, true)
+    }
+
+    fn from_image_as(
+        gl: &gl::Gl,
+        image: &ImageBuffer<u8>,
+        format: TextureFormat,
+        mipmaps: bool,
+    ) -> Self {
+        let texture = Texture2D::create(gl, image.width, image.height, format, 0);
+        with_pixel_store(gl, gl::UNPACK_ALIGNMENT, 1, || texture.upload(Some(&image.data)));
+        texture.set_wrap(gl::REPEAT, gl::REPEAT);
+        if mipmaps {
+            texture.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+            texture.generate_mipmaps();
+        } else {
+            texture.set_filter(gl::LINEAR, gl::LINEAR);
+        }
+        texture
+    }
+
+    /// Uploads a floating point image with 1 to 4 channels, rows ordered bottom-up
+    pub fn from_image_f32(gl: &gl::Gl, image: &ImageBuffer<f32>) -> Self {
+        let format = match image.channels {
+            1 => TextureFormat::R32F,
+            2 => TextureFormat::RG16F,
+            3 => TextureFormat::RGB16F,
+            _ => TextureFormat::RGBA32F,
+        };
+
+        let texture = Texture2D::create(gl, image.width, image.height, format, 0);
+        texture.upload(Some(&image.data));
+        texture.set_filter(gl::LINEAR, gl::LINEAR);
+        texture.set_wrap(gl::CLAMP_TO_EDGE, gl::CLAMP_TO_EDGE);
+        texture
+    }
+
+    fn create(gl: &gl::Gl, width: u32, height: u32, format: TextureFormat, samples: u32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe { gl.GenTextures(1, &mut id) };
+
+        Texture2D {
+            gl: gl.clone(),
+            id,
+            width,
+            height,
+            format,
+            samples,
+        }
+    }
+
+    fn upload<T>(&self, data: Option<&[T]>) {
+        self.bind(0);
+        unsafe {
+            self.gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.format.internal as GLint,
+                self.width as GLsizei,
+                self.height as GLsizei,
+                0,
+                self.format.format,
+                self.format.kind,
+                data.map_or(std::ptr::null(), |d| d.as_ptr() as *const GLvoid),
+            );
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// `GL_TEXTURE_2D` or `GL_TEXTURE_2D_MULTISAMPLE`
+    pub fn target(&self) -> GLenum {
+        if self.samples > 0 {
+            gl::TEXTURE_2D_MULTISAMPLE
+        } else {
+            gl::TEXTURE_2D
+        }
+    }
+
+    /// Binds the texture to texture unit `unit`
+    pub fn bind(&self, unit: GLuint) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(self.target(), self.id);
+        }
+    }
+
+    pub fn set_filter(&self, min: GLenum, mag: GLenum) {
+        self.bind(0);
+        unsafe {
+            self.gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min as GLint);
+            self.gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag as GLint);
+        }
+    }
+
+    pub fn set_wrap(&self, s: GLenum, t: GLenum) {
+        self.bind(0);
+        unsafe {
+            self.gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, s as GLint);
+            self.gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, t as GLint);
+        }
+    }
+
+    /// Replaces a `width` x `height` block of level 0 starting at texel `(x, y)`
+    /// with tightly packed 8-bit pixels in the texture's transfer format
+    pub fn upload_region(&self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        assert_eq!(
+            data.len(),
+            (width * height * self.format.channels()) as usize,
+            "region data does not match its size"
+        );
+        with_pixel_store(&self.gl, gl::UNPACK_ALIGNMENT, 1, || unsafe {
+            self.gl.TextureSubImage2D(
+                self.id,
+                0,
+                x as GLint,
+                y as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                self.format.format,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const GLvoid,
+            );
+        });
+    }
+
+    /// Reads level 0 back as floats, with the channels of the texture format
+    pub fn read_f32(&self) -> ImageBuffer<f32> {
+        let mut image = ImageBuffer::new(self.width, self.height, self.format.channels());
+        with_pixel_store(&self.gl, gl::PACK_ALIGNMENT, 1, || unsafe {
+            self.gl.GetTextureImage(
+                self.id,
+                0,
+                self.format.format,
+                gl::FLOAT,
+                (image.data.len() * std::mem::size_of::<f32>()) as GLsizei,
+                image.data.as_mut_ptr() as *mut GLvoid,
+            );
+        });
+        image
+    }
+
+    /// Applies the filtering and wrapping of `sampler` to the texture itself
+    pub fn set_sampler(&self, sampler: &SamplerDesc) {
+        self.set_filter(sampler.min_filter, sampler.mag_filter);
+        self.set_wrap(sampler.wrap_s, sampler.wrap_t);
+    }
+
+    pub fn generate_mipmaps(&self) {
+        self.bind(0);
+        unsafe { self.gl.GenerateMipmap(gl::TEXTURE_2D) };
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &self.id) };
+    }
+}
+
+/// Wrapper for OpenGL cube map textures with square faces and a mip chain
+pub struct TextureCube {
+    gl: gl::Gl,
+    id: GLuint,
+    size: u32,
+    format: TextureFormat,
+    levels: u32,
+}
+
+impl TextureCube {
+    /// Allocates `levels` mip levels of every face, with trilinear filtering
+    /// and edge clamping
+    pub fn new(gl: &gl::Gl, size: u32, format: TextureFormat, levels: u32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut id);
+            gl.BindTexture(gl::TEXTURE_CUBE_MAP, id);
+            for level in 0..levels {
+                let level_size = (size >> level).max(1) as GLsizei;
+                for face in 0..6 {
+                    gl.TexImage2D(
+                        gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                        level as GLint,
+                        format.internal as GLint,
+                        level_size,
+                        level_size,
+                        0,
+                        format.format,
+                        format.kind,
+                        std::ptr::null(),
+                    );
+                }
+            }
+
+            let min_filter = if levels > 1 {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            };
+            let parameters = [
+                (gl::TEXTURE_MIN_FILTER, min_filter),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_MAX_LEVEL, levels.max(1) - 1),
+            ];
+            for (name, value) in parameters {
+                gl.TexParameteri(gl::TEXTURE_CUBE_MAP, name, value as GLint);
+            }
+        }
+
+        TextureCube {
+            gl: gl.clone(),
+            id,
+            size,
+            format,
+            levels,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Edge length of the level 0 faces
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn level_size(&self, level: u32) -> u32 {
+        (self.size >> level).max(1)
+    }
+
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+
+    /// Uploads floats with the channels of the texture format to
+    /// `face` (0..6 in `+X, -X, +Y, -Y, +Z, -Z` order) at `level`
+    pub fn upload_face(&self, face: u32, level: u32, data: &[f32]) {
+        let size = self.level_size(level);
+        assert_eq!(
+            data.len(),
+            (size * size * self.format.channels()) as usize,
+            "face data size mismatch"
+        );
+
+        self.bind(0);
+        with_pixel_store(&self.gl, gl::UNPACK_ALIGNMENT, 1, || unsafe {
+            self.gl.TexSubImage2D(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                level as GLint,
+                0,
+                0,
+                size as GLsizei,
+                size as GLsizei,
+                self.format.format,
+                gl::FLOAT,
+                data.as_ptr() as *const GLvoid,
+            );
+        });
+    }
+
+    /// Reads `face` at `level` back as floats with the channels of the texture format
+    pub fn read_face(&self, face: u32, level: u32) -> Vec<f32> {
+        let size = self.level_size(level);
+        let mut data = vec![0.0f32; (size * size * self.format.channels()) as usize];
+
+        self.bind(0);
+        with_pixel_store(&self.gl, gl::PACK_ALIGNMENT, 1, || unsafe {
+            self.gl.GetTexImage(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                level as GLint,
+                self.format.format,
+                gl::FLOAT,
+                data.as_mut_ptr() as *mut GLvoid,
+            );
+        });
+        data
+    }
+
+    pub fn generate_mipmaps(&self) {
+        self.bind(0);
+        unsafe { self.gl.GenerateMipmap(gl::TEXTURE_CUBE_MAP) };
+    }
+}
+
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &self.id) };
+    }
+}
+
+/// Wrapper for OpenGL 2D array and cube map array textures with square layers,
+/// e.g. the depth maps of several shadow casting lights
+pub struct TextureArray {
+    gl: gl::Gl,
+    id: GLuint,
+    target: GLenum,
+    size: u32,
+    layers: u32,
+    format: TextureFormat,
+}
+
+impl TextureArray {
+    /// Allocates `layers` layers of a `TEXTURE_2D_ARRAY`, with linear filtering
+    /// and edge clamping
+    pub fn new_2d(gl: &gl::Gl, size: u32, layers: u32, format: TextureFormat) -> Self {
+        TextureArray::allocate(gl, gl::TEXTURE_2D_ARRAY, size, layers, format)
+    }
+
+    /// Allocates `count` cube maps of a `TEXTURE_CUBE_MAP_ARRAY`; layer
+    /// `cube * 6 + face` holds `face` of `cube`
+    pub fn new_cube(gl: &gl::Gl, size: u32, count: u32, format: TextureFormat) -> Self {
+        TextureArray::allocate(gl, gl::TEXTURE_CUBE_MAP_ARRAY, size, count * 6, format)
+    }
+
+    fn allocate(gl: &gl::Gl, target: GLenum, size: u32, layers: u32, format: TextureFormat) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut id);
+            gl.BindTexture(target, id);
+            gl.TexImage3D(
+                target,
+                0,
+                format.internal as GLint,
+                size as GLsizei,
+                size as GLsizei,
+                layers as GLsizei,
+                0,
+                format.format,
+                format.kind,
+                std::ptr::null(),
+            );
+
+            let parameters = [
+                (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+                (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+                (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+                (gl::TEXTURE_MAX_LEVEL, 0),
+            ];
+            for (name, value) in parameters {
+                gl.TexParameteri(target, name, value as GLint);
+            }
+        }
+
+        TextureArray {
+            gl: gl.clone(),
+            id,
+            target,
+            size,
+            layers,
+            format,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// `TEXTURE_2D_ARRAY` or `TEXTURE_CUBE_MAP_ARRAY`
+    pub fn target(&self) -> GLenum {
+        self.target
+    }
+
+    /// Edge length of every layer
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Number of 2D layers, six per cube map
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(self.target, self.id);
+        }
+    }
+
+    /// Enables `LEQUAL` depth comparison, for sampling a depth format through
+    /// shadow samplers; disabled, the raw depth is returned
+    pub fn set_depth_compare(&self, enabled: bool) {
+        let mode = if enabled {
+            gl::COMPARE_REF_TO_TEXTURE
+        } else {
+            gl::NONE
+        };
+        unsafe {
+            self.gl.BindTexture(self.target, self.id);
+            self.gl
+                .TexParameteri(self.target, gl::TEXTURE_COMPARE_MODE, mode as GLint);
+            self.gl
+                .TexParameteri(self.target, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as GLint);
+        }
+    }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &self.id) };
+    }
+}
+
+/// Filtering and wrapping settings as GL enums
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+
+impl SamplerDesc {
+    /// Trilinear filtering with repeat, for mipmapped material textures
+    pub const TRILINEAR_REPEAT: SamplerDesc = SamplerDesc {
+        min_filter: gl::LINEAR_MIPMAP_LINEAR,
+        mag_filter: gl::LINEAR,
+        wrap_s: gl::REPEAT,
+        wrap_t: gl::REPEAT,
+    };
+    pub const LINEAR_CLAMP: SamplerDesc = SamplerDesc {
+        min_filter: gl::LINEAR,
+        mag_filter: gl::LINEAR,
+        wrap_s: gl::CLAMP_TO_EDGE,
+        wrap_t: gl::CLAMP_TO_EDGE,
+    };
+    pub const NEAREST_CLAMP: SamplerDesc = SamplerDesc {
+        min_filter: gl::NEAREST,
+        mag_filter: gl::NEAREST,
+        wrap_s: gl::CLAMP_TO_EDGE,
+        wrap_t: gl::CLAMP_TO_EDGE,
+    };
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc::TRILINEAR_REPEAT
+    }
+}
+
+/// Wrapper for OpenGL sampler objects.
+/// A bound sampler overrides the parameters of the texture on the same unit.
+pub struct Sampler {
+    gl: gl::Gl,
+    id: GLuint,
+    desc: SamplerDesc,
+}
+
+impl Sampler {
+    pub fn new(gl: &gl::Gl, desc: SamplerDesc) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl.GenSamplers(1, &mut id);
+            gl.SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, desc.min_filter as GLint);
+            gl.SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, desc.mag_filter as GLint);
+            gl.SamplerParameteri(id, gl::TEXTURE_WRAP_S, desc.wrap_s as GLint);
+            gl.SamplerParameteri(id, gl::TEXTURE_WRAP_T, desc.wrap_t as GLint);
+        }
+
+        Sampler {
+            gl: gl.clone(),
+            id,
+            desc,
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn desc(&self) -> &SamplerDesc {
+        &self.desc
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        unsafe { self.gl.BindSampler(unit, self.id) };
+    }
+
+    pub fn unbind(&self, unit: GLuint) {
+        unsafe { self.gl.BindSampler(unit, 0) };
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteSamplers(1, &self.id) };
+    }
+}
+
+/// 8-bit normalized format with `channels` channels, RGBA for anything above 3
+fn unorm_format(channels: u32) -> TextureFormat {
+    match channels {
+        1 => TextureFormat::R8,
+        2 => TextureFormat::RG8,
+        3 => TextureFormat::RGB8,
+        _ => TextureFormat::RGBA8,
+    }
+}
+
+/// Runs `f` with the pixel store parameter `name` set to `value`,
+/// then restores the value that was set before
+pub fn with_pixel_store<R>(gl: &gl::Gl, name: GLenum, value: GLint, f: impl FnOnce() -> R) -> R {
+    let mut previous: GLint = 0;
+    unsafe {
+        gl.GetIntegerv(name, &mut previous);
+        gl.PixelStorei(name, value);
+    }
+    let result = f();
+    unsafe { gl.PixelStorei(name, previous) };
+    result
+}
@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+/// Source of the time driving `app::run`
+pub trait Clock {
+    /// Time elapsed since an arbitrary fixed start
+    fn now(&mut self) -> Duration;
+}
+
+/// Real time, measured from its creation
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Simulated time that only moves when told to, for stepping a loop
+/// deterministically without a window
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    time: Duration,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock::default()
+    }
+
+    pub fn advance(&mut self, duration: Duration) {
+        self.time += duration;
+    }
+
+    pub fn set(&mut self, time: Duration) {
+        self.time = time;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&mut self) -> Duration {
+        self.time
+    }
+}
+
+/// How `app::run` advances the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// One `App::update` per rendered frame with the measured frame time
+    #[default]
+    Variable,
+    /// `App::fixed_update` at a constant rate, as many times per frame as
+    /// the elapsed time requires, up to `max_steps`
+    Fixed { ticks_per_second: u32, max_steps: u32 },
+}
+
+/// Accumulates elapsed time into whole simulation ticks of constant length.
+///
+/// When a frame took longer than `max_steps` ticks, the remaining time is
+/// dropped instead of carried over, so a slow simulation slows down rather
+/// than falling further behind every frame.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    last: Option<Duration>,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32, max_steps: u32) -> Self {
+        assert!(ticks_per_second > 0, "fixed timestep needs at least one tick per second");
+        FixedTimestep {
+            step: Duration::from_secs(1) / ticks_per_second,
+            max_steps: max_steps.max(1),
+            accumulator: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    /// Length of a tick
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the time passed since the previous call (none on the first) and
+    /// returns the number of ticks to simulate now
+    pub fn advance(&mut self, now: Duration) -> u32 {
+        if let Some(last) = self.last {
+            self.accumulator += now.saturating_sub(last);
+        }
+        self.last = Some(now);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if steps == self.max_steps && self.accumulator >= self.step {
+            // Spiral of death: give up on catching up
+            self.accumulator = Duration::from_nanos(
+                (self.accumulator.as_nanos() % self.step.as_nanos()) as u64,
+            );
+        }
+        steps
+    }
+
+    /// Fraction of a tick accumulated but not simulated yet, for
+    /// interpolating between the last two simulated states when rendering
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn assert_alpha(timestep: &FixedTimestep, expected: f32) {
+        assert!(
+            (timestep.alpha() - expected).abs() < 1e-4,
+            "alpha {} != {}",
+            timestep.alpha(),
+            expected
+        );
+    }
+
+    #[test]
+    fn ticks_for_elapsed_time() {
+        let mut timestep = FixedTimestep::new(100, 10);
+        assert_eq!(timestep.step(), ms(10));
+
+        // The first call only records the start time
+        assert_eq!(timestep.advance(ms(500)), 0);
+        assert_eq!(timestep.advance(ms(530)), 3);
+        assert_eq!(timestep.advance(ms(530)), 0);
+        assert_eq!(timestep.advance(ms(540)), 1);
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut timestep = FixedTimestep::new(100, 10);
+        timestep.advance(ms(0));
+
+        assert_eq!(timestep.advance(ms(25)), 2);
+        assert_alpha(&timestep, 0.5);
+        // 5ms left over plus 7ms make one more tick with 2ms to spare
+        assert_eq!(timestep.advance(ms(32)), 1);
+        assert_alpha(&timestep, 0.2);
+        assert_eq!(timestep.advance(ms(39)), 0);
+        assert_alpha(&timestep, 0.9);
+        assert_eq!(timestep.advance(ms(41)), 1);
+        assert_alpha(&timestep, 0.1);
+    }
+
+    #[test]
+    fn max_steps_drops_the_backlog() {
+        let mut timestep = FixedTimestep::new(100, 4);
+        timestep.advance(ms(0));
+
+        // A one second hitch only runs max_steps ticks
+        assert_eq!(timestep.advance(ms(1003)), 4);
+        assert_alpha(&timestep, 0.3);
+        // ...and the missing 96 ticks are not made up later
+        assert_eq!(timestep.advance(ms(1010)), 1);
+        assert_alpha(&timestep, 0.0);
+        assert_eq!(timestep.advance(ms(1020)), 1);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut timestep = FixedTimestep::new(60, 5);
+        let mut clock = ManualClock::new();
+        timestep.advance(clock.now());
+
+        // Frame times that keep landing at different phases of a tick,
+        // including hitches longer than max_steps ticks
+        for i in 0..500u64 {
+            clock.advance(Duration::from_micros(1_000 + i * 7_919 % 120_000));
+            let steps = timestep.advance(clock.now());
+            assert!(steps <= 5);
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha {} out of range", alpha);
+        }
+    }
+}
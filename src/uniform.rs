@@ -0,0 +1,123 @@
+use crate::color::Color;
+use crate::math::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use gl::types::*;
+
+/// Values that can be written to a GLSL uniform of the matching type
+pub trait Uniform {
+    /// Writes the value to `location` of `program` (`glProgramUniform*`),
+    /// so the program does not need to be bound.
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint);
+}
+
+impl Uniform for f32 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform1f(program, location, *self) };
+    }
+}
+
+impl Uniform for i32 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform1i(program, location, *self) };
+    }
+}
+
+impl Uniform for u32 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform1ui(program, location, *self) };
+    }
+}
+
+impl Uniform for bool {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform1i(program, location, *self as GLint) };
+    }
+}
+
+impl Uniform for Vec2 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform2fv(program, location, 1, self.as_ptr()) };
+    }
+}
+
+impl Uniform for Vec3 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform3fv(program, location, 1, self.as_ptr()) };
+    }
+}
+
+impl Uniform for Vec4 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform4fv(program, location, 1, self.as_ptr()) };
+    }
+}
+
+/// Linear components, as shaders expect
+impl Uniform for Color {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform4fv(program, location, 1, &self.r) };
+    }
+}
+
+impl Uniform for Mat3 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniformMatrix3fv(program, location, 1, gl::FALSE, self.as_ptr()) };
+    }
+}
+
+impl Uniform for Mat4 {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniformMatrix4fv(program, location, 1, gl::FALSE, self.as_ptr()) };
+    }
+}
+
+impl Uniform for [f32; 2] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        Vec2::from(*self).set_uniform(gl, program, location);
+    }
+}
+
+impl Uniform for [f32; 3] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        Vec3::from(*self).set_uniform(gl, program, location);
+    }
+}
+
+impl Uniform for [f32; 4] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        Vec4::from(*self).set_uniform(gl, program, location);
+    }
+}
+
+/// Uniform arrays, e.g. `uniform mat4 u_Bones[64]`
+impl Uniform for [Mat4] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe {
+            gl.ProgramUniformMatrix4fv(
+                program,
+                location,
+                self.len() as GLsizei,
+                gl::FALSE,
+                self.as_ptr() as *const GLfloat,
+            )
+        };
+    }
+}
+
+impl Uniform for [Vec4] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe {
+            gl.ProgramUniform4fv(
+                program,
+                location,
+                self.len() as GLsizei,
+                self.as_ptr() as *const GLfloat,
+            )
+        };
+    }
+}
+
+impl Uniform for [f32] {
+    fn set_uniform(&self, gl: &gl::Gl, program: GLuint, location: GLint) {
+        unsafe { gl.ProgramUniform1fv(program, location, self.len() as GLsizei, self.as_ptr()) };
+    }
+}